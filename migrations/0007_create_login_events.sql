@@ -0,0 +1,20 @@
+-- Login events table (history of sign-in attempts per user)
+
+CREATE TYPE auth_method AS ENUM ('password', 'adminpassword', 'adminpanel');
+
+CREATE TABLE login_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    success BOOLEAN NOT NULL,
+    failure_reason VARCHAR(100),
+    ip_address VARCHAR(45),
+    user_agent TEXT,
+    auth_method auth_method NOT NULL,
+    device_fingerprint VARCHAR(64),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Indexes for per-user history and known-device lookups
+CREATE INDEX idx_login_events_user_id ON login_events(user_id, created_at DESC);
+CREATE INDEX idx_login_events_fingerprint ON login_events(user_id, device_fingerprint);
@@ -0,0 +1,13 @@
+-- Soft delete for users. Deleted rows keep their data until the retention
+-- job removes them, and no longer reserve their email or username: the
+-- unique constraints become partial indexes over live rows. The indexes keep
+-- the constraint names so duplicate errors are reported the same way.
+
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ;
+
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+ALTER TABLE users DROP CONSTRAINT users_username_key;
+
+CREATE UNIQUE INDEX users_email_key ON users(email) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX users_username_key ON users(username) WHERE deleted_at IS NULL;
+CREATE INDEX idx_users_deleted_at ON users(deleted_at) WHERE deleted_at IS NOT NULL;
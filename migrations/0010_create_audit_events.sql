@@ -0,0 +1,43 @@
+-- Audit log. Rows are hash-chained (see `audit::model`): `hash` covers the
+-- row's contents and the previous row's hash, so changing or removing a row
+-- breaks the chain. The triggers make the table append-only for good measure.
+-- before_json/after_json hold the exact JSON text that was hashed.
+
+CREATE TABLE audit_events (
+    seq BIGINT PRIMARY KEY,
+    id UUID UNIQUE NOT NULL,
+    actor_id UUID,
+    action VARCHAR(100) NOT NULL,
+    target_type VARCHAR(50) NOT NULL,
+    target_id VARCHAR(255),
+    before_json TEXT,
+    after_json TEXT,
+    ip_address VARCHAR(45),
+    request_id VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL,
+    prev_hash CHAR(64) NOT NULL,
+    hash CHAR(64) UNIQUE NOT NULL
+);
+
+-- Indexes for the viewer's filters
+CREATE INDEX idx_audit_events_actor_id ON audit_events(actor_id, seq DESC);
+CREATE INDEX idx_audit_events_target ON audit_events(target_type, target_id, seq DESC);
+CREATE INDEX idx_audit_events_action ON audit_events(action, seq DESC);
+CREATE INDEX idx_audit_events_created_at ON audit_events(created_at);
+
+CREATE OR REPLACE FUNCTION audit_events_append_only()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER audit_events_no_update_or_delete
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW
+    EXECUTE FUNCTION audit_events_append_only();
+
+CREATE TRIGGER audit_events_no_truncate
+    BEFORE TRUNCATE ON audit_events
+    FOR EACH STATEMENT
+    EXECUTE FUNCTION audit_events_append_only();
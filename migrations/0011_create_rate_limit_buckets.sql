@@ -0,0 +1,10 @@
+-- Rate limit buckets shared between instances (see `rate_limit`). A bucket
+-- is stored as the time it will next be full; rows past that time are
+-- equivalent to no row and are pruned.
+
+CREATE TABLE rate_limit_buckets (
+    key VARCHAR(255) PRIMARY KEY,
+    full_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_rate_limit_buckets_full_at ON rate_limit_buckets(full_at);
@@ -0,0 +1,16 @@
+-- Idempotency keys (see `idempotency`). status/headers/body are NULL while
+-- the first request runs; expires_at is then its lock timeout, and after it
+-- completes the end of the replay window.
+
+CREATE TABLE idempotency_keys (
+    scope VARCHAR(255) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    fingerprint CHAR(64) NOT NULL,
+    status SMALLINT,
+    headers TEXT,
+    body BYTEA,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (scope, key)
+);
+
+CREATE INDEX idx_idempotency_keys_expires_at ON idempotency_keys(expires_at);
@@ -0,0 +1,11 @@
+-- SQLite has no ENUM types. Enum columns are stored as TEXT and constrained
+-- with CHECK clauses in the table migrations, using the same lowercase values
+-- as the PostgreSQL types:
+--
+--   account_level:   free, premium, enterprise
+--   account_status:  active, pending, suspended, banned, deactivated
+--   admin_role:      superadmin, admin, moderator
+--   token_type:      access, refresh, adminaccess, adminrefresh
+--   validation_type: emailverification, passwordreset, twofactorauth, admininvite, accountactivation
+
+SELECT 1;
@@ -0,0 +1,30 @@
+-- User accounts table (stores account level, status, and capabilities)
+-- capabilities is a JSON array of strings.
+
+CREATE TABLE user_accounts (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT UNIQUE NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    account_level TEXT NOT NULL DEFAULT 'free'
+        CHECK (account_level IN ('free', 'premium', 'enterprise')),
+    account_status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (account_status IN ('active', 'pending', 'suspended', 'banned', 'deactivated')),
+    capabilities TEXT NOT NULL DEFAULT '[]',
+    status_reason TEXT,
+    status_changed_at TEXT,
+    status_changed_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now'))
+);
+
+-- Index for user lookup
+CREATE INDEX idx_user_accounts_user_id ON user_accounts(user_id);
+CREATE INDEX idx_user_accounts_status ON user_accounts(account_status);
+
+-- Trigger for updated_at
+CREATE TRIGGER update_user_accounts_updated_at
+    AFTER UPDATE ON user_accounts
+    FOR EACH ROW
+    WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+    UPDATE user_accounts SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
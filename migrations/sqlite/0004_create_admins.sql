@@ -0,0 +1,26 @@
+-- Admins table
+-- permissions is a JSON array of strings.
+
+CREATE TABLE admins (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT UNIQUE NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role TEXT NOT NULL DEFAULT 'moderator'
+        CHECK (role IN ('superadmin', 'admin', 'moderator')),
+    permissions TEXT NOT NULL DEFAULT '[]',
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')),
+    created_by TEXT REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- Indexes
+CREATE INDEX idx_admins_user_id ON admins(user_id);
+CREATE INDEX idx_admins_role ON admins(role);
+
+-- Trigger for updated_at
+CREATE TRIGGER update_admins_updated_at
+    AFTER UPDATE ON admins
+    FOR EACH ROW
+    WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+    UPDATE admins SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
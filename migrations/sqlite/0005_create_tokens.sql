@@ -0,0 +1,19 @@
+-- Auth tokens table (for tracking refresh tokens and revocation)
+
+CREATE TABLE auth_tokens (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL,
+    token_type TEXT NOT NULL
+        CHECK (token_type IN ('access', 'refresh', 'adminaccess', 'adminrefresh')),
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')),
+    revoked_at TEXT,
+    device_info TEXT
+);
+
+-- Indexes for token lookup and cleanup
+CREATE INDEX idx_auth_tokens_user_id ON auth_tokens(user_id);
+CREATE INDEX idx_auth_tokens_hash ON auth_tokens(token_hash);
+CREATE INDEX idx_auth_tokens_expires ON auth_tokens(expires_at);
+CREATE INDEX idx_auth_tokens_type ON auth_tokens(token_type);
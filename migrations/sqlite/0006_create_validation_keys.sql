@@ -0,0 +1,20 @@
+-- Validation keys table (for email verification, password reset, etc.)
+-- metadata is stored as JSON text.
+
+CREATE TABLE validation_keys (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users(id) ON DELETE CASCADE,
+    key_type TEXT NOT NULL
+        CHECK (key_type IN ('emailverification', 'passwordreset', 'twofactorauth', 'admininvite', 'accountactivation')),
+    key_value TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used INTEGER NOT NULL DEFAULT 0,
+    metadata TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now'))
+);
+
+-- Indexes
+CREATE INDEX idx_validation_keys_user_id ON validation_keys(user_id);
+CREATE INDEX idx_validation_keys_value ON validation_keys(key_value);
+CREATE INDEX idx_validation_keys_type ON validation_keys(key_type);
+CREATE INDEX idx_validation_keys_expires ON validation_keys(expires_at);
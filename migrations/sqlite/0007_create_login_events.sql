@@ -0,0 +1,19 @@
+-- Login events table (history of sign-in attempts per user)
+
+CREATE TABLE login_events (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    success INTEGER NOT NULL,
+    failure_reason TEXT,
+    ip_address TEXT,
+    user_agent TEXT,
+    auth_method TEXT NOT NULL
+        CHECK (auth_method IN ('password', 'adminpassword', 'adminpanel')),
+    device_fingerprint TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now'))
+);
+
+-- Indexes for per-user history and known-device lookups
+CREATE INDEX idx_login_events_user_id ON login_events(user_id, created_at DESC);
+CREATE INDEX idx_login_events_fingerprint ON login_events(user_id, device_fingerprint);
@@ -0,0 +1,45 @@
+-- Soft delete for users. Deleted rows keep their data until the retention
+-- job removes them, and no longer reserve their email or username.
+--
+-- SQLite can't drop the column UNIQUE constraints, so the table is rebuilt.
+-- `SqliteStorage::run_migrations` turns foreign key enforcement off while
+-- migrating, so dropping the old table doesn't cascade to the tables that
+-- reference it; they point at the new table once it is renamed.
+
+CREATE TABLE users_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL,
+    password_hash TEXT NOT NULL,
+    username TEXT NOT NULL,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')),
+    last_login TEXT,
+    deleted_at TEXT
+);
+
+INSERT INTO users_new (id, email, password_hash, username, first_name, last_name,
+                       is_active, created_at, updated_at, last_login)
+SELECT id, email, password_hash, username, first_name, last_name,
+       is_active, created_at, updated_at, last_login
+FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE UNIQUE INDEX users_email_key ON users(email) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX users_username_key ON users(username) WHERE deleted_at IS NULL;
+CREATE INDEX idx_users_email ON users(email);
+CREATE INDEX idx_users_username ON users(username);
+CREATE INDEX idx_users_is_active ON users(is_active);
+CREATE INDEX idx_users_deleted_at ON users(deleted_at) WHERE deleted_at IS NOT NULL;
+
+CREATE TRIGGER update_users_updated_at
+    AFTER UPDATE ON users
+    FOR EACH ROW
+    WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+    UPDATE users SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
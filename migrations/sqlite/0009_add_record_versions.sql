@@ -0,0 +1,6 @@
+-- Optimistic concurrency: every edit to a user, account or admin row bumps
+-- its version, and updates only apply if the caller saw the latest one.
+
+ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE user_accounts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE admins ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,38 @@
+-- Audit log. Rows are hash-chained (see `audit::model`): `hash` covers the
+-- row's contents and the previous row's hash, so changing or removing a row
+-- breaks the chain. The triggers make the table append-only for good measure.
+-- before_json/after_json hold the exact JSON text that was hashed.
+
+CREATE TABLE audit_events (
+    seq INTEGER PRIMARY KEY NOT NULL,
+    id TEXT UNIQUE NOT NULL,
+    actor_id TEXT,
+    action TEXT NOT NULL,
+    target_type TEXT NOT NULL,
+    target_id TEXT,
+    before_json TEXT,
+    after_json TEXT,
+    ip_address TEXT,
+    request_id TEXT,
+    created_at TEXT NOT NULL,
+    prev_hash TEXT NOT NULL,
+    hash TEXT UNIQUE NOT NULL
+);
+
+-- Indexes for the viewer's filters
+CREATE INDEX idx_audit_events_actor_id ON audit_events(actor_id, seq DESC);
+CREATE INDEX idx_audit_events_target ON audit_events(target_type, target_id, seq DESC);
+CREATE INDEX idx_audit_events_action ON audit_events(action, seq DESC);
+CREATE INDEX idx_audit_events_created_at ON audit_events(created_at);
+
+CREATE TRIGGER audit_events_no_update
+    BEFORE UPDATE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
+
+CREATE TRIGGER audit_events_no_delete
+    BEFORE DELETE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
@@ -1,16 +1,24 @@
 use askama::Template;
 use axum::{
     extract::{Query, State},
+    http::HeaderMap,
     response::{Html, IntoResponse, Redirect, Response},
     Form,
 };
 use tower_cookies::{Cookie, Cookies};
 
 use crate::app::AppState;
-use crate::auth::model::AccountStatus;
+use crate::audit::{self, actions, AuditContext, AuditEntry, AuditEvent, AuditFilter};
+use crate::auth::history::{self, LoginContext};
+use crate::auth::model::{AccountStatus, AuthMethod};
+use crate::auth::sessions;
+use crate::routing::client_ip::ClientIp;
+use crate::routing::request_id;
+use crate::users::model::{PageRequest, UserFilter, UserSort, UserSummary};
 use super::ui::{
     AUTH_COOKIE_NAME, LoginTemplate, DashboardTemplate, UsersTemplate,
-    LoginForm, PaginationQuery,
+    LoginForm, UserRow, UsersQuery, MetricsTemplate, MetricRow, CacheRow,
+    AuditTemplate, AuditRow, AuditQuery,
 };
 
 pub async fn login_page(cookies: Cookies) -> impl IntoResponse {
@@ -22,12 +30,26 @@ pub async fn login_page(cookies: Cookies) -> impl IntoResponse {
 
 pub async fn login_submit(
     State(state): State<AppState>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
     cookies: Cookies,
     Form(form): Form<LoginForm>,
 ) -> impl IntoResponse {
+    let ctx = LoginContext::from_request(&headers, client_ip, None);
+    let method = AuthMethod::AdminPanel;
+
     let user = match state.storage.get_user_by_email(&form.email).await {
         Ok(Some(user)) => user,
-        _ => {
+        Ok(None) => {
+            history::record_failure(&state, &ctx, method, &form.email, None, "INVALID_CREDENTIALS").await;
+            return Html(
+                LoginTemplate { error: Some("Invalid email or password".to_string()) }
+                    .render()
+                    .unwrap_or_default(),
+            )
+            .into_response();
+        }
+        Err(_) => {
             return Html(
                 LoginTemplate { error: Some("Invalid email or password".to_string()) }
                     .render()
@@ -39,6 +61,7 @@ pub async fn login_submit(
 
     let password_valid = bcrypt::verify(&form.password, &user.password_hash).unwrap_or(false);
     if !password_valid {
+        history::record_failure(&state, &ctx, method, &form.email, Some(user.id), "INVALID_CREDENTIALS").await;
         return Html(
             LoginTemplate { error: Some("Invalid email or password".to_string()) }
                 .render()
@@ -50,6 +73,7 @@ pub async fn login_submit(
     let admin = match state.storage.get_admin_by_user_id(user.id).await {
         Ok(Some(admin)) => admin,
         _ => {
+            history::record_failure(&state, &ctx, method, &form.email, Some(user.id), "NOT_ADMIN").await;
             return Html(
                 LoginTemplate { error: Some("You are not authorized to access the admin panel".to_string()) }
                     .render()
@@ -72,6 +96,7 @@ pub async fn login_submit(
     };
 
     if account.account_status != AccountStatus::Active {
+        history::record_failure(&state, &ctx, method, &form.email, Some(user.id), "ACCOUNT_INACTIVE").await;
         return Html(
             LoginTemplate { error: Some("Your account is not active".to_string()) }
                 .render()
@@ -91,6 +116,7 @@ pub async fn login_submit(
             .into_response();
         }
     };
+    sessions::track(&state.validation, user.id, &token_pair);
 
     let mut cookie = Cookie::new(AUTH_COOKIE_NAME, token_pair.access_token);
     cookie.set_path("/admin");
@@ -98,6 +124,7 @@ pub async fn login_submit(
     cookie.set_secure(false);
     cookies.add(cookie);
 
+    history::record_success(&state, &ctx, method, &user).await;
     let _ = state.storage.update_user_last_login(user.id).await;
 
     Redirect::to("/admin/dashboard").into_response()
@@ -105,11 +132,16 @@ pub async fn login_submit(
 
 pub async fn logout(
     State(state): State<AppState>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
     cookies: Cookies,
 ) -> impl IntoResponse {
     if let Some(cookie) = cookies.get(AUTH_COOKIE_NAME) {
         if let Ok(claims) = state.token_service.verify_access_token(cookie.value()) {
-            state.validation.blacklist_jti(claims.jti);
+            state.validation.blacklist_jti(claims.jti, claims.expires_at());
+
+            let ctx = AuditContext::from_request(&headers, client_ip, Some(claims.sub));
+            audit::record(&*state.audit, AuditEntry::new(actions::LOGOUT, "user", claims.sub).context(&ctx)).await;
         }
     }
 
@@ -147,26 +179,178 @@ pub async fn dashboard(
 pub async fn users_list(
     State(state): State<AppState>,
     cookies: Cookies,
-    Query(query): Query<PaginationQuery>,
+    Query(query): Query<UsersQuery>,
 ) -> Response {
     let claims = match verify_admin_cookie(&state, &cookies).await {
         Some(claims) => claims,
         None => return Redirect::to("/admin/login").into_response(),
     };
 
-    let _page = query.page.unwrap_or(1);
+    let page = PageRequest::new(query.page, None);
+    let filter = UserFilter { search: query.q.clone(), ..Default::default() };
+
+    let (users, total, message) = match state.storage.list_users(&filter, UserSort::default(), page).await {
+        Ok(result) => (result.users, result.total, None),
+        Err(_) => (vec![], 0, Some("Failed to load users".to_string())),
+    };
 
     let template = UsersTemplate {
         user_email: claims.email,
-        users: vec![],
-        message: None,
-        current_page: 1,
-        total_pages: 1,
+        users: users.into_iter().map(user_row).collect(),
+        message,
+        search: query.q.unwrap_or_default(),
+        total_users: total,
+        current_page: page.page,
+        total_pages: page.total_pages(total),
+    };
+
+    Html(template.render().unwrap_or_default()).into_response()
+}
+
+pub async fn metrics_page(State(state): State<AppState>, cookies: Cookies) -> Response {
+    let claims = match verify_admin_cookie(&state, &cookies).await {
+        Some(claims) => claims,
+        None => return Redirect::to("/admin/login").into_response(),
+    };
+
+    let bound = |ms: Option<f64>| match ms {
+        Some(ms) => format!("≤ {} ms", ms),
+        None => "> 2500 ms".to_string(),
+    };
+    let methods = state
+        .storage_metrics
+        .stats()
+        .into_iter()
+        .map(|s| MetricRow {
+            method: s.method.to_string(),
+            calls: s.calls,
+            errors: s.errors,
+            error_kinds: s
+                .error_kinds
+                .iter()
+                .map(|(kind, count)| format!("{}: {}", kind, count))
+                .collect::<Vec<_>>()
+                .join(", "),
+            mean_ms: format!("{:.2}", s.mean_ms),
+            p50_ms: bound(s.p50_ms),
+            p95_ms: bound(s.p95_ms),
+            p99_ms: bound(s.p99_ms),
+        })
+        .collect();
+
+    let caches = state
+        .storage
+        .cache_stats()
+        .into_iter()
+        .map(|c| CacheRow {
+            name: c.name.to_string(),
+            hits: c.hits,
+            misses: c.misses,
+            hit_rate: match c.hits + c.misses {
+                0 => "-".to_string(),
+                total => format!("{:.1}%", c.hits as f64 * 100.0 / total as f64),
+            },
+            entries: c.entries,
+            capacity: c.capacity,
+        })
+        .collect();
+
+    let template = MetricsTemplate {
+        user_email: claims.email,
+        methods,
+        caches,
     };
 
     Html(template.render().unwrap_or_default()).into_response()
 }
 
+pub async fn audit_page(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Query(query): Query<AuditQuery>,
+) -> Response {
+    let claims = match verify_admin_cookie(&state, &cookies).await {
+        Some(claims) => claims,
+        None => return Redirect::to("/admin/login").into_response(),
+    };
+
+    let text = |value: Option<String>| value.map(|v| v.trim().to_string()).unwrap_or_default();
+    let (action, actor, target) = (text(query.action), text(query.actor), text(query.target));
+    let non_empty = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+
+    let page = PageRequest::new(query.page, None);
+    let mut message = None;
+    let filter = AuditFilter {
+        action: non_empty(&action),
+        actor_id: match non_empty(&actor).map(|a| a.parse()) {
+            Some(Ok(id)) => Some(id),
+            Some(Err(_)) => {
+                message = Some("Actor must be a user id".to_string());
+                None
+            }
+            None => None,
+        },
+        target_id: non_empty(&target),
+        ..Default::default()
+    };
+
+    let (events, total) = match state.audit.list(&filter, page).await {
+        Ok(result) => (result.events, result.total),
+        Err(_) => {
+            message = Some("Failed to load audit events".to_string());
+            (vec![], 0)
+        }
+    };
+
+    let template = AuditTemplate {
+        user_email: claims.email,
+        events: events.into_iter().map(audit_row).collect(),
+        message,
+        action,
+        actor,
+        target,
+        total_events: total,
+        current_page: page.page,
+        total_pages: page.total_pages(total),
+    };
+
+    Html(template.render().unwrap_or_default()).into_response()
+}
+
+fn audit_row(event: AuditEvent) -> AuditRow {
+    let json = |value: Option<serde_json::Value>| value.map(|v| v.to_string()).unwrap_or_default();
+    AuditRow {
+        seq: event.seq,
+        created_at: event.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        action: event.action,
+        actor: event.actor_id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string()),
+        target: match event.target_id {
+            Some(id) => format!("{} {}", event.target_type, id),
+            None => event.target_type,
+        },
+        before: json(event.before),
+        after: json(event.after),
+        ip_address: event.ip_address.unwrap_or_default(),
+    }
+}
+
+fn user_row(summary: UserSummary) -> UserRow {
+    let user = summary.user;
+    UserRow {
+        id: user.id.to_string(),
+        username: user.username,
+        email: user.email,
+        first_name: user.first_name,
+        last_name: user.last_name,
+        is_active: user.is_active,
+        account_level: summary
+            .account_level
+            .map(|level| format!("{:?}", level))
+            .unwrap_or_else(|| "None".to_string()),
+        created_at: user.created_at.format("%Y-%m-%d %H:%M").to_string(),
+    }
+}
+
 async fn verify_admin_cookie(
     state: &AppState,
     cookies: &Cookies,
@@ -183,5 +367,6 @@ async fn verify_admin_cookie(
         return None;
     }
 
+    request_id::record_user(claims.sub);
     Some(claims)
 }
@@ -1,3 +1,4 @@
 pub mod handlers;
 pub mod ui;
-pub mod model;
\ No newline at end of file
+pub mod model;
+pub mod setup;
@@ -11,9 +11,27 @@ pub struct Admin {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub created_by: Option<Uuid>,
+    /// Bumped by every edit; updates must name the version they were based on.
+    #[serde(default)]
+    pub version: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone)]
+pub struct CreateAdminRequest {
+    pub user_id: Uuid,
+    pub role: AdminRole,
+    pub permissions: Vec<String>,
+    pub created_by: Option<Uuid>,
+}
+
+/// Changes for `StorageLayer::update_admin`. Unset fields are left as they are.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateAdminRequest {
+    pub role: Option<AdminRole>,
+    pub permissions: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "admin_role", rename_all = "lowercase")]
 pub enum AdminRole {
     SuperAdmin,
@@ -0,0 +1,286 @@
+//! First-run bootstrap. Until a superadmin exists the server either creates
+//! one from `BOOTSTRAP_ADMIN_EMAIL` and a password file, or prints a one-time
+//! token that unlocks `/admin/setup`, where the first superadmin is created.
+//! Either way the username is chosen at setup, so someone who registered as
+//! `admin` first can't block it.
+
+use std::fmt;
+use std::fs;
+use std::sync::Mutex;
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use super::model::AdminRole;
+use super::ui::{SetupForm, SetupQuery, SetupTemplate};
+use crate::app::AppState;
+use crate::audit::{self, actions, AuditEntry, AuditSink};
+use crate::config::Config;
+use crate::storage::{seed_admin, DbError, StorageLayer};
+
+/// The setup token printed at startup, kept only as a hash. It can be
+/// redeemed once.
+#[derive(Default)]
+pub struct SetupToken {
+    hash: Mutex<Option<String>>,
+}
+
+impl SetupToken {
+    pub fn new(token: Option<&str>) -> Self {
+        Self { hash: Mutex::new(token.map(hash_token)) }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.hash.lock().unwrap().is_some()
+    }
+
+    /// Takes the token if `token` matches it, so a concurrent request can't
+    /// redeem it as well.
+    fn redeem(&self, token: &str) -> bool {
+        let mut hash = self.hash.lock().unwrap();
+        if hash.as_deref() != Some(hash_token(token).as_str()) {
+            return false;
+        }
+        *hash = None;
+        true
+    }
+
+    /// Puts a redeemed token back after the setup it authorized failed.
+    fn restore(&self, token: &str) {
+        *self.hash.lock().unwrap() = Some(hash_token(token));
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn generate_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().r#gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug)]
+pub enum SetupError {
+    Invalid(String),
+    Storage(DbError),
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::Invalid(msg) => write!(f, "{}", msg),
+            SetupError::Storage(e) => write!(f, "{}", e.public_message()),
+        }
+    }
+}
+
+impl From<DbError> for SetupError {
+    fn from(err: DbError) -> Self {
+        SetupError::Storage(err)
+    }
+}
+
+/// Used by `bootstrap` when `bootstrap.admin_username` isn't set.
+pub const DEFAULT_ADMIN_USERNAME: &str = "admin";
+
+/// What `bootstrap` found or did.
+pub enum Bootstrap {
+    /// A superadmin already exists.
+    Ready,
+    /// The superadmin named by `BOOTSTRAP_ADMIN_EMAIL` was created.
+    Created(String),
+    /// Nobody can administer the server yet; this token unlocks the setup page.
+    Pending(String),
+}
+
+pub async fn bootstrap(
+    storage: &dyn StorageLayer,
+    audit: &dyn AuditSink,
+    config: &Config,
+) -> Result<Bootstrap, SetupError> {
+    if storage.has_superadmin().await? {
+        return Ok(Bootstrap::Ready);
+    }
+
+    let Some(email) = &config.bootstrap.admin_email else {
+        return Ok(Bootstrap::Pending(generate_token()));
+    };
+    let path = config.bootstrap.admin_password_file.as_ref().ok_or_else(|| {
+        SetupError::Invalid("BOOTSTRAP_ADMIN_EMAIL requires BOOTSTRAP_ADMIN_PASSWORD_FILE".to_string())
+    })?;
+    let password = fs::read_to_string(path)
+        .map_err(|e| SetupError::Invalid(format!("Failed to read {}: {}", path, e)))?;
+    let password = password.lines().next().unwrap_or_default();
+
+    let username = config.bootstrap.admin_username.as_deref().unwrap_or(DEFAULT_ADMIN_USERNAME);
+    create_superadmin(storage, audit, email, username, password, "BOOTSTRAP_ADMIN_EMAIL").await?;
+    Ok(Bootstrap::Created(email.clone()))
+}
+
+/// Creates a superadmin with a new user and an active account. Fails rather
+/// than promoting a user who already has `email` or `username`.
+pub async fn create_superadmin(
+    storage: &dyn StorageLayer,
+    audit: &dyn AuditSink,
+    email: &str,
+    username: &str,
+    password: &str,
+    source: &str,
+) -> Result<(), SetupError> {
+    if !email.contains('@') || !email.contains('.') {
+        return Err(SetupError::Invalid("Invalid email format".to_string()));
+    }
+    if username.trim().is_empty() {
+        return Err(SetupError::Invalid("Username must not be empty".to_string()));
+    }
+    if password.len() < 8 {
+        return Err(SetupError::Invalid("Password must be at least 8 characters".to_string()));
+    }
+    if storage.get_user_by_email(email).await?.is_some() {
+        return Err(SetupError::Invalid("A user with that email already exists".to_string()));
+    }
+    if storage.get_user_by_username(username).await?.is_some() {
+        return Err(SetupError::Invalid("A user with that username already exists".to_string()));
+    }
+
+    let password_hash = bcrypt::hash(password, bcrypt::DEFAULT_COST)
+        .map_err(|e| SetupError::Invalid(format!("Failed to hash password: {}", e)))?;
+    seed_admin(storage, email, username, &password_hash).await?;
+
+    let user = storage.get_user_by_email(email).await?.ok_or(DbError::NotFound)?;
+    let mut entry = AuditEntry::new(actions::ADMIN_GRANTED, "admin", user.id);
+    entry.after = Some(serde_json::json!({ "role": AdminRole::SuperAdmin, "source": source }));
+    audit::record(audit, entry).await;
+    Ok(())
+}
+
+fn render(token: String, error: Option<String>) -> Response {
+    Html(SetupTemplate { token, error }.render().unwrap_or_default()).into_response()
+}
+
+/// The page is only reachable while no superadmin exists.
+async fn is_locked(state: &AppState) -> bool {
+    !matches!(state.storage.has_superadmin().await, Ok(false))
+}
+
+pub async fn setup_page(State(state): State<AppState>, Query(query): Query<SetupQuery>) -> Response {
+    if is_locked(&state).await {
+        return Redirect::to("/admin/login").into_response();
+    }
+    render(query.token.unwrap_or_default(), None)
+}
+
+pub async fn setup_submit(State(state): State<AppState>, Form(form): Form<SetupForm>) -> Response {
+    if is_locked(&state).await {
+        return Redirect::to("/admin/login").into_response();
+    }
+    if form.password != form.password_confirm {
+        return render(form.token, Some("Passwords do not match".to_string()));
+    }
+    if !state.setup.redeem(&form.token) {
+        return render(form.token, Some("Invalid or expired setup token".to_string()));
+    }
+
+    let created =
+        create_superadmin(&*state.storage, &*state.audit, &form.email, &form.username, &form.password, "setup page");
+    match created.await {
+        Ok(()) => Redirect::to("/admin/login").into_response(),
+        Err(e) => {
+            state.setup.restore(&form.token);
+            render(form.token, Some(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::MemoryAuditSink;
+    use crate::storage::MemoryStorage;
+    use crate::users::model::CreateUserRequest;
+
+    #[test]
+    fn test_setup_token_is_redeemed_once() {
+        let setup = SetupToken::new(Some("secret"));
+        assert!(!setup.redeem("guess"));
+        assert!(setup.redeem("secret"));
+        assert!(!setup.is_pending());
+        assert!(!setup.redeem("secret"));
+
+        setup.restore("secret");
+        assert!(setup.redeem("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_creates_admin_from_password_file() {
+        let storage = MemoryStorage::new();
+        let audit = MemoryAuditSink::new();
+        let path = std::env::temp_dir().join(format!("learner-bootstrap-{}", uuid::Uuid::new_v4()));
+        fs::write(&path, "correct horse battery\n").unwrap();
+
+        let mut config = Config::default();
+        config.bootstrap.admin_email = Some("root@example.com".to_string());
+        config.bootstrap.admin_password_file = Some(path.to_string_lossy().into_owned());
+        let result = bootstrap(&storage, &audit, &config).await;
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Ok(Bootstrap::Created(_))));
+
+        let user = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+        assert!(bcrypt::verify("correct horse battery", &user.password_hash).unwrap());
+        assert!(matches!(bootstrap(&storage, &audit, &config).await, Ok(Bootstrap::Ready)));
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_without_email_issues_token() {
+        let storage = MemoryStorage::new();
+        let config = Config::default();
+
+        let Ok(Bootstrap::Pending(token)) = bootstrap(&storage, &MemoryAuditSink::new(), &config).await else {
+            panic!("expected a setup token");
+        };
+        assert_eq!(token.len(), 64);
+        assert!(!storage.has_superadmin().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_create_superadmin_refuses_existing_email() {
+        let storage = MemoryStorage::new();
+        let audit = MemoryAuditSink::new();
+        create_superadmin(&storage, &audit, "root@example.com", "root", "password1", "test").await.unwrap();
+
+        let err =
+            create_superadmin(&storage, &audit, "root@example.com", "root2", "password2", "test").await.unwrap_err();
+        assert!(matches!(err, SetupError::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_isnt_blocked_by_a_registered_admin_username() {
+        let storage = MemoryStorage::new();
+        let audit = MemoryAuditSink::new();
+        let squatter = CreateUserRequest {
+            email: "squatter@example.com".to_string(),
+            password: String::new(),
+            username: DEFAULT_ADMIN_USERNAME.to_string(),
+            first_name: "Squat".to_string(),
+            last_name: "Ter".to_string(),
+        };
+        storage.create_user(&squatter, "hash").await.unwrap();
+
+        let err = create_superadmin(&storage, &audit, "root@example.com", DEFAULT_ADMIN_USERNAME, "password1", "test")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "A user with that username already exists");
+
+        create_superadmin(&storage, &audit, "root@example.com", "root", "password1", "test").await.unwrap();
+        let root = storage.get_user_by_username("root").await.unwrap().unwrap();
+        assert_eq!(root.email, "root@example.com");
+        assert!(storage.has_superadmin().await.unwrap());
+    }
+}
@@ -9,6 +9,13 @@ pub struct LoginTemplate {
     pub error: Option<String>,
 }
 
+#[derive(Template)]
+#[template(path = "admin/setup.html")]
+pub struct SetupTemplate {
+    pub token: String,
+    pub error: Option<String>,
+}
+
 #[derive(Template)]
 #[template(path = "admin/dashboard.html")]
 pub struct DashboardTemplate {
@@ -27,8 +34,63 @@ pub struct UsersTemplate {
     pub user_email: String,
     pub users: Vec<UserRow>,
     pub message: Option<String>,
-    pub current_page: i32,
-    pub total_pages: i32,
+    pub search: String,
+    pub total_users: i64,
+    pub current_page: i64,
+    pub total_pages: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/metrics.html")]
+pub struct MetricsTemplate {
+    pub user_email: String,
+    pub methods: Vec<MetricRow>,
+    pub caches: Vec<CacheRow>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/audit.html")]
+pub struct AuditTemplate {
+    pub user_email: String,
+    pub events: Vec<AuditRow>,
+    pub message: Option<String>,
+    pub action: String,
+    pub actor: String,
+    pub target: String,
+    pub total_events: i64,
+    pub current_page: i64,
+    pub total_pages: i64,
+}
+
+pub struct AuditRow {
+    pub seq: i64,
+    pub created_at: String,
+    pub action: String,
+    pub actor: String,
+    pub target: String,
+    pub before: String,
+    pub after: String,
+    pub ip_address: String,
+}
+
+pub struct MetricRow {
+    pub method: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub error_kinds: String,
+    pub mean_ms: String,
+    pub p50_ms: String,
+    pub p95_ms: String,
+    pub p99_ms: String,
+}
+
+pub struct CacheRow {
+    pub name: String,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: String,
+    pub entries: usize,
+    pub capacity: usize,
 }
 
 pub struct UserRow {
@@ -49,6 +111,29 @@ pub struct LoginForm {
 }
 
 #[derive(Deserialize)]
-pub struct PaginationQuery {
-    pub page: Option<i32>,
+pub struct SetupForm {
+    pub token: String,
+    pub email: String,
+    pub username: String,
+    pub password: String,
+    pub password_confirm: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetupQuery {
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UsersQuery {
+    pub page: Option<i64>,
+    pub q: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub page: Option<i64>,
+    pub action: Option<String>,
+    pub actor: Option<String>,
+    pub target: Option<String>,
 }
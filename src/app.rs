@@ -1,71 +1,191 @@
-use axum::{Router, response::Html, routing::{get, post}};
+use axum::{Router, middleware, response::Html, routing::{get, post}};
+use std::net::IpAddr;
 use std::sync::Arc;
 use tower_cookies::CookieManagerLayer;
 
-use crate::storage::StorageLayer;
-use crate::routing::{public_routes, private_routes};
+use crate::audit::{AuditSink, MemoryAuditSink};
+use crate::storage::{MetricsStorage, StorageLayer, StorageMetrics};
+use crate::routing::{error as api_error, metrics as metrics_routes, public_routes, private_routes, request_id, versions};
 use crate::validation::ValidationStore;
 use crate::auth::TokenService;
+use crate::auth::middleware::require_admin;
 use crate::admin::handlers as admin_handlers;
-use crate::auth::r#in as auth_in;
-use crate::auth::out as auth_out;
-use crate::auth::new as auth_new;
+use crate::admin::setup::{self as admin_setup, SetupToken};
+use crate::email::{DisabledTransport, LogTransport, MailTransport};
+use crate::config::{Config, MailTransportKind, MetricsConfig, TokenConfig};
+use crate::lifecycle::Lifecycle;
+use crate::metrics::AppMetrics;
+use crate::idempotency::{self, IdempotencyStore, MemoryIdempotencyStore};
+use crate::openapi;
+use crate::rate_limit::{self, MemoryRateLimitStore, RateLimitStore, RateLimiter, RateLimits, RouteGroup};
+use crate::scheduler::{self, Scheduler, SchedulerConfig};
 
 #[derive(Clone)]
 pub struct AppState {
     pub storage: Arc<dyn StorageLayer>,
     pub validation: Arc<ValidationStore>,
     pub token_service: Arc<TokenService>,
+    pub mailer: Arc<dyn MailTransport>,
+    pub storage_metrics: Arc<StorageMetrics>,
+    pub scheduler: Arc<Scheduler>,
+    pub audit: Arc<dyn AuditSink>,
+    pub setup: Arc<SetupToken>,
+    pub lifecycle: Arc<Lifecycle>,
+    pub metrics: Arc<AppMetrics>,
+    /// `None` when rate limiting is disabled.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub idempotency: Arc<dyn IdempotencyStore>,
+    /// See `ServerConfig::trusted_proxies`.
+    pub trusted_proxies: Arc<[IpAddr]>,
 }
 
 pub struct AppConfig {
-    pub jwt_secret: String,
+    pub token: TokenConfig,
+    pub mailer: Arc<dyn MailTransport>,
+    pub scheduler: SchedulerConfig,
+    pub audit: Arc<dyn AuditSink>,
+    /// Printed at startup when no superadmin exists yet; see `admin::setup`.
+    pub setup_token: Option<String>,
+    /// Shared with the caller so it can drain the app on shutdown.
+    pub lifecycle: Arc<Lifecycle>,
+    pub metrics: MetricsConfig,
+    /// `None` disables rate limiting.
+    pub rate_limits: Option<RateLimits>,
+    pub rate_limit_store: Arc<dyn RateLimitStore>,
+    pub idempotency: Arc<dyn IdempotencyStore>,
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
+/// The routers to serve. `metrics` is only set when metrics have their own
+/// listen address, and `/metrics` is then left out of `main`.
+pub struct App {
+    pub main: Router,
+    pub metrics: Option<Router>,
+}
+
+impl From<&Config> for AppConfig {
+    fn from(config: &Config) -> Self {
+        let mailer: Arc<dyn MailTransport> = match config.mail.transport {
+            MailTransportKind::Log => Arc::new(LogTransport),
+            MailTransportKind::Disabled => Arc::new(DisabledTransport),
+        };
         Self {
-            jwt_secret: std::env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "super-secret-key-change-in-production".to_string()),
+            token: config.token.clone(),
+            mailer,
+            scheduler: config.scheduler_config(),
+            audit: Arc::new(MemoryAuditSink::new()),
+            setup_token: None,
+            lifecycle: Arc::new(Lifecycle::new()),
+            metrics: config.metrics.clone(),
+            rate_limits: config.rate_limits(),
+            rate_limit_store: Arc::new(MemoryRateLimitStore::new()),
+            idempotency: Arc::new(MemoryIdempotencyStore::new()),
+            trusted_proxies: config.server.trusted_proxies.clone(),
         }
     }
 }
 
-pub async fn create_app(storage: Arc<dyn StorageLayer>) -> Router {
-    create_app_with_config(storage, AppConfig::default()).await
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self::from(&Config::default())
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// A state over `storage` with everything else in memory and rate
+    /// limiting off, for tests that don't need the routers.
+    pub fn for_tests(storage: Arc<dyn StorageLayer>) -> Self {
+        Self {
+            storage,
+            validation: Arc::new(ValidationStore::new()),
+            token_service: Arc::new(TokenService::new("secret".to_string())),
+            mailer: Arc::new(LogTransport),
+            storage_metrics: Arc::new(StorageMetrics::new()),
+            scheduler: Arc::new(Scheduler::new(0.0)),
+            audit: Arc::new(MemoryAuditSink::new()),
+            setup: Arc::new(SetupToken::default()),
+            lifecycle: Arc::new(Lifecycle::new()),
+            metrics: Arc::new(AppMetrics::new()),
+            rate_limiter: None,
+            idempotency: Arc::new(MemoryIdempotencyStore::new()),
+            trusted_proxies: Arc::from([]),
+        }
+    }
 }
 
-pub async fn create_app_with_config(storage: Arc<dyn StorageLayer>, config: AppConfig) -> Router {
+pub async fn create_app_with_config(storage: Arc<dyn StorageLayer>, config: AppConfig) -> App {
     let validation_store = Arc::new(ValidationStore::new());
-    let token_service = Arc::new(TokenService::new(config.jwt_secret));
+    let token_service = Arc::new(TokenService::with_ttl(
+        config.token.jwt_secret,
+        config.token.access_ttl_minutes,
+        config.token.refresh_ttl_days,
+    ));
+
+    // Every storage call made by the app is timed and counted.
+    let storage_metrics = Arc::new(StorageMetrics::new());
+    let storage: Arc<dyn StorageLayer> = Arc::new(MetricsStorage::new(storage, storage_metrics.clone()));
 
     let app_state = AppState {
         storage,
         validation: validation_store,
         token_service,
+        mailer: config.mailer,
+        storage_metrics,
+        scheduler: Arc::new(Scheduler::new(config.scheduler.jitter)),
+        audit: config.audit,
+        setup: Arc::new(SetupToken::new(config.setup_token.as_deref())),
+        lifecycle: config.lifecycle,
+        metrics: Arc::new(AppMetrics::new()),
+        rate_limiter: config
+            .rate_limits
+            .map(|limits| Arc::new(RateLimiter::new(limits, config.rate_limit_store))),
+        idempotency: config.idempotency,
+        trusted_proxies: config.trusted_proxies.into(),
     };
 
+    if config.scheduler.enabled {
+        scheduler::start_maintenance(&app_state, &config.scheduler);
+    }
+
+    // Only the form submission counts against the sign-in limit.
+    let admin_login = Router::new().route("/login", post(admin_handlers::login_submit));
     let admin_ui_routes = Router::new()
-        .route("/login", get(admin_handlers::login_page).post(admin_handlers::login_submit))
+        .route("/login", get(admin_handlers::login_page))
+        .merge(rate_limit::limited(admin_login, &app_state, RouteGroup::Auth))
         .route("/logout", post(admin_handlers::logout))
+        .route("/setup", get(admin_setup::setup_page).post(admin_setup::setup_submit))
         .route("/dashboard", get(admin_handlers::dashboard))
-        .route("/users", get(admin_handlers::users_list));
+        .route("/users", get(admin_handlers::users_list))
+        .route("/metrics", get(admin_handlers::metrics_page))
+        .route("/audit", get(admin_handlers::audit_page));
+
+    let admin_api_routes = private_routes::router()
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), require_admin));
+    let admin_api_routes = idempotency::idempotent(admin_api_routes, &app_state);
+    let admin_api_routes = rate_limit::limited(admin_api_routes, &app_state, RouteGroup::Admin);
 
-    let auth_routes = Router::new()
-        .route("/register", post(auth_new::register))
-        .route("/login", post(auth_in::user_login))
-        .route("/admin/login", post(auth_in::admin_login))
-        .route("/logout", post(auth_out::logout))
-        .route("/logout-all", post(auth_out::logout_all));
+    let metrics_routes = metrics_routes::router(config.metrics.token);
+    let (main_metrics, separate_metrics) = match config.metrics.listen {
+        Some(_) => (Router::new(), Some(metrics_routes)),
+        None => (metrics_routes, None),
+    };
 
-    Router::new()
+    let main = Router::new()
         .route("/", get(root_handler))
         .merge(public_routes::router())
+        .merge(main_metrics)
+        .merge(openapi::router())
         .nest("/admin", admin_ui_routes)
-        .nest("/admin/api", private_routes::router())
-        .nest("/auth", auth_routes)
+        .nest("/admin/api", admin_api_routes)
+        .merge(versions::router(&app_state))
+        .fallback(api_error::not_found)
         .layer(CookieManagerLayer::new())
-        .with_state(app_state)
+        .layer(middleware::from_fn_with_state(app_state.clone(), metrics_routes::track_requests))
+        .layer(middleware::from_fn(request_id::trace_requests))
+        .with_state(app_state.clone());
+
+    App { main, metrics: separate_metrics.map(|router| router.with_state(app_state)) }
 }
 
 async fn root_handler() -> Html<&'static str> {
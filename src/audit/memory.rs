@@ -0,0 +1,60 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::model::GENESIS_HASH;
+use super::{AuditEntry, AuditEvent, AuditFilter, AuditPage, AuditSink};
+use crate::storage::DbError;
+use crate::users::model::PageRequest;
+
+/// Keeps the log in process memory; it's lost on restart. Used alongside
+/// `MemoryStorage`.
+#[derive(Default)]
+pub struct MemoryAuditSink {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl MemoryAuditSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AuditSink for MemoryAuditSink {
+    async fn record(&self, entry: &AuditEntry) -> Result<AuditEvent, DbError> {
+        let mut events = self.events.lock().unwrap();
+        let (seq, prev_hash) = events
+            .last()
+            .map_or((1, GENESIS_HASH), |last| (last.seq + 1, last.hash.as_str()));
+
+        let event = AuditEvent::chain(entry, seq, prev_hash);
+        events.push(event.clone());
+        Ok(event)
+    }
+
+    async fn list(&self, filter: &AuditFilter, page: PageRequest) -> Result<AuditPage, DbError> {
+        let events = self.events.lock().unwrap();
+        let matches: Vec<&AuditEvent> = events.iter().rev().filter(|e| filter.matches(e)).collect();
+
+        Ok(AuditPage {
+            total: matches.len() as i64,
+            events: matches
+                .into_iter()
+                .skip(page.offset() as usize)
+                .take(page.per_page as usize)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    async fn events_after(&self, after: i64, limit: i64) -> Result<Vec<AuditEvent>, DbError> {
+        let events = self.events.lock().unwrap();
+        Ok(events
+            .iter()
+            .filter(|e| e.seq > after)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
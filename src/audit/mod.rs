@@ -0,0 +1,178 @@
+pub mod model;
+pub mod memory;
+pub mod postgres;
+pub mod sqlite;
+
+pub use memory::MemoryAuditSink;
+pub use model::{actions, AuditContext, AuditEntry, AuditEvent, AuditFilter, AuditPage, ChainVerification};
+pub use postgres::PostgresAuditSink;
+pub use sqlite::SqliteAuditSink;
+
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use uuid::Uuid;
+
+use crate::routing::client_ip::ClientIp;
+use crate::storage::DbError;
+use crate::users::model::PageRequest;
+use crate::utils;
+use model::{verify_chain, GENESIS_HASH};
+
+/// How many events `verify` loads at a time.
+const VERIFY_BATCH: i64 = 500;
+
+/// Append-only, hash-chained record of security-relevant actions.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    /// Appends `entry` to the end of the chain.
+    async fn record(&self, entry: &AuditEntry) -> Result<AuditEvent, DbError>;
+
+    /// Events matching `filter`, newest first.
+    async fn list(&self, filter: &AuditFilter, page: PageRequest) -> Result<AuditPage, DbError>;
+
+    /// Up to `limit` events with a seq above `after`, oldest first.
+    async fn events_after(&self, after: i64, limit: i64) -> Result<Vec<AuditEvent>, DbError>;
+
+    /// Walks the whole log checking the hash chain.
+    async fn verify(&self) -> Result<ChainVerification, DbError> {
+        let mut prev = (0, GENESIS_HASH.to_string());
+        let mut checked = 0;
+        loop {
+            let batch = self.events_after(prev.0, VERIFY_BATCH).await?;
+            let Some(last) = batch.last() else {
+                return Ok(ChainVerification { events_checked: checked, broken_at: None });
+            };
+            if let Err(seq) = verify_chain((prev.0, &prev.1), &batch) {
+                checked += batch.iter().take_while(|e| e.seq < seq).count() as u64;
+                return Ok(ChainVerification { events_checked: checked, broken_at: Some(seq) });
+            }
+            checked += batch.len() as u64;
+            prev = (last.seq, last.hash.clone());
+        }
+    }
+}
+
+impl AuditContext {
+    pub fn from_request(headers: &HeaderMap, client_ip: ClientIp, actor_id: Option<Uuid>) -> Self {
+        Self {
+            actor_id,
+            ip_address: client_ip.to_column(),
+            request_id: utils::request_id(headers),
+        }
+    }
+}
+
+/// Records `entry`, logging rather than returning a failure so that an
+/// unavailable audit log doesn't fail the action being audited.
+pub async fn record(sink: &dyn AuditSink, entry: AuditEntry) {
+    if let Err(e) = sink.record(&entry).await {
+        tracing::error!(action = %entry.action, error = %e, "failed to record audit event");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::storage::SqliteStorage;
+
+    async fn sqlite_sink() -> SqliteAuditSink {
+        let storage = SqliteStorage::from_url("sqlite::memory:").await.unwrap();
+        storage.run_migrations().await.unwrap();
+        SqliteAuditSink::new(storage.pool().clone())
+    }
+
+    fn entry(action: &str, target_id: u32, actor_id: Option<Uuid>) -> AuditEntry {
+        let ctx = AuditContext { actor_id, ip_address: Some("10.0.0.1".to_string()), request_id: None };
+        AuditEntry::new(action, "user", target_id)
+            .context(&ctx)
+            .changes(&serde_json::json!({"n": target_id}), &serde_json::json!({"n": target_id + 1}))
+    }
+
+    /// Cases every sink must pass.
+    async fn check_sink(sink: Arc<dyn AuditSink>) {
+        let actor = Uuid::new_v4();
+        let first = sink.record(&entry(actions::USER_UPDATED, 1, Some(actor))).await.unwrap();
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+
+        // Concurrent appends must still form a single chain.
+        let appends = (2..=20).map(|i| {
+            let sink = sink.clone();
+            tokio::spawn(async move { sink.record(&entry(actions::USER_DELETED, i, None)).await.unwrap() })
+        });
+        for handle in appends.collect::<Vec<_>>() {
+            handle.await.unwrap();
+        }
+
+        let verification = sink.verify().await.unwrap();
+        assert_eq!(verification.events_checked, 20);
+        assert_eq!(verification.broken_at, None);
+
+        let stored = sink.events_after(0, 1).await.unwrap().remove(0);
+        assert_eq!(stored.hash, first.hash, "events read back hash the same");
+        assert_eq!(stored.after, Some(serde_json::json!({"n": 2})));
+        assert_eq!(stored.ip_address.as_deref(), Some("10.0.0.1"));
+
+        let by_actor = AuditFilter { actor_id: Some(actor), ..Default::default() };
+        let page = sink.list(&by_actor, PageRequest::default()).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.events[0].id, first.id);
+
+        let deletions = AuditFilter { action: Some(actions::USER_DELETED.to_string()), ..Default::default() };
+        let page = sink.list(&deletions, PageRequest::new(Some(2), Some(5))).await.unwrap();
+        assert_eq!(page.total, 19);
+        assert_eq!(page.events.len(), 5);
+        assert!(page.events.windows(2).all(|w| w[0].seq > w[1].seq), "newest first");
+
+        let by_target = AuditFilter { target_id: Some("7".to_string()), ..Default::default() };
+        assert_eq!(sink.list(&by_target, PageRequest::default()).await.unwrap().total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_sink() {
+        check_sink(Arc::new(MemoryAuditSink::new())).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_sink() {
+        check_sink(Arc::new(sqlite_sink().await)).await;
+    }
+
+    #[tokio::test]
+    async fn test_postgres_sink() {
+        let Some((storage, db_name)) = crate::storage::conformance::postgres_database().await else {
+            return;
+        };
+        let result = tokio::spawn(check_sink(Arc::new(PostgresAuditSink::new(storage.pool().clone())))).await;
+        crate::storage::conformance::teardown(&db_name).await;
+        result.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_rows_cannot_be_changed() {
+        let sink = sqlite_sink().await;
+        sink.record(&entry(actions::USER_UPDATED, 1, None)).await.unwrap();
+
+        let update = sqlx::query("UPDATE audit_events SET action = 'user.restored'").execute(sink.pool()).await;
+        assert!(update.is_err());
+        let delete = sqlx::query("DELETE FROM audit_events").execute(sink.pool()).await;
+        assert!(delete.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_postgres_rows_cannot_be_changed() {
+        let Some((storage, db_name)) = crate::storage::conformance::postgres_database().await else {
+            return;
+        };
+        let sink = PostgresAuditSink::new(storage.pool().clone());
+        sink.record(&entry(actions::USER_UPDATED, 1, None)).await.unwrap();
+
+        let update = sqlx::query("UPDATE audit_events SET action = 'user.restored'").execute(storage.pool()).await;
+        let delete = sqlx::query("DELETE FROM audit_events").execute(storage.pool()).await;
+        crate::storage::conformance::teardown(&db_name).await;
+        assert!(update.is_err());
+        assert!(delete.is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+
+use super::model::GENESIS_HASH;
+use super::{AuditEntry, AuditEvent, AuditFilter, AuditPage, AuditSink};
+use crate::storage::lock::lock_key;
+use crate::storage::DbError;
+use crate::users::model::PageRequest;
+
+const EVENT_COLUMNS: &str = "seq, id, actor_id, action, target_type, target_id, before_json, after_json,
+                             ip_address, request_id, created_at, prev_hash, hash";
+
+/// Writes the log to the `audit_events` table. Appends from every instance
+/// are serialized with an advisory lock so the chain never forks.
+pub struct PostgresAuditSink {
+    pool: PgPool,
+}
+
+impl PostgresAuditSink {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn parse_json(text: Option<String>) -> Result<Option<Value>, DbError> {
+    text.map(|t| serde_json::from_str(&t))
+        .transpose()
+        .map_err(|e| DbError::Query(format!("Failed to decode audit event: {}", e)))
+}
+
+fn event_from_row(row: &PgRow) -> Result<AuditEvent, DbError> {
+    Ok(AuditEvent {
+        seq: row.try_get("seq")?,
+        id: row.try_get("id")?,
+        actor_id: row.try_get("actor_id")?,
+        action: row.try_get("action")?,
+        target_type: row.try_get("target_type")?,
+        target_id: row.try_get("target_id")?,
+        before: parse_json(row.try_get("before_json")?)?,
+        after: parse_json(row.try_get("after_json")?)?,
+        ip_address: row.try_get("ip_address")?,
+        request_id: row.try_get("request_id")?,
+        created_at: row.try_get("created_at")?,
+        prev_hash: row.try_get("prev_hash")?,
+        hash: row.try_get("hash")?,
+    })
+}
+
+fn push_filter<'a>(qb: &mut QueryBuilder<'a, Postgres>, filter: &'a AuditFilter) {
+    qb.push(" FROM audit_events WHERE TRUE");
+    if let Some(actor_id) = filter.actor_id {
+        qb.push(" AND actor_id = ").push_bind(actor_id);
+    }
+    if let Some(action) = &filter.action {
+        qb.push(" AND action = ").push_bind(action);
+    }
+    if let Some(target_type) = &filter.target_type {
+        qb.push(" AND target_type = ").push_bind(target_type);
+    }
+    if let Some(target_id) = &filter.target_id {
+        qb.push(" AND target_id = ").push_bind(target_id);
+    }
+    if let Some(since) = filter.since {
+        qb.push(" AND created_at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        qb.push(" AND created_at < ").push_bind(until);
+    }
+}
+
+#[async_trait]
+impl AuditSink for PostgresAuditSink {
+    async fn record(&self, entry: &AuditEntry) -> Result<AuditEvent, DbError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(lock_key("audit_events"))
+            .execute(&mut *tx)
+            .await?;
+
+        let last: Option<(i64, String)> =
+            sqlx::query_as("SELECT seq, hash FROM audit_events ORDER BY seq DESC LIMIT 1")
+                .fetch_optional(&mut *tx)
+                .await?;
+        let event = match &last {
+            Some((seq, hash)) => AuditEvent::chain(entry, seq + 1, hash),
+            None => AuditEvent::chain(entry, 1, GENESIS_HASH),
+        };
+
+        sqlx::query(&format!(
+            "INSERT INTO audit_events ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+            EVENT_COLUMNS
+        ))
+        .bind(event.seq)
+        .bind(event.id)
+        .bind(event.actor_id)
+        .bind(&event.action)
+        .bind(&event.target_type)
+        .bind(&event.target_id)
+        .bind(event.before.as_ref().map(Value::to_string))
+        .bind(event.after.as_ref().map(Value::to_string))
+        .bind(&event.ip_address)
+        .bind(&event.request_id)
+        .bind(event.created_at)
+        .bind(&event.prev_hash)
+        .bind(&event.hash)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(event)
+    }
+
+    async fn list(&self, filter: &AuditFilter, page: PageRequest) -> Result<AuditPage, DbError> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*)");
+        push_filter(&mut count, filter);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut query = QueryBuilder::new(format!("SELECT {}", EVENT_COLUMNS));
+        push_filter(&mut query, filter);
+        query.push(" ORDER BY seq DESC LIMIT ").push_bind(page.per_page);
+        query.push(" OFFSET ").push_bind(page.offset());
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let events = rows.iter().map(event_from_row).collect::<Result<_, _>>()?;
+        Ok(AuditPage { events, total })
+    }
+
+    async fn events_after(&self, after: i64, limit: i64) -> Result<Vec<AuditEvent>, DbError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM audit_events WHERE seq > $1 ORDER BY seq LIMIT $2",
+            EVENT_COLUMNS
+        ))
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+}
@@ -0,0 +1,152 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::sqlite::{SqlitePool, SqliteRow};
+use sqlx::{QueryBuilder, Row, Sqlite};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::model::GENESIS_HASH;
+use super::{AuditEntry, AuditEvent, AuditFilter, AuditPage, AuditSink};
+use crate::storage::DbError;
+use crate::users::model::PageRequest;
+
+const EVENT_COLUMNS: &str = "seq, id, actor_id, action, target_type, target_id, before_json, after_json,
+                             ip_address, request_id, created_at, prev_hash, hash";
+
+/// Writes the log to the `audit_events` table of a SQLite store. Appends are
+/// serialized within the process, which is the only writer SQLite expects.
+pub struct SqliteAuditSink {
+    pool: SqlitePool,
+    append: Mutex<()>,
+}
+
+impl SqliteAuditSink {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool, append: Mutex::new(()) }
+    }
+
+    #[cfg(test)]
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+fn decode_err(e: impl std::fmt::Display) -> DbError {
+    DbError::Query(format!("Failed to decode audit event: {}", e))
+}
+
+fn parse_json(text: Option<String>) -> Result<Option<Value>, DbError> {
+    text.map(|t| serde_json::from_str(&t)).transpose().map_err(decode_err)
+}
+
+fn parse_uuid(text: Option<String>) -> Result<Option<Uuid>, DbError> {
+    text.map(|t| Uuid::parse_str(&t)).transpose().map_err(decode_err)
+}
+
+fn event_from_row(row: &SqliteRow) -> Result<AuditEvent, DbError> {
+    Ok(AuditEvent {
+        seq: row.try_get("seq")?,
+        id: parse_uuid(row.try_get("id")?)?.ok_or_else(|| decode_err("missing id"))?,
+        actor_id: parse_uuid(row.try_get("actor_id")?)?,
+        action: row.try_get("action")?,
+        target_type: row.try_get("target_type")?,
+        target_id: row.try_get("target_id")?,
+        before: parse_json(row.try_get("before_json")?)?,
+        after: parse_json(row.try_get("after_json")?)?,
+        ip_address: row.try_get("ip_address")?,
+        request_id: row.try_get("request_id")?,
+        created_at: row.try_get("created_at")?,
+        prev_hash: row.try_get("prev_hash")?,
+        hash: row.try_get("hash")?,
+    })
+}
+
+fn push_filter<'a>(qb: &mut QueryBuilder<'a, Sqlite>, filter: &'a AuditFilter) {
+    qb.push(" FROM audit_events WHERE 1 = 1");
+    if let Some(actor_id) = filter.actor_id {
+        qb.push(" AND actor_id = ").push_bind(actor_id.to_string());
+    }
+    if let Some(action) = &filter.action {
+        qb.push(" AND action = ").push_bind(action);
+    }
+    if let Some(target_type) = &filter.target_type {
+        qb.push(" AND target_type = ").push_bind(target_type);
+    }
+    if let Some(target_id) = &filter.target_id {
+        qb.push(" AND target_id = ").push_bind(target_id);
+    }
+    if let Some(since) = filter.since {
+        qb.push(" AND created_at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        qb.push(" AND created_at < ").push_bind(until);
+    }
+}
+
+#[async_trait]
+impl AuditSink for SqliteAuditSink {
+    async fn record(&self, entry: &AuditEntry) -> Result<AuditEvent, DbError> {
+        let _append = self.append.lock().await;
+        let mut tx = self.pool.begin().await?;
+
+        let last: Option<(i64, String)> =
+            sqlx::query_as("SELECT seq, hash FROM audit_events ORDER BY seq DESC LIMIT 1")
+                .fetch_optional(&mut *tx)
+                .await?;
+        let event = match &last {
+            Some((seq, hash)) => AuditEvent::chain(entry, seq + 1, hash),
+            None => AuditEvent::chain(entry, 1, GENESIS_HASH),
+        };
+
+        sqlx::query(&format!(
+            "INSERT INTO audit_events ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+            EVENT_COLUMNS
+        ))
+        .bind(event.seq)
+        .bind(event.id.to_string())
+        .bind(event.actor_id.map(|id| id.to_string()))
+        .bind(&event.action)
+        .bind(&event.target_type)
+        .bind(&event.target_id)
+        .bind(event.before.as_ref().map(Value::to_string))
+        .bind(event.after.as_ref().map(Value::to_string))
+        .bind(&event.ip_address)
+        .bind(&event.request_id)
+        .bind(event.created_at)
+        .bind(&event.prev_hash)
+        .bind(&event.hash)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(event)
+    }
+
+    async fn list(&self, filter: &AuditFilter, page: PageRequest) -> Result<AuditPage, DbError> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*)");
+        push_filter(&mut count, filter);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut query = QueryBuilder::new(format!("SELECT {}", EVENT_COLUMNS));
+        push_filter(&mut query, filter);
+        query.push(" ORDER BY seq DESC LIMIT ").push_bind(page.per_page);
+        query.push(" OFFSET ").push_bind(page.offset());
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let events = rows.iter().map(event_from_row).collect::<Result<_, _>>()?;
+        Ok(AuditPage { events, total })
+    }
+
+    async fn events_after(&self, after: i64, limit: i64) -> Result<Vec<AuditEvent>, DbError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM audit_events WHERE seq > $1 ORDER BY seq LIMIT $2",
+            EVENT_COLUMNS
+        ))
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+}
@@ -0,0 +1,197 @@
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::app::AppState;
+use crate::audit::{self, actions, AuditEntry};
+use crate::auth::middleware::AuthenticatedUser;
+use crate::auth::model::{AuthMethod, LoginEvent};
+use crate::email::messages;
+use crate::routing::client_ip::ClientIp;
+use crate::routing::error::{ApiError, WithApiError};
+use crate::users::model::User;
+use crate::utils;
+
+pub const DEFAULT_HISTORY_LIMIT: i64 = 50;
+pub const MAX_HISTORY_LIMIT: i64 = 200;
+
+/// Request metadata captured for every login attempt.
+#[derive(Debug, Clone)]
+pub struct LoginContext {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub device_fingerprint: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl LoginContext {
+    pub fn from_request(headers: &HeaderMap, client_ip: ClientIp, device_info: Option<&str>) -> Self {
+        let user_agent = utils::user_agent(headers);
+        let device_fingerprint = device_fingerprint(user_agent.as_deref(), device_info);
+
+        Self {
+            ip_address: client_ip.to_column(),
+            user_agent,
+            device_fingerprint,
+            request_id: utils::request_id(headers),
+        }
+    }
+}
+
+/// Stable identifier for the client device, derived from the user agent and
+/// any device info the client sent. Returns `None` when neither is present.
+pub fn device_fingerprint(user_agent: Option<&str>, device_info: Option<&str>) -> Option<String> {
+    if user_agent.is_none() && device_info.is_none() {
+        return None;
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(user_agent.unwrap_or_default().as_bytes());
+    hasher.update([0u8]);
+    hasher.update(device_info.unwrap_or_default().as_bytes());
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+fn build_event(
+    ctx: &LoginContext,
+    method: AuthMethod,
+    email: &str,
+    user_id: Option<Uuid>,
+    failure_reason: Option<&str>,
+) -> LoginEvent {
+    LoginEvent {
+        id: Uuid::new_v4(),
+        user_id,
+        email: email.to_string(),
+        success: failure_reason.is_none(),
+        failure_reason: failure_reason.map(|r| r.to_string()),
+        ip_address: ctx.ip_address.clone(),
+        user_agent: ctx.user_agent.clone(),
+        auth_method: method,
+        device_fingerprint: ctx.device_fingerprint.clone(),
+        created_at: Utc::now(),
+    }
+}
+
+/// Logs rather than returns a failure, like `audit::record`, so that an
+/// unavailable history table doesn't lock everyone out.
+async fn store_event(state: &AppState, event: &LoginEvent) {
+    if let Err(e) = state.storage.record_login_event(event).await {
+        tracing::error!(email = %event.email, success = event.success, error = %e, "failed to record login event");
+    }
+}
+
+pub async fn record_failure(
+    state: &AppState,
+    ctx: &LoginContext,
+    method: AuthMethod,
+    email: &str,
+    user_id: Option<Uuid>,
+    reason: &str,
+) {
+    state.metrics.record_login(&method, Some(reason));
+    let event = build_event(ctx, method, email, user_id, Some(reason));
+    store_event(state, &event).await;
+
+    // The caller never proved who they are, so the account is only the target.
+    let mut entry = AuditEntry { actor_id: None, ..audit_entry(ctx, actions::LOGIN_FAILED, user_id) };
+    entry.after = Some(serde_json::json!({ "email": email, "method": event.auth_method, "reason": reason }));
+    audit::record(&*state.audit, entry).await;
+}
+
+fn audit_entry(ctx: &LoginContext, action: &str, user_id: Option<Uuid>) -> AuditEntry {
+    AuditEntry {
+        actor_id: user_id,
+        action: action.to_string(),
+        target_type: "user".to_string(),
+        target_id: user_id.map(|id| id.to_string()),
+        ip_address: ctx.ip_address.clone(),
+        request_id: ctx.request_id.clone(),
+        ..Default::default()
+    }
+}
+
+/// Records a successful login and, if it came from a device the user has not
+/// signed in from before, sends them a "new sign-in" notification. The very
+/// first login of an account is not reported.
+pub async fn record_success(state: &AppState, ctx: &LoginContext, method: AuthMethod, user: &User) {
+    let is_new_device = match &ctx.device_fingerprint {
+        Some(fingerprint) if user.last_login.is_some() => {
+            matches!(state.storage.is_known_device(user.id, fingerprint).await, Ok(false))
+        }
+        _ => false,
+    };
+
+    state.metrics.record_login(&method, None);
+    let event = build_event(ctx, method, &user.email, Some(user.id), None);
+    store_event(state, &event).await;
+
+    let mut entry = audit_entry(ctx, actions::LOGIN_SUCCEEDED, Some(user.id));
+    entry.after = Some(serde_json::json!({ "method": event.auth_method }));
+    audit::record(&*state.audit, entry).await;
+
+    if is_new_device {
+        let mailer = state.mailer.clone();
+        let message = messages::new_sign_in(user, &event);
+        tokio::spawn(async move {
+            if let Err(e) = mailer.send(&message).await {
+                tracing::warn!(error = %e, "failed to send new sign-in notification");
+            }
+        });
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginHistoryQuery {
+    pub limit: Option<i64>,
+}
+
+impl LoginHistoryQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_HISTORY_LIMIT)
+            .clamp(1, MAX_HISTORY_LIMIT)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoginHistoryResponse {
+    pub events: Vec<LoginEvent>,
+}
+
+pub async fn login_history(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthenticatedUser>,
+    WithApiError(Query(query)): WithApiError<Query<LoginHistoryQuery>>,
+) -> Result<Json<LoginHistoryResponse>, ApiError> {
+    let events = state.storage.get_login_events(auth.claims.sub, query.limit()).await?;
+
+    Ok(Json(LoginHistoryResponse { events }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        let a = device_fingerprint(Some("Mozilla/5.0"), Some("iPhone"));
+        let b = device_fingerprint(Some("Mozilla/5.0"), Some("iPhone"));
+        assert_eq!(a, b);
+        assert_eq!(a.unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_fingerprint_distinguishes_devices() {
+        let a = device_fingerprint(Some("Mozilla/5.0"), None);
+        let b = device_fingerprint(Some("curl/8.0"), None);
+        assert_ne!(a, b);
+        assert!(device_fingerprint(None, None).is_none());
+    }
+}
@@ -1,9 +1,18 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    Json,
+};
 use serde::Deserialize;
+use uuid::Uuid;
 
 use crate::app::AppState;
-use crate::auth::model::{LoginRequest, LoginResponse, AccountInfo, AccountStatus};
+use crate::auth::model::{LoginRequest, LoginResponse, AccountInfo, AccountStatus, AuthMethod};
 use crate::auth::account_levels::get_all_capabilities;
+use crate::auth::history::{self, LoginContext};
+use crate::auth::sessions;
+use crate::routing::client_ip::ClientIp;
+use crate::routing::error::{ApiError, WithApiError};
 use crate::users::model::UserProfile;
 
 #[derive(Debug, Deserialize)]
@@ -13,86 +22,79 @@ pub struct AdminLoginRequest {
     pub device_info: Option<String>,
 }
 
-#[derive(Debug, serde::Serialize)]
-pub struct AuthError {
-    pub error: String,
-    pub code: String,
+fn invalid_credentials() -> ApiError {
+    ApiError::unauthorized("INVALID_CREDENTIALS", "Invalid email or password")
 }
 
-impl AuthError {
-    fn invalid_credentials() -> Self {
-        Self {
-            error: "Invalid email or password".to_string(),
-            code: "INVALID_CREDENTIALS".to_string(),
-        }
-    }
-
-    fn account_inactive() -> Self {
-        Self {
-            error: "Account is not active".to_string(),
-            code: "ACCOUNT_INACTIVE".to_string(),
-        }
-    }
+fn account_inactive() -> ApiError {
+    ApiError::forbidden("ACCOUNT_INACTIVE", "Account is not active")
+}
 
-    fn not_admin() -> Self {
-        Self {
-            error: "User is not an admin".to_string(),
-            code: "NOT_ADMIN".to_string(),
-        }
-    }
+fn not_admin() -> ApiError {
+    ApiError::forbidden("NOT_ADMIN", "User is not an admin")
+}
 
-    fn internal_error() -> Self {
-        Self {
-            error: "Internal server error".to_string(),
-            code: "INTERNAL_ERROR".to_string(),
-        }
-    }
+/// Records the failed attempt in the login history and passes the error on.
+async fn reject(
+    state: &AppState,
+    ctx: &LoginContext,
+    method: AuthMethod,
+    email: &str,
+    user_id: Option<Uuid>,
+    error: ApiError,
+) -> ApiError {
+    history::record_failure(state, ctx, method, email, user_id, error.code).await;
+    error
 }
 
 pub async fn admin_login(
     State(state): State<AppState>,
-    Json(req): Json<AdminLoginRequest>,
-) -> Result<Json<LoginResponse>, (StatusCode, Json<AuthError>)> {
-    let user = state
-        .storage
-        .get_user_by_email(&req.email)
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthError::invalid_credentials())))?;
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    WithApiError(Json(req)): WithApiError<Json<AdminLoginRequest>>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let ctx = LoginContext::from_request(&headers, client_ip, req.device_info.as_deref());
+    let method = AuthMethod::AdminPassword;
+
+    let user = match state.storage.get_user_by_email(&req.email).await? {
+        Some(user) => user,
+        None => {
+            return Err(reject(&state, &ctx, method, &req.email, None, invalid_credentials()).await);
+        }
+    };
 
-    let password_valid = bcrypt::verify(&req.password, &user.password_hash)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?;
+    let password_valid = bcrypt::verify(&req.password, &user.password_hash).map_err(|_| ApiError::internal())?;
 
     if !password_valid {
-        return Err((StatusCode::UNAUTHORIZED, Json(AuthError::invalid_credentials())));
+        return Err(reject(&state, &ctx, method, &req.email, Some(user.id), invalid_credentials()).await);
     }
 
     if !user.is_active {
-        return Err((StatusCode::FORBIDDEN, Json(AuthError::account_inactive())));
+        return Err(reject(&state, &ctx, method, &req.email, Some(user.id), account_inactive()).await);
     }
 
-    let admin = state
-        .storage
-        .get_admin_by_user_id(user.id)
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?
-        .ok_or_else(|| (StatusCode::FORBIDDEN, Json(AuthError::not_admin())))?;
+    let admin = match state.storage.get_admin_by_user_id(user.id).await? {
+        Some(admin) => admin,
+        None => {
+            return Err(reject(&state, &ctx, method, &req.email, Some(user.id), not_admin()).await);
+        }
+    };
 
     let account = state
         .storage
         .get_account_by_user_id(user.id)
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?
-        .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?;
+        .await?
+        .ok_or_else(ApiError::internal)?;
 
     if account.account_status != AccountStatus::Active {
-        return Err((StatusCode::FORBIDDEN, Json(AuthError::account_inactive())));
+        return Err(reject(&state, &ctx, method, &req.email, Some(user.id), account_inactive()).await);
     }
 
     let token_pair = state
         .token_service
         .generate_admin_tokens(&user, &account, &admin)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?;
+        .map_err(|_| ApiError::internal())?;
+    sessions::track(&state.validation, user.id, &token_pair);
 
     let refresh_record = state.token_service.create_token_record(
         user.id,
@@ -103,6 +105,7 @@ pub async fn admin_login(
     );
 
     let _ = state.storage.store_token(&refresh_record).await;
+    history::record_success(&state, &ctx, method, &user).await;
     let _ = state.storage.update_user_last_login(user.id).await;
 
     let user_profile = UserProfile::from(&user);
@@ -123,41 +126,45 @@ pub async fn admin_login(
 
 pub async fn user_login(
     State(state): State<AppState>,
-    Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, (StatusCode, Json<AuthError>)> {
-    let user = state
-        .storage
-        .get_user_by_email(&req.email)
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthError::invalid_credentials())))?;
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    WithApiError(Json(req)): WithApiError<Json<LoginRequest>>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let ctx = LoginContext::from_request(&headers, client_ip, None);
+    let method = AuthMethod::Password;
 
-    let password_valid = bcrypt::verify(&req.password, &user.password_hash)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?;
+    let user = match state.storage.get_user_by_email(&req.email).await? {
+        Some(user) => user,
+        None => {
+            return Err(reject(&state, &ctx, method, &req.email, None, invalid_credentials()).await);
+        }
+    };
+
+    let password_valid = bcrypt::verify(&req.password, &user.password_hash).map_err(|_| ApiError::internal())?;
 
     if !password_valid {
-        return Err((StatusCode::UNAUTHORIZED, Json(AuthError::invalid_credentials())));
+        return Err(reject(&state, &ctx, method, &req.email, Some(user.id), invalid_credentials()).await);
     }
 
     if !user.is_active {
-        return Err((StatusCode::FORBIDDEN, Json(AuthError::account_inactive())));
+        return Err(reject(&state, &ctx, method, &req.email, Some(user.id), account_inactive()).await);
     }
 
     let account = state
         .storage
         .get_account_by_user_id(user.id)
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?
-        .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?;
+        .await?
+        .ok_or_else(ApiError::internal)?;
 
     if account.account_status != AccountStatus::Active {
-        return Err((StatusCode::FORBIDDEN, Json(AuthError::account_inactive())));
+        return Err(reject(&state, &ctx, method, &req.email, Some(user.id), account_inactive()).await);
     }
 
     let token_pair = state
         .token_service
         .generate_user_tokens(&user, &account)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(AuthError::internal_error())))?;
+        .map_err(|_| ApiError::internal())?;
+    sessions::track(&state.validation, user.id, &token_pair);
 
     let refresh_record = state.token_service.create_token_record(
         user.id,
@@ -168,6 +175,7 @@ pub async fn user_login(
     );
 
     let _ = state.storage.store_token(&refresh_record).await;
+    history::record_success(&state, &ctx, method, &user).await;
     let _ = state.storage.update_user_last_login(user.id).await;
 
     let user_profile = UserProfile::from(&user);
@@ -185,3 +193,74 @@ pub async fn user_login(
         },
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        extract::ConnectInfo,
+        http::{Request, StatusCode},
+        Router,
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::app::create_app_with_config;
+    use crate::auth::model::LoginEvent;
+    use crate::storage::{MemoryStorage, StorageLayer};
+    use crate::test_support::{self, PASSWORD};
+
+    async fn app_with_user() -> (Router, Arc<MemoryStorage>, Uuid) {
+        let storage = Arc::new(MemoryStorage::new());
+        let user = test_support::active_user(&*storage, "history").await;
+        let app = create_app_with_config(storage.clone(), test_support::config()).await.main;
+        (app, storage, user.id)
+    }
+
+    /// Logs in from 203.0.113.9, which also claims to be forwarding for
+    /// someone else.
+    async fn login(app: &Router, password: &str) -> StatusCode {
+        let body = json!({ "email": "history@example.com", "password": password });
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/auth/login")
+            .header("content-type", "application/json")
+            .header("x-forwarded-for", "192.0.2.1")
+            .extension(ConnectInfo(SocketAddr::from(([203, 0, 113, 9], 40000))))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    async fn only_event(storage: &MemoryStorage, user_id: Uuid) -> LoginEvent {
+        let mut events = storage.get_login_events(user_id, 10).await.unwrap();
+        assert_eq!(events.len(), 1);
+        events.remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_successful_login_is_recorded() {
+        let (app, storage, user_id) = app_with_user().await;
+        assert_eq!(login(&app, PASSWORD).await, StatusCode::OK);
+
+        let event = only_event(&storage, user_id).await;
+        assert!(event.success);
+        assert_eq!(event.auth_method, AuthMethod::Password);
+        assert_eq!(event.ip_address.as_deref(), Some("203.0.113.9"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_login_is_recorded() {
+        let (app, storage, user_id) = app_with_user().await;
+        assert_eq!(login(&app, "wrong-password").await, StatusCode::UNAUTHORIZED);
+
+        let event = only_event(&storage, user_id).await;
+        assert!(!event.success);
+        assert_eq!(event.failure_reason.as_deref(), Some("INVALID_CREDENTIALS"));
+        assert_eq!(event.ip_address.as_deref(), Some("203.0.113.9"));
+    }
+}
@@ -1,50 +1,44 @@
+use std::future::Future;
+use std::pin::Pin;
+
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
     middleware::Next,
     response::Response,
-    Json,
 };
-use serde::Serialize;
 
 use crate::app::AppState;
 use crate::auth::model::Claims;
 use crate::auth::tokens::TokenService;
+use crate::routing::error::ApiError;
+use crate::routing::request_id;
 
-#[derive(Debug, Serialize)]
-pub struct AuthMiddlewareError {
-    pub error: String,
-    pub code: String,
-}
+/// Verifies the bearer token and that it hasn't been revoked.
+fn authenticate(state: &AppState, request: &Request) -> Result<Claims, ApiError> {
+    let auth_header = request
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| ApiError::unauthorized("MISSING_TOKEN", "Missing authorization header"))?;
 
-impl AuthMiddlewareError {
-    fn missing_token() -> Self {
-        Self {
-            error: "Missing authorization header".to_string(),
-            code: "MISSING_TOKEN".to_string(),
-        }
-    }
+    let token = TokenService::extract_bearer_token(auth_header)
+        .ok_or_else(|| ApiError::unauthorized("MISSING_TOKEN", "Missing authorization header"))?;
 
-    fn invalid_token() -> Self {
-        Self {
-            error: "Invalid or expired token".to_string(),
-            code: "INVALID_TOKEN".to_string(),
-        }
-    }
+    let claims = state
+        .token_service
+        .verify_access_token(token)
+        .map_err(|_| ApiError::unauthorized("INVALID_TOKEN", "Invalid or expired token"))?;
 
-    fn token_revoked() -> Self {
-        Self {
-            error: "Token has been revoked".to_string(),
-            code: "TOKEN_REVOKED".to_string(),
-        }
+    if state.validation.is_jti_blacklisted(&claims.jti) {
+        return Err(ApiError::unauthorized("TOKEN_REVOKED", "Token has been revoked"));
     }
+    Ok(claims)
+}
 
-    fn not_admin() -> Self {
-        Self {
-            error: "Admin access required".to_string(),
-            code: "NOT_ADMIN".to_string(),
-        }
-    }
+/// The caller's claims if the request is authenticated, for layers that
+/// treat anonymous requests differently rather than refusing them.
+pub fn caller(state: &AppState, request: &Request) -> Option<Claims> {
+    authenticate(state, request).ok()
 }
 
 #[derive(Clone)]
@@ -56,25 +50,10 @@ pub async fn require_auth(
     State(state): State<AppState>,
     mut request: Request,
     next: Next,
-) -> Result<Response, (StatusCode, Json<AuthMiddlewareError>)> {
-    let auth_header = request
-        .headers()
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::missing_token())))?;
-
-    let token = TokenService::extract_bearer_token(auth_header)
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::missing_token())))?;
-
-    let claims = state
-        .token_service
-        .verify_access_token(token)
-        .map_err(|_| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::invalid_token())))?;
-
-    if state.validation.is_jti_blacklisted(&claims.jti) {
-        return Err((StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::token_revoked())));
-    }
+) -> Result<Response, ApiError> {
+    let claims = authenticate(&state, &request)?;
 
+    request_id::record_user(claims.sub);
     request.extensions_mut().insert(AuthenticatedUser { claims });
 
     Ok(next.run(request).await)
@@ -84,29 +63,14 @@ pub async fn require_admin(
     State(state): State<AppState>,
     mut request: Request,
     next: Next,
-) -> Result<Response, (StatusCode, Json<AuthMiddlewareError>)> {
-    let auth_header = request
-        .headers()
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::missing_token())))?;
-
-    let token = TokenService::extract_bearer_token(auth_header)
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::missing_token())))?;
-
-    let claims = state
-        .token_service
-        .verify_access_token(token)
-        .map_err(|_| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::invalid_token())))?;
-
-    if state.validation.is_jti_blacklisted(&claims.jti) {
-        return Err((StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::token_revoked())));
-    }
+) -> Result<Response, ApiError> {
+    let claims = authenticate(&state, &request)?;
 
     if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, Json(AuthMiddlewareError::not_admin())));
+        return Err(ApiError::forbidden("NOT_ADMIN", "Admin access required"));
     }
 
+    request_id::record_user(claims.sub);
     request.extensions_mut().insert(AuthenticatedUser { claims });
 
     Ok(next.run(request).await)
@@ -116,45 +80,24 @@ pub fn get_authenticated_user(request: &Request) -> Option<&AuthenticatedUser> {
     request.extensions().get::<AuthenticatedUser>()
 }
 
+/// The future returned by the middleware `require_capability` builds.
+type MiddlewareFuture = Pin<Box<dyn Future<Output = Result<Response, ApiError>> + Send>>;
+
 pub fn require_capability(
     capability: &'static str,
-) -> impl Fn(
-    State<AppState>,
-    Request,
-    Next,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response, (StatusCode, Json<AuthMiddlewareError>)>> + Send>>
-       + Clone
-       + Send {
+) -> impl Fn(State<AppState>, Request, Next) -> MiddlewareFuture + Clone + Send {
     move |State(state): State<AppState>, mut request: Request, next: Next| {
         Box::pin(async move {
-            let auth_header = request
-                .headers()
-                .get("Authorization")
-                .and_then(|h| h.to_str().ok())
-                .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::missing_token())))?;
-
-            let token = TokenService::extract_bearer_token(auth_header)
-                .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::missing_token())))?;
-
-            let claims = state
-                .token_service
-                .verify_access_token(token)
-                .map_err(|_| (StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::invalid_token())))?;
-
-            if state.validation.is_jti_blacklisted(&claims.jti) {
-                return Err((StatusCode::UNAUTHORIZED, Json(AuthMiddlewareError::token_revoked())));
-            }
+            let claims = authenticate(&state, &request)?;
 
             if !claims.capabilities.contains(&capability.to_string()) {
-                return Err((
-                    StatusCode::FORBIDDEN,
-                    Json(AuthMiddlewareError {
-                        error: format!("Missing required capability: {}", capability),
-                        code: "MISSING_CAPABILITY".to_string(),
-                    }),
+                return Err(ApiError::forbidden(
+                    "MISSING_CAPABILITY",
+                    format!("Missing required capability: {}", capability),
                 ));
             }
 
+            request_id::record_user(claims.sub);
             request.extensions_mut().insert(AuthenticatedUser { claims });
 
             Ok(next.run(request).await)
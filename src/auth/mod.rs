@@ -6,5 +6,7 @@ pub mod middleware;
 pub mod r#in;
 pub mod out;
 pub mod new;
+pub mod history;
+pub mod sessions;
 
 pub use tokens::TokenService;
@@ -14,9 +14,45 @@ pub struct UserAccount {
     pub status_changed_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Bumped by every edit; updates must name the version they were based on.
+    #[serde(default)]
+    pub version: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone)]
+pub struct CreateAccountRequest {
+    pub user_id: Uuid,
+    pub account_level: AccountLevel,
+    pub account_status: AccountStatus,
+    pub capabilities: Vec<String>,
+}
+
+impl CreateAccountRequest {
+    /// A new self-registered account: free tier, pending activation.
+    pub fn default_for(user_id: Uuid) -> Self {
+        Self {
+            user_id,
+            account_level: AccountLevel::Free,
+            account_status: AccountStatus::Pending,
+            capabilities: vec![],
+        }
+    }
+}
+
+/// Changes for `StorageLayer::update_account`. Unset fields are left as they
+/// are; `status_reason` and `changed_by` are only recorded along with a new
+/// `account_status`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateAccountRequest {
+    pub account_level: Option<AccountLevel>,
+    pub account_status: Option<AccountStatus>,
+    pub capabilities: Option<Vec<String>>,
+    pub status_reason: Option<String>,
+    #[serde(skip)]
+    pub changed_by: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "account_level", rename_all = "lowercase")]
 pub enum AccountLevel {
     Free,
@@ -93,8 +129,47 @@ pub struct Claims {
     pub exp: usize,
 }
 
+impl Claims {
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.exp as i64, 0).unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UserRole {
     User,
     Admin,
 }
+
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LoginEvent {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub email: String,
+    pub success: bool,
+    pub failure_reason: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub auth_method: AuthMethod,
+    pub device_fingerprint: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "auth_method", rename_all = "lowercase")]
+pub enum AuthMethod {
+    Password,
+    AdminPassword,
+    AdminPanel,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Password => "password",
+            AuthMethod::AdminPassword => "admin_password",
+            AuthMethod::AdminPanel => "admin_panel",
+        }
+    }
+}
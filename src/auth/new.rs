@@ -1,11 +1,9 @@
-use axum::{
-    extract::State,
-    http::StatusCode,
-    Json,
-};
+use axum::{extract::State, Json};
 use serde::{Deserialize, Serialize};
 
 use crate::app::AppState;
+use crate::auth::model::CreateAccountRequest;
+use crate::routing::error::{ApiError, WithApiError};
 use crate::users::model::CreateUserRequest;
 
 #[derive(Debug, Deserialize)]
@@ -24,71 +22,22 @@ pub struct RegisterResponse {
     pub user_id: Option<uuid::Uuid>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct RegisterError {
-    pub error: String,
-    pub code: String,
-}
-
-impl RegisterError {
-    fn email_exists() -> Self {
-        Self {
-            error: "Email already registered".to_string(),
-            code: "EMAIL_EXISTS".to_string(),
-        }
-    }
-
-    fn username_exists() -> Self {
-        Self {
-            error: "Username already taken".to_string(),
-            code: "USERNAME_EXISTS".to_string(),
-        }
-    }
-
-    fn weak_password() -> Self {
-        Self {
-            error: "Password must be at least 8 characters".to_string(),
-            code: "WEAK_PASSWORD".to_string(),
-        }
-    }
-
-    fn invalid_email() -> Self {
-        Self {
-            error: "Invalid email format".to_string(),
-            code: "INVALID_EMAIL".to_string(),
-        }
-    }
-
-    fn internal_error() -> Self {
-        Self {
-            error: "Internal server error".to_string(),
-            code: "INTERNAL_ERROR".to_string(),
-        }
-    }
-}
-
 pub async fn register(
     State(state): State<AppState>,
-    Json(req): Json<RegisterRequest>,
-) -> Result<Json<RegisterResponse>, (StatusCode, Json<RegisterError>)> {
+    WithApiError(Json(req)): WithApiError<Json<RegisterRequest>>,
+) -> Result<Json<RegisterResponse>, ApiError> {
+    let mut invalid = ApiError::bad_request("VALIDATION_FAILED", "Some fields are invalid");
     if !req.email.contains('@') || !req.email.contains('.') {
-        return Err((StatusCode::BAD_REQUEST, Json(RegisterError::invalid_email())));
+        invalid = invalid.field("email", "Invalid email format");
     }
-
     if req.password.len() < 8 {
-        return Err((StatusCode::BAD_REQUEST, Json(RegisterError::weak_password())));
+        invalid = invalid.field("password", "Password must be at least 8 characters");
     }
-
-    if let Ok(Some(_)) = state.storage.get_user_by_email(&req.email).await {
-        return Err((StatusCode::CONFLICT, Json(RegisterError::email_exists())));
-    }
-
-    if let Ok(Some(_)) = state.storage.get_user_by_username(&req.username).await {
-        return Err((StatusCode::CONFLICT, Json(RegisterError::username_exists())));
+    if !invalid.fields.is_empty() {
+        return Err(invalid);
     }
 
-    let password_hash = bcrypt::hash(&req.password, bcrypt::DEFAULT_COST)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(RegisterError::internal_error())))?;
+    let password_hash = bcrypt::hash(&req.password, bcrypt::DEFAULT_COST).map_err(|_| ApiError::internal())?;
 
     let create_req = CreateUserRequest {
         email: req.email,
@@ -98,13 +47,19 @@ pub async fn register(
         last_name: req.last_name,
     };
 
+    // The user and their account are created together so a failure can't leave
+    // behind a user who is unable to log in. A taken email or username comes
+    // back from the unique constraints as `DbError::Duplicate`.
     let user = state
         .storage
-        .create_user(&create_req, &password_hash)
-        .await
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Json(RegisterError::internal_error())))?;
-
-    let _ = state.storage.create_account(user.id).await;
+        .transaction(|tx| {
+            Box::pin(async move {
+                let user = tx.create_user(&create_req, &password_hash).await?;
+                tx.create_account(&CreateAccountRequest::default_for(user.id)).await?;
+                Ok(user)
+            })
+        })
+        .await?;
 
     Ok(Json(RegisterResponse {
         success: true,
@@ -1,12 +1,16 @@
 use axum::{
     extract::State,
-    http::{StatusCode, HeaderMap},
+    http::HeaderMap,
     Json,
 };
 use serde::Serialize;
 
 use crate::app::AppState;
+use crate::audit::{self, actions, AuditContext, AuditEntry};
+use crate::auth::model::Claims;
 use crate::auth::tokens::TokenService;
+use crate::routing::client_ip::ClientIp;
+use crate::routing::error::ApiError;
 
 #[derive(Debug, Serialize)]
 pub struct LogoutResponse {
@@ -14,48 +18,33 @@ pub struct LogoutResponse {
     pub message: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct LogoutError {
-    pub error: String,
-    pub code: String,
-}
-
-impl LogoutError {
-    fn missing_token() -> Self {
-        Self {
-            error: "Missing or invalid authorization header".to_string(),
-            code: "MISSING_TOKEN".to_string(),
-        }
-    }
+/// The claims of the still-valid access token the request is signed with.
+fn verified_claims(state: &AppState, headers: &HeaderMap) -> Result<Claims, ApiError> {
+    let token = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(TokenService::extract_bearer_token)
+        .ok_or_else(|| ApiError::unauthorized("MISSING_TOKEN", "Missing or invalid authorization header"))?;
 
-    fn invalid_token() -> Self {
-        Self {
-            error: "Invalid token".to_string(),
-            code: "INVALID_TOKEN".to_string(),
-        }
-    }
+    state
+        .token_service
+        .verify_access_token(token)
+        .map_err(|_| ApiError::unauthorized("INVALID_TOKEN", "Invalid token"))
 }
 
 pub async fn logout(
     State(state): State<AppState>,
+    client_ip: ClientIp,
     headers: HeaderMap,
-) -> Result<Json<LogoutResponse>, (StatusCode, Json<LogoutError>)> {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(LogoutError::missing_token())))?;
+) -> Result<Json<LogoutResponse>, ApiError> {
+    let claims = verified_claims(&state, &headers)?;
 
-    let token = TokenService::extract_bearer_token(auth_header)
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(LogoutError::missing_token())))?;
-
-    let claims = state
-        .token_service
-        .verify_access_token(token)
-        .map_err(|_| (StatusCode::UNAUTHORIZED, Json(LogoutError::invalid_token())))?;
-
-    state.validation.blacklist_jti(claims.jti);
+    state.validation.blacklist_jti(claims.jti, claims.expires_at());
     let _ = state.storage.revoke_all_user_tokens(claims.sub).await;
 
+    let ctx = AuditContext::from_request(&headers, client_ip, Some(claims.sub));
+    audit::record(&*state.audit, AuditEntry::new(actions::LOGOUT, "user", claims.sub).context(&ctx)).await;
+
     Ok(Json(LogoutResponse {
         success: true,
         message: "Successfully logged out".to_string(),
@@ -64,23 +53,16 @@ pub async fn logout(
 
 pub async fn logout_all(
     State(state): State<AppState>,
+    client_ip: ClientIp,
     headers: HeaderMap,
-) -> Result<Json<LogoutResponse>, (StatusCode, Json<LogoutError>)> {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(LogoutError::missing_token())))?;
-
-    let token = TokenService::extract_bearer_token(auth_header)
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(LogoutError::missing_token())))?;
-
-    let claims = state
-        .token_service
-        .verify_access_token(token)
-        .map_err(|_| (StatusCode::UNAUTHORIZED, Json(LogoutError::invalid_token())))?;
+) -> Result<Json<LogoutResponse>, ApiError> {
+    let claims = verified_claims(&state, &headers)?;
 
     let _ = state.storage.revoke_all_user_tokens(claims.sub).await;
-    state.validation.blacklist_jti(claims.jti);
+    state.validation.blacklist_jti(claims.jti, claims.expires_at());
+
+    let ctx = AuditContext::from_request(&headers, client_ip, Some(claims.sub));
+    audit::record(&*state.audit, AuditEntry::new(actions::LOGOUT_ALL, "user", claims.sub).context(&ctx)).await;
 
     Ok(Json(LogoutResponse {
         success: true,
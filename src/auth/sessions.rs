@@ -0,0 +1,163 @@
+//! A user's sessions: the access tokens issued to them, which this instance
+//! remembers until they expire, and their stored refresh tokens. Anything
+//! that takes a user's access away ends both.
+
+use uuid::Uuid;
+
+use crate::audit::{self, actions, AuditContext, AuditEntry, AuditSink};
+use crate::auth::model::{AccountStatus, UpdateAccountRequest, UserAccount};
+use crate::auth::tokens::TokenPair;
+use crate::storage::{DbError, StorageLayer};
+use crate::validation::ValidationStore;
+
+/// Remembers the access token of a pair just issued to `user_id`.
+pub fn track(validation: &ValidationStore, user_id: Uuid, pair: &TokenPair) {
+    validation.record_issued_jti(user_id, pair.access_jti, pair.access_expires_at);
+}
+
+/// Blacklists the user's outstanding access tokens and revokes their refresh
+/// tokens. Without a `ValidationStore`, as from the CLI, only the refresh
+/// tokens can be revoked and access tokens run out on their own.
+pub async fn end_all(
+    storage: &dyn StorageLayer,
+    validation: Option<&ValidationStore>,
+    user_id: Uuid,
+) -> Result<(), DbError> {
+    if let Some(validation) = validation {
+        validation.blacklist_user(user_id);
+    }
+    storage.revoke_all_user_tokens(user_id).await
+}
+
+/// Applies `changes` to the account `before` at `version` and records it,
+/// for the admin API and the CLI alike. An account left in any status but
+/// active has its sessions ended.
+pub async fn update_account(
+    storage: &dyn StorageLayer,
+    audit: &dyn AuditSink,
+    validation: Option<&ValidationStore>,
+    before: &UserAccount,
+    changes: &UpdateAccountRequest,
+    version: i64,
+    ctx: &AuditContext,
+) -> Result<UserAccount, DbError> {
+    let user_id = before.user_id;
+    let account = storage.update_account(user_id, changes, version).await?;
+
+    let action = if account.account_status != before.account_status {
+        actions::ACCOUNT_STATUS_CHANGED
+    } else {
+        actions::ACCOUNT_UPDATED
+    };
+    let entry = AuditEntry::new(action, "account", user_id).context(ctx).changes(before, &account);
+    audit::record(audit, entry).await;
+
+    if account.account_status != AccountStatus::Active {
+        end_all(storage, validation, user_id).await?;
+    }
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        Router,
+    };
+    use chrono::Utc;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use crate::app::create_app_with_config;
+    use crate::routing::etag::etag;
+    use crate::storage::{MemoryStorage, StorageLayer};
+    use crate::test_support::{self, PASSWORD};
+
+    /// An app with an active user, and a superadmin's bearer token.
+    async fn app_with_user() -> (Router, Arc<MemoryStorage>, uuid::Uuid, String) {
+        let storage = Arc::new(MemoryStorage::new());
+        let user = test_support::active_user(&*storage, "leaving").await;
+        let config = test_support::config();
+        let admin_token = test_support::superadmin_token(&*storage, &config).await;
+        let app = create_app_with_config(storage.clone(), config).await.main;
+        (app, storage, user.id, admin_token)
+    }
+
+    async fn login(app: &Router) -> String {
+        let body = json!({ "email": "leaving@example.com", "password": PASSWORD });
+        let request = Request::post("/api/v1/auth/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        body["access_token"].as_str().unwrap().to_string()
+    }
+
+    async fn history_status(app: &Router, token: &str) -> StatusCode {
+        let request = Request::get("/api/v1/auth/login-history")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_deleting_a_user_ends_their_sessions() {
+        let (app, storage, user_id, admin_token) = app_with_user().await;
+        let access_token = login(&app).await;
+        assert_eq!(history_status(&app, &access_token).await, StatusCode::OK);
+        assert_eq!(storage.count_active_tokens(Utc::now()).await.unwrap(), 1);
+
+        let request = Request::delete(format!("/admin/api/users/{}", user_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", admin_token))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        assert_eq!(history_status(&app, &access_token).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(storage.count_active_tokens(Utc::now()).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_deactivating_a_user_ends_their_sessions() {
+        let (app, storage, user_id, admin_token) = app_with_user().await;
+        let access_token = login(&app).await;
+        let user = storage.get_user_by_id(user_id).await.unwrap().unwrap();
+
+        let request = Request::put(format!("/admin/api/users/{}", user_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", admin_token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::IF_MATCH, etag(user.version))
+            .body(Body::from(json!({ "is_active": false }).to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        assert_eq!(history_status(&app, &access_token).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(storage.count_active_tokens(Utc::now()).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_suspending_an_account_ends_its_sessions() {
+        let (app, storage, user_id, admin_token) = app_with_user().await;
+        let access_token = login(&app).await;
+        let account = storage.get_account_by_user_id(user_id).await.unwrap().unwrap();
+
+        let body = json!({ "account_status": "Suspended", "status_reason": "chargeback" });
+        let request = Request::put(format!("/admin/api/users/{}/account", user_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", admin_token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::IF_MATCH, etag(account.version))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        assert_eq!(history_status(&app, &access_token).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(storage.count_active_tokens(Utc::now()).await.unwrap(), 0);
+    }
+}
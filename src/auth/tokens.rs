@@ -1,4 +1,6 @@
-use chrono::{Duration, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation, Algorithm};
 use uuid::Uuid;
 
@@ -33,12 +35,16 @@ pub struct TokenPair {
     pub refresh_token: String,
     pub access_expires_in: i64,
     pub refresh_expires_in: i64,
+    pub access_jti: Uuid,
+    pub access_expires_at: DateTime<Utc>,
 }
 
 pub struct TokenService {
     jwt_secret: String,
     access_token_ttl: Duration,
     refresh_token_ttl: Duration,
+    /// Tokens issued since start, indexed by `TokenType`.
+    issued: [AtomicU64; 4],
 }
 
 impl TokenService {
@@ -47,6 +53,7 @@ impl TokenService {
             jwt_secret,
             access_token_ttl: Duration::minutes(15),
             refresh_token_ttl: Duration::days(7),
+            issued: Default::default(),
         }
     }
 
@@ -55,6 +62,7 @@ impl TokenService {
             jwt_secret,
             access_token_ttl: Duration::minutes(access_ttl_minutes),
             refresh_token_ttl: Duration::days(refresh_ttl_days),
+            issued: Default::default(),
         }
     }
 
@@ -107,6 +115,7 @@ impl TokenService {
             &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
         )
         .map_err(|e| TokenError::EncodingFailed(e.to_string()))?;
+        self.count_issued(if is_admin { TokenType::AdminAccess } else { TokenType::Access });
 
         let refresh_jti = Uuid::new_v4();
         let refresh_claims = RefreshClaims {
@@ -123,15 +132,27 @@ impl TokenService {
             &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
         )
         .map_err(|e| TokenError::EncodingFailed(e.to_string()))?;
+        self.count_issued(if is_admin { TokenType::AdminRefresh } else { TokenType::Refresh });
 
         Ok(TokenPair {
             access_token,
             refresh_token,
             access_expires_in: self.access_token_ttl.num_seconds(),
             refresh_expires_in: self.refresh_token_ttl.num_seconds(),
+            access_jti,
+            access_expires_at: access_exp,
         })
     }
 
+    fn count_issued(&self, token_type: TokenType) {
+        self.issued[token_type as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Tokens of `token_type` issued since start.
+    pub fn issued_count(&self, token_type: TokenType) -> u64 {
+        self.issued[token_type as usize].load(Ordering::Relaxed)
+    }
+
     pub fn verify_access_token(&self, token: &str) -> Result<Claims, TokenError> {
         let key = DecodingKey::from_secret(self.jwt_secret.as_bytes());
         let validation = Validation::new(Algorithm::HS256);
@@ -0,0 +1,28 @@
+use std::env;
+use std::process;
+
+use learner::cli;
+use learner::config::Config;
+
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (config, args) = match Config::load(&args) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let result = match cli::parse(&args) {
+        Ok(command) => cli::run(command, &config).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(e.exit_code());
+    }
+}
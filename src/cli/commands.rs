@@ -0,0 +1,455 @@
+use std::fs;
+use std::io::{self, BufRead};
+use std::net::ToSocketAddrs;
+use std::path::Path;
+
+use chrono::Utc;
+use serde::Deserialize;
+
+use super::{Command, CliError, NewUser, PasswordSource, Store, USAGE};
+use crate::admin::model::{AdminRole, CreateAdminRequest, UpdateAdminRequest};
+use crate::audit::{self, actions, AuditContext, AuditEntry, AuditSink};
+use crate::auth::model::{AccountLevel, AccountStatus, CreateAccountRequest, UpdateAccountRequest, UserAccount};
+use crate::auth::sessions;
+use crate::config::Config;
+use crate::storage::{DbError, StorageLayer};
+use crate::users::model::{CreateUserRequest, UpdateUserRequest, User};
+
+/// Runs `command`, opening the store only for commands that need it.
+pub async fn run(command: Command, config: &Config) -> Result<(), CliError> {
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::ConfigCheck => check_config(config).await,
+        command => {
+            let store = Store::open(config).await?;
+            let result = execute(command, &store).await;
+            store.close()?;
+            result
+        }
+    }
+}
+
+async fn execute(command: Command, store: &Store) -> Result<(), CliError> {
+    let storage = store.storage();
+    let audit = store.audit();
+
+    match command {
+        Command::MigrateUp => {
+            let Some(before) = store.migration_status().await? else {
+                println!("{} has no schema to migrate", store.describe());
+                return Ok(());
+            };
+            store.run_migrations().await?;
+            let pending = before.iter().filter(|m| !m.applied).count();
+            println!("Applied {} migration{}", pending, if pending == 1 { "" } else { "s" });
+        }
+        Command::MigrateStatus => {
+            let Some(migrations) = store.migration_status().await? else {
+                println!("{} has no schema to migrate", store.describe());
+                return Ok(());
+            };
+            for m in &migrations {
+                let state = if m.applied { "applied" } else { "pending" };
+                println!("{:<8} {:04} {}", state, m.version, m.description);
+            }
+        }
+        Command::Seed { fixtures } => seed(storage, &*audit, &fixtures).await?,
+        Command::UserCreate(new_user) => {
+            let password = read_password(&new_user.password)?;
+            let user = create_user(storage, &*audit, &new_user, &password, None).await?;
+            println!("Created user {} ({})", user.email, user.id);
+        }
+        Command::UserSetPassword { email, password } => {
+            let user = find_user(storage, &email).await?;
+            let password_hash = hash_password(&read_password(&password)?)?;
+            let changes = UpdateUserRequest { password_hash: Some(password_hash), ..Default::default() };
+            let updated = storage.update_user(user.id, &changes, user.version).await?;
+            // Whoever held the old password may also hold a session.
+            storage.revoke_all_user_tokens(user.id).await?;
+
+            let entry = AuditEntry::new(actions::USER_PASSWORD_CHANGED, "user", user.id).changes(&user, &updated);
+            audit::record(&*audit, entry).await;
+            println!("Password changed for {}; their sessions have been ended", email);
+        }
+        Command::UserSetLevel { email, level } => {
+            let changes = UpdateAccountRequest {
+                capabilities: Some(level.default_capabilities()),
+                account_level: Some(level),
+                ..Default::default()
+            };
+            update_account(storage, &*audit, &email, &changes).await?;
+            println!("Account level of {} changed", email);
+        }
+        Command::UserSuspend { email, reason } => {
+            let changes = UpdateAccountRequest {
+                account_status: Some(AccountStatus::Suspended),
+                status_reason: reason,
+                ..Default::default()
+            };
+            update_account(storage, &*audit, &email, &changes).await?;
+            println!("Suspended {}", email);
+        }
+        Command::AdminGrant { email, role } => {
+            let user = find_user(storage, &email).await?;
+            grant_admin(storage, &*audit, &user, role).await?;
+            println!("{} is now an admin", email);
+        }
+        Command::AdminRevoke { email } => {
+            let user = find_user(storage, &email).await?;
+            let before = storage.get_admin_by_user_id(user.id).await?;
+            match storage.delete_admin(user.id).await {
+                Err(DbError::NotFound) => return Err(CliError::Failed(format!("{} is not an admin", email))),
+                result => result?,
+            }
+            storage.revoke_all_user_tokens(user.id).await?;
+
+            let mut entry = AuditEntry::new(actions::ADMIN_REVOKED, "admin", user.id);
+            entry.before = before.and_then(|admin| serde_json::to_value(admin).ok());
+            audit::record(&*audit, entry).await;
+            println!("{} is no longer an admin", email);
+        }
+        Command::TokensPurge => {
+            let now = Utc::now();
+            let tokens = storage.purge_expired_tokens(now).await?;
+            let keys = storage.purge_expired_validation_keys(now).await?;
+            println!("Purged {} expired tokens and {} expired validation keys", tokens, keys);
+        }
+        Command::Help | Command::ConfigCheck => unreachable!("handled by run"),
+    }
+    Ok(())
+}
+
+/// A fixtures file: `{"users": [{"email": ..., "username": ..., "password": ...}]}`,
+/// optionally with names, an `account_level` and an `admin_role` per user.
+#[derive(Debug, Deserialize)]
+struct Fixtures {
+    users: Vec<FixtureUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FixtureUser {
+    email: String,
+    username: String,
+    password: String,
+    #[serde(default)]
+    first_name: String,
+    #[serde(default)]
+    last_name: String,
+    account_level: Option<AccountLevel>,
+    admin_role: Option<AdminRole>,
+}
+
+/// Creates every fixture user whose email isn't taken yet, so seeding twice
+/// is harmless.
+async fn seed(storage: &dyn StorageLayer, audit: &dyn AuditSink, path: &Path) -> Result<(), CliError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| CliError::Failed(format!("Failed to read {}: {}", path.display(), e)))?;
+    let fixtures: Fixtures = serde_json::from_str(&text)
+        .map_err(|e| CliError::Failed(format!("Invalid fixtures file {}: {}", path.display(), e)))?;
+
+    let (mut created, mut skipped) = (0, 0);
+    for fixture in fixtures.users {
+        if storage.get_user_by_email(&fixture.email).await?.is_some() {
+            skipped += 1;
+            continue;
+        }
+        let new_user = NewUser {
+            email: fixture.email,
+            username: fixture.username,
+            first_name: fixture.first_name,
+            last_name: fixture.last_name,
+            level: fixture.account_level.unwrap_or(AccountLevel::Free),
+            password: PasswordSource::Stdin,
+        };
+        create_user(storage, audit, &new_user, &fixture.password, fixture.admin_role).await?;
+        created += 1;
+    }
+
+    println!("Created {} users, skipped {} that already exist", created, skipped);
+    Ok(())
+}
+
+/// Creates an active user with an account at `new_user.level`, and an admin
+/// record if `role` is given, all in one transaction.
+async fn create_user(
+    storage: &dyn StorageLayer,
+    audit: &dyn AuditSink,
+    new_user: &NewUser,
+    password: &str,
+    role: Option<AdminRole>,
+) -> Result<User, CliError> {
+    if !new_user.email.contains('@') || !new_user.email.contains('.') {
+        return Err(CliError::Failed(format!("Invalid email '{}'", new_user.email)));
+    }
+    let password_hash = hash_password(password)?;
+
+    let req = CreateUserRequest {
+        email: new_user.email.clone(),
+        password: String::new(),
+        username: new_user.username.clone(),
+        first_name: new_user.first_name.clone(),
+        last_name: new_user.last_name.clone(),
+    };
+    let level = new_user.level.clone();
+    let user = storage
+        .transaction(|tx| {
+            Box::pin(async move {
+                let user = tx.create_user(&req, &password_hash).await?;
+                tx.create_account(&CreateAccountRequest {
+                    user_id: user.id,
+                    capabilities: level.default_capabilities(),
+                    account_level: level,
+                    account_status: AccountStatus::Active,
+                })
+                .await?;
+                if let Some(role) = role {
+                    tx.create_admin(&admin_request(user.id, role)).await?;
+                }
+                Ok(user)
+            })
+        })
+        .await?;
+
+    audit::record(audit, AuditEntry::new(actions::USER_CREATED, "user", user.id)).await;
+    Ok(user)
+}
+
+fn admin_request(user_id: uuid::Uuid, role: AdminRole) -> CreateAdminRequest {
+    let permissions = match role {
+        AdminRole::SuperAdmin => vec!["*".to_string()],
+        _ => vec![],
+    };
+    CreateAdminRequest { user_id, role, permissions, created_by: None }
+}
+
+/// Makes `user` an admin with `role`, or changes the role of an existing one.
+async fn grant_admin(
+    storage: &dyn StorageLayer,
+    audit: &dyn AuditSink,
+    user: &User,
+    role: AdminRole,
+) -> Result<(), CliError> {
+    if let Some(admin) = storage.get_admin_by_user_id(user.id).await? {
+        let changes = UpdateAdminRequest { role: Some(role), ..Default::default() };
+        let updated = storage.update_admin(user.id, &changes, admin.version).await?;
+        let entry = AuditEntry::new(actions::ADMIN_UPDATED, "admin", user.id).changes(&admin, &updated);
+        audit::record(audit, entry).await;
+        return Ok(());
+    }
+
+    let req = admin_request(user.id, role);
+    let admin = storage
+        .transaction(|tx| Box::pin(async move { tx.create_admin(&req).await }))
+        .await?;
+    let mut entry = AuditEntry::new(actions::ADMIN_GRANTED, "admin", user.id);
+    entry.after = serde_json::to_value(admin).ok();
+    audit::record(audit, entry).await;
+    Ok(())
+}
+
+async fn update_account(
+    storage: &dyn StorageLayer,
+    audit: &dyn AuditSink,
+    email: &str,
+    changes: &UpdateAccountRequest,
+) -> Result<UserAccount, CliError> {
+    let user = find_user(storage, email).await?;
+    let account = storage
+        .get_account_by_user_id(user.id)
+        .await?
+        .ok_or_else(|| CliError::Failed(format!("{} has no account", email)))?;
+    let ctx = AuditContext::default();
+    Ok(sessions::update_account(storage, audit, None, &account, changes, account.version, &ctx).await?)
+}
+
+async fn find_user(storage: &dyn StorageLayer, email: &str) -> Result<User, CliError> {
+    storage
+        .get_user_by_email(email)
+        .await?
+        .ok_or_else(|| CliError::Failed(format!("No user with email {}", email)))
+}
+
+fn read_password(source: &PasswordSource) -> Result<String, CliError> {
+    let line = match source {
+        PasswordSource::File(path) => fs::read_to_string(path)
+            .map_err(|e| CliError::Failed(format!("Failed to read {}: {}", path.display(), e)))?
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        PasswordSource::Stdin => {
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(|e| CliError::Failed(format!("Failed to read password: {}", e)))?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    Ok(line)
+}
+
+fn hash_password(password: &str) -> Result<String, CliError> {
+    if password.len() < 8 {
+        return Err(CliError::Failed("Password must be at least 8 characters".to_string()));
+    }
+    bcrypt::hash(password, bcrypt::DEFAULT_COST)
+        .map_err(|e| CliError::Failed(format!("Failed to hash password: {}", e)))
+}
+
+/// Prints one line per check and fails if any check found an error.
+async fn check_config(config: &Config) -> Result<(), CliError> {
+    let mut errors = 0;
+    let mut report = |ok: Result<String, String>, warning: bool| match ok {
+        Ok(msg) => println!("ok       {}", msg),
+        Err(msg) if warning => println!("warning  {}", msg),
+        Err(msg) => {
+            errors += 1;
+            println!("error    {}", msg)
+        }
+    };
+
+    report(
+        if config.uses_default_secret() {
+            Err("JWT_SECRET is not set; tokens are signed with a built-in secret".to_string())
+        } else if config.token.jwt_secret.len() < 32 {
+            Err("JWT_SECRET is shorter than 32 characters".to_string())
+        } else {
+            Ok("JWT_SECRET is set".to_string())
+        },
+        true,
+    );
+
+    let addr = config.server_addr();
+    report(
+        match addr.to_socket_addrs() {
+            Ok(_) => Ok(format!("server address {}", addr)),
+            Err(e) => Err(format!("server address {} is invalid: {}", addr, e)),
+        },
+        false,
+    );
+
+    let store = match Store::open(config).await {
+        Ok(store) => store,
+        Err(e) => {
+            report(Err(format!("cannot open storage: {}", e)), false);
+            return Err(CliError::Failed(format!("{} configuration error(s)", errors)));
+        }
+    };
+    let warn_memory = matches!(store, Store::Memory(_, None));
+    report(
+        if warn_memory {
+            Err(format!("{}; set MEMORY_SNAPSHOT_PATH to keep data", store.describe()))
+        } else {
+            Ok(format!("storage: {}", store.describe()))
+        },
+        true,
+    );
+
+    if store.storage().health_check().await {
+        report(Ok("storage is reachable".to_string()), false);
+    } else {
+        report(Err("storage health check failed".to_string()), false);
+    }
+
+    match store.storage().has_superadmin().await {
+        Ok(true) => report(Ok("a superadmin exists".to_string()), false),
+        Ok(false) => report(
+            Err("no superadmin yet; the server will print a setup token, or set BOOTSTRAP_ADMIN_EMAIL".to_string()),
+            true,
+        ),
+        // Most likely the schema isn't there yet; the migration check says so.
+        Err(_) => {}
+    }
+
+    match store.migration_status().await {
+        Ok(Some(migrations)) => {
+            let pending = migrations.iter().filter(|m| !m.applied).count();
+            if pending == 0 {
+                report(Ok(format!("all {} migrations applied", migrations.len())), false);
+            } else {
+                report(Err(format!("{} pending migrations; run `learnerctl migrate up`", pending)), true);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => report(Err(format!("cannot read migration status: {}", e)), false),
+    }
+    store.close()?;
+
+    if errors > 0 {
+        return Err(CliError::Failed(format!("{} configuration error(s)", errors)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditFilter, MemoryAuditSink};
+    use crate::auth::TokenService;
+    use crate::storage::MemoryStorage;
+    use crate::users::model::PageRequest;
+
+    fn new_user(email: &str, level: AccountLevel) -> NewUser {
+        NewUser {
+            email: email.to_string(),
+            username: email.split('@').next().unwrap().to_string(),
+            first_name: String::new(),
+            last_name: String::new(),
+            level,
+            password: PasswordSource::Stdin,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_user_grant_and_revoke_admin() {
+        let storage = MemoryStorage::new();
+        let audit = MemoryAuditSink::new();
+
+        let user = create_user(&storage, &audit, &new_user("ann@example.com", AccountLevel::Premium), "password1", None)
+            .await
+            .unwrap();
+        let account = storage.get_account_by_user_id(user.id).await.unwrap().unwrap();
+        assert_eq!(account.account_level, AccountLevel::Premium);
+        assert_eq!(account.account_status, AccountStatus::Active);
+        assert!(bcrypt::verify("password1", &user.password_hash).unwrap());
+
+        grant_admin(&storage, &audit, &user, AdminRole::Moderator).await.unwrap();
+        grant_admin(&storage, &audit, &user, AdminRole::Admin).await.unwrap();
+        let admin = storage.get_admin_by_user_id(user.id).await.unwrap().unwrap();
+        assert_eq!(admin.role, AdminRole::Admin);
+
+        let events = audit.list(&AuditFilter::default(), PageRequest::new(None, None)).await.unwrap();
+        let logged: Vec<&str> = events.events.iter().rev().map(|e| e.action.as_str()).collect();
+        assert_eq!(logged, [actions::USER_CREATED, actions::ADMIN_GRANTED, actions::ADMIN_UPDATED]);
+    }
+
+    #[tokio::test]
+    async fn test_create_user_rejects_short_password() {
+        let storage = MemoryStorage::new();
+        let err = create_user(&storage, &MemoryAuditSink::new(), &new_user("bo@example.com", AccountLevel::Free), "short", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CliError::Failed(_)));
+        assert!(storage.get_user_by_email("bo@example.com").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_suspend_revokes_refresh_tokens() {
+        let storage = MemoryStorage::new();
+        let audit = MemoryAuditSink::new();
+        let user = create_user(&storage, &audit, &new_user("cy@example.com", AccountLevel::Free), "password1", None)
+            .await
+            .unwrap();
+        let tokens = TokenService::new("secret".to_string());
+        storage.store_token(&tokens.create_token_record(user.id, "refresh", false, true, None)).await.unwrap();
+
+        let suspend = UpdateAccountRequest { account_status: Some(AccountStatus::Suspended), ..Default::default() };
+        let account = update_account(&storage, &audit, "cy@example.com", &suspend).await.unwrap();
+        assert_eq!(account.account_status, AccountStatus::Suspended);
+        assert_eq!(storage.count_active_tokens(Utc::now()).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,276 @@
+//! `learnerctl`: maintenance commands run against the same store the server
+//! uses, chosen from the same configuration (`database.url`, else in-memory
+//! storage persisted at `storage.snapshot_path`).
+
+pub mod commands;
+pub mod store;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::admin::model::AdminRole;
+use crate::auth::model::AccountLevel;
+use crate::storage::DbError;
+
+pub use commands::run;
+pub use store::Store;
+
+pub const USAGE: &str = "\
+Usage: learnerctl [--config <file.toml>] [--<section>.<key> <value>]... <command>
+
+Commands:
+  migrate up                      Run pending migrations
+  migrate status                  List migrations and whether they have run
+  seed --fixtures <file.json>     Create the users listed in a fixtures file
+  user create --email <email> --username <name> [--first-name <name>]
+              [--last-name <name>] [--level free|premium|enterprise]
+              [--password-file <path>]
+  user set-password <email> [--password-file <path>]
+  user set-level <email> <free|premium|enterprise>
+  user suspend <email> [--reason <text>]
+  admin grant <email> [--role superadmin|admin|moderator]
+  admin revoke <email>
+  tokens purge                    Delete expired auth tokens and validation keys
+  config check                    Validate the configuration and database
+
+Passwords are read from --password-file, or from the first line of stdin.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    MigrateUp,
+    MigrateStatus,
+    Seed { fixtures: PathBuf },
+    UserCreate(NewUser),
+    UserSetPassword { email: String, password: PasswordSource },
+    UserSetLevel { email: String, level: AccountLevel },
+    UserSuspend { email: String, reason: Option<String> },
+    AdminGrant { email: String, role: AdminRole },
+    AdminRevoke { email: String },
+    TokensPurge,
+    ConfigCheck,
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewUser {
+    pub email: String,
+    pub username: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub level: AccountLevel,
+    pub password: PasswordSource,
+}
+
+/// Where a password comes from. Never the command line, where it would end up
+/// in shell history and process listings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordSource {
+    Stdin,
+    File(PathBuf),
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Storage(DbError),
+    Failed(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+            CliError::Storage(e) => write!(f, "{}", e),
+            CliError::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<DbError> for CliError {
+    fn from(err: DbError) -> Self {
+        CliError::Storage(err)
+    }
+}
+
+fn usage(msg: impl Into<String>) -> CliError {
+    CliError::Usage(msg.into())
+}
+
+/// Positional words and `--name value` / `--name=value` options.
+struct Args {
+    words: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn split(args: &[String]) -> Result<Self, CliError> {
+        let mut words = Vec::new();
+        let mut options = HashMap::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                words.push(arg.clone());
+                continue;
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => {
+                    let value = iter.next().ok_or_else(|| usage(format!("--{} needs a value", name)))?;
+                    (name.to_string(), value.clone())
+                }
+            };
+            if options.insert(name.clone(), value).is_some() {
+                return Err(usage(format!("--{} given more than once", name)));
+            }
+        }
+        Ok(Self { words, options })
+    }
+
+    fn take(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name)
+    }
+
+    fn require(&mut self, name: &str) -> Result<String, CliError> {
+        self.take(name).ok_or_else(|| usage(format!("missing --{}", name)))
+    }
+
+    fn password(&mut self) -> PasswordSource {
+        self.take("password-file").map_or(PasswordSource::Stdin, |p| PasswordSource::File(p.into()))
+    }
+
+    /// Fails on anything the command didn't consume.
+    fn finish(self, expected_words: usize) -> Result<(), CliError> {
+        if let Some(extra) = self.words.get(expected_words) {
+            return Err(usage(format!("unexpected argument '{}'", extra)));
+        }
+        if let Some(name) = self.options.keys().next() {
+            return Err(usage(format!("unknown option --{}", name)));
+        }
+        Ok(())
+    }
+}
+
+fn parse_level(value: &str) -> Result<AccountLevel, CliError> {
+    match value.to_lowercase().as_str() {
+        "free" => Ok(AccountLevel::Free),
+        "premium" => Ok(AccountLevel::Premium),
+        "enterprise" => Ok(AccountLevel::Enterprise),
+        _ => Err(usage(format!("unknown account level '{}'", value))),
+    }
+}
+
+fn parse_role(value: &str) -> Result<AdminRole, CliError> {
+    match value.to_lowercase().as_str() {
+        "superadmin" => Ok(AdminRole::SuperAdmin),
+        "admin" => Ok(AdminRole::Admin),
+        "moderator" => Ok(AdminRole::Moderator),
+        _ => Err(usage(format!("unknown admin role '{}'", value))),
+    }
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Command, CliError> {
+    let mut args = Args::split(args)?;
+    let words: Vec<&str> = args.words.iter().map(String::as_str).collect();
+    let email = words.get(2).map(|e| e.to_string());
+
+    let (command, consumed) = match words.as_slice() {
+        [] | ["help", ..] => (Command::Help, words.len()),
+        ["migrate", "up", ..] => (Command::MigrateUp, 2),
+        ["migrate", "status", ..] => (Command::MigrateStatus, 2),
+        ["seed", ..] => (Command::Seed { fixtures: args.require("fixtures")?.into() }, 1),
+        ["user", "create", ..] => {
+            let user = NewUser {
+                email: args.require("email")?,
+                username: args.require("username")?,
+                first_name: args.take("first-name").unwrap_or_default(),
+                last_name: args.take("last-name").unwrap_or_default(),
+                level: args.take("level").map_or(Ok(AccountLevel::Free), |l| parse_level(&l))?,
+                password: args.password(),
+            };
+            (Command::UserCreate(user), 2)
+        }
+        ["user", "set-password", _, ..] => {
+            (Command::UserSetPassword { email: email.unwrap(), password: args.password() }, 3)
+        }
+        ["user", "set-level", _, level, ..] => {
+            (Command::UserSetLevel { email: email.unwrap(), level: parse_level(level)? }, 4)
+        }
+        ["user", "suspend", _, ..] => {
+            (Command::UserSuspend { email: email.unwrap(), reason: args.take("reason") }, 3)
+        }
+        ["admin", "grant", _, ..] => {
+            let role = args.take("role").map_or(Ok(AdminRole::Admin), |r| parse_role(&r))?;
+            (Command::AdminGrant { email: email.unwrap(), role }, 3)
+        }
+        ["admin", "revoke", _, ..] => (Command::AdminRevoke { email: email.unwrap() }, 3),
+        ["tokens", "purge", ..] => (Command::TokensPurge, 2),
+        ["config", "check", ..] => (Command::ConfigCheck, 2),
+        _ => return Err(usage(format!("unknown command '{}'", words.join(" ")))),
+    };
+
+    args.finish(consumed)?;
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Command, CliError> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse(&args)
+    }
+
+    #[test]
+    fn test_parses_commands_and_options() {
+        assert_eq!(parse_str("migrate status").unwrap(), Command::MigrateStatus);
+        assert_eq!(
+            parse_str("seed --fixtures=dev.json").unwrap(),
+            Command::Seed { fixtures: "dev.json".into() }
+        );
+        assert_eq!(
+            parse_str("user set-level ann@example.com Premium").unwrap(),
+            Command::UserSetLevel { email: "ann@example.com".to_string(), level: AccountLevel::Premium }
+        );
+        assert_eq!(
+            parse_str("admin grant ann@example.com --role moderator").unwrap(),
+            Command::AdminGrant { email: "ann@example.com".to_string(), role: AdminRole::Moderator }
+        );
+
+        let Command::UserCreate(user) =
+            parse_str("user create --email ann@example.com --username ann --password-file pw.txt").unwrap()
+        else {
+            panic!("expected user create");
+        };
+        assert_eq!(user.level, AccountLevel::Free);
+        assert_eq!(user.password, PasswordSource::File("pw.txt".into()));
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments() {
+        for line in [
+            "migrate sideways",
+            "seed",
+            "user create --email ann@example.com",
+            "user set-level ann@example.com platinum",
+            "user suspend ann@example.com --force yes",
+            "tokens purge now",
+            "user set-password ann@example.com --password-file",
+        ] {
+            let err = parse_str(line).unwrap_err();
+            assert!(matches!(err, CliError::Usage(_)), "{}: {:?}", line, err);
+            assert_eq!(err.exit_code(), 2);
+        }
+    }
+}
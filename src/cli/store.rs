@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::audit::{AuditSink, MemoryAuditSink, PostgresAuditSink, SqliteAuditSink};
+use crate::config::Config;
+use crate::storage::{DbError, MemoryStorage, MigrationStatus, PostgresStorage, SqliteStorage, StorageLayer};
+
+/// The store the server would use with the same environment. Unlike the
+/// server, opening it runs no migrations and seeds nothing.
+pub enum Store {
+    Postgres(PostgresStorage),
+    Sqlite(SqliteStorage),
+    /// `None` when `MEMORY_SNAPSHOT_PATH` is unset, in which case nothing the
+    /// command does outlives it.
+    Memory(Box<MemoryStorage>, Option<String>),
+}
+
+impl Store {
+    pub async fn open(config: &Config) -> Result<Self, DbError> {
+        let Some(url) = &config.database.url else {
+            let memory = match &config.storage.snapshot_path {
+                Some(path) => Box::new(MemoryStorage::open(path)?),
+                None => Box::new(MemoryStorage::new()),
+            };
+            return Ok(Store::Memory(memory, config.storage.snapshot_path.clone()));
+        };
+
+        if url.starts_with("sqlite:") {
+            Ok(Store::Sqlite(SqliteStorage::connect(url, &config.pool_config()).await?))
+        } else {
+            Ok(Store::Postgres(PostgresStorage::connect(url, &config.pool_config()).await?))
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Store::Postgres(_) => "PostgreSQL".to_string(),
+            Store::Sqlite(_) => "SQLite".to_string(),
+            Store::Memory(_, Some(path)) => format!("in-memory storage persisted at {}", path),
+            Store::Memory(_, None) => "in-memory storage (not persisted)".to_string(),
+        }
+    }
+
+    pub fn storage(&self) -> &dyn StorageLayer {
+        match self {
+            Store::Postgres(pg) => pg,
+            Store::Sqlite(sqlite) => sqlite,
+            Store::Memory(memory, _) => &**memory,
+        }
+    }
+
+    pub fn audit(&self) -> Arc<dyn AuditSink> {
+        match self {
+            Store::Postgres(pg) => Arc::new(PostgresAuditSink::new(pg.pool().clone())),
+            Store::Sqlite(sqlite) => Arc::new(SqliteAuditSink::new(sqlite.pool().clone())),
+            Store::Memory(..) => Arc::new(MemoryAuditSink::new()),
+        }
+    }
+
+    /// `None` for in-memory storage, which has no schema.
+    pub async fn migration_status(&self) -> Result<Option<Vec<MigrationStatus>>, DbError> {
+        match self {
+            Store::Postgres(pg) => pg.migration_status().await.map(Some),
+            Store::Sqlite(sqlite) => sqlite.migration_status().await.map(Some),
+            Store::Memory(..) => Ok(None),
+        }
+    }
+
+    pub async fn run_migrations(&self) -> Result<(), DbError> {
+        match self {
+            Store::Postgres(pg) => pg.run_migrations().await,
+            Store::Sqlite(sqlite) => sqlite.run_migrations().await,
+            Store::Memory(..) => Ok(()),
+        }
+    }
+
+    /// Persists in-memory storage; database writes are already durable.
+    pub fn close(self) -> Result<(), DbError> {
+        match self {
+            Store::Memory(memory, _) => memory.snapshot(),
+            _ => Ok(()),
+        }
+    }
+}
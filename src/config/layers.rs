@@ -0,0 +1,288 @@
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+use super::{Config, ConfigError};
+
+/// Names a TOML file to load when `--config` isn't given.
+pub const CONFIG_FILE_ENV: &str = "LEARNER_CONFIG";
+
+/// Each environment variable and the key it sets. An empty variable counts
+/// as unset.
+pub const ENV_VARS: &[(&str, &str)] = &[
+    ("APP_ENV", "environment"),
+    ("SERVER_HOST", "server.host"),
+    ("SERVER_PORT", "server.port"),
+    ("SHUTDOWN_TIMEOUT_SECS", "server.shutdown_timeout_secs"),
+    ("PRE_STOP_DELAY_SECS", "server.pre_stop_delay_secs"),
+    ("TRUSTED_PROXIES", "server.trusted_proxies"),
+    ("LOG_FORMAT", "logging.format"),
+    ("LOG_FILTER", "logging.filter"),
+    ("DATABASE_URL", "database.url"),
+    ("DATABASE_MAX_CONNECTIONS", "database.max_connections"),
+    ("DATABASE_MIN_CONNECTIONS", "database.min_connections"),
+    ("DATABASE_ACQUIRE_TIMEOUT_SECS", "database.acquire_timeout_secs"),
+    ("JWT_SECRET", "token.jwt_secret"),
+    ("ACCESS_TOKEN_TTL_MINUTES", "token.access_ttl_minutes"),
+    ("REFRESH_TOKEN_TTL_DAYS", "token.refresh_ttl_days"),
+    ("MAIL_TRANSPORT", "mail.transport"),
+    ("MEMORY_SNAPSHOT_PATH", "storage.snapshot_path"),
+    ("MEMORY_SNAPSHOT_INTERVAL_SECS", "storage.snapshot_interval_secs"),
+    ("CACHE_TTL_SECS", "storage.cache_ttl_secs"),
+    ("CACHE_CAPACITY", "storage.cache_capacity"),
+    ("JOBS_ENABLED", "features.jobs"),
+    ("CACHE_ENABLED", "features.cache"),
+    ("TOKEN_PURGE_INTERVAL_SECS", "jobs.purge_interval_secs"),
+    ("USER_RETENTION_DAYS", "jobs.user_retention_days"),
+    ("METRICS_TOKEN", "metrics.token"),
+    ("METRICS_LISTEN", "metrics.listen"),
+    ("RATE_LIMIT_ENABLED", "rate_limit.enabled"),
+    ("RATE_LIMIT_STORE", "rate_limit.store"),
+    ("BOOTSTRAP_ADMIN_EMAIL", "bootstrap.admin_email"),
+    ("BOOTSTRAP_ADMIN_USERNAME", "bootstrap.admin_username"),
+    ("BOOTSTRAP_ADMIN_PASSWORD_FILE", "bootstrap.admin_password_file"),
+];
+
+/// What the command line contributes: `--config <file>` and any
+/// `--<section>.<key> <value>` overrides.
+#[derive(Debug, Default)]
+pub struct LoadOptions {
+    pub file: Option<String>,
+    pub overrides: Vec<(String, String)>,
+}
+
+impl LoadOptions {
+    /// Takes the configuration flags out of `args` and returns the rest
+    /// untouched, so a binary can parse its own arguments afterwards.
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<String>), ConfigError> {
+        let mut options = Self::default();
+        let mut rest = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                rest.push(arg.clone());
+                continue;
+            };
+            let (name, inline) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+            if name != "config" && !name.contains('.') {
+                rest.push(arg.clone());
+                continue;
+            }
+            let value = match inline {
+                Some(value) => value,
+                None => iter.next().cloned().ok_or_else(|| ConfigError::InvalidValue {
+                    key: name.to_string(),
+                    message: format!("--{} needs a value", name),
+                })?,
+            };
+            if name == "config" {
+                options.file = Some(value);
+            } else {
+                options.overrides.push((name.to_string(), value));
+            }
+        }
+        Ok((options, rest))
+    }
+}
+
+impl Config {
+    /// Loads from the process environment and `args`, returning the arguments
+    /// that weren't configuration flags.
+    pub fn load(args: &[String]) -> Result<(Self, Vec<String>), ConfigError> {
+        let (options, rest) = LoadOptions::from_args(args)?;
+        let config = Self::load_with(&options, |name| env::var(name).ok())?;
+        Ok((config, rest))
+    }
+
+    /// Defaults, then the TOML file, then `env`, then `options.overrides`;
+    /// each layer only replaces what it sets. Fails on any invalid value.
+    pub fn load_with(options: &LoadOptions, env: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let file = options.file.clone().or_else(|| env(CONFIG_FILE_ENV).filter(|f| !f.is_empty()));
+        let mut config = match file {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+
+        for (name, key) in ENV_VARS {
+            if let Some(value) = env(name).filter(|v| !v.is_empty()) {
+                config.set(key, &value).map_err(|e| match e {
+                    ConfigError::InvalidValue { message, .. } => {
+                        ConfigError::InvalidValue { key: name.to_string(), message }
+                    }
+                    e => e,
+                })?;
+            }
+        }
+        for (key, value) in &options.overrides {
+            config.set(key, value)?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Defaults overlaid with whatever the file sets. Unknown keys are errors
+    /// so a typo doesn't silently keep the default.
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let file_error = |message: String| ConfigError::File { path: path.to_string(), message };
+        let contents = fs::read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+        toml::from_str(&contents).map_err(|e| file_error(e.message().to_string()))
+    }
+
+    /// Sets one value by its dotted key, as used in the file and on the
+    /// command line. Empty values clear optional settings.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "environment" => self.environment = parse(key, value)?,
+            "server.host" => self.server.host = value.to_string(),
+            "server.port" => self.server.port = parse(key, value)?,
+            "server.shutdown_timeout_secs" => self.server.shutdown_timeout_secs = parse(key, value)?,
+            "server.pre_stop_delay_secs" => self.server.pre_stop_delay_secs = parse(key, value)?,
+            "server.trusted_proxies" => self.server.trusted_proxies = parse_list(key, value)?,
+            "logging.format" => self.logging.format = parse(key, value)?,
+            "logging.filter" => self.logging.filter = value.to_string(),
+            "database.url" => self.database.url = optional(value),
+            "database.max_connections" => self.database.max_connections = parse(key, value)?,
+            "database.min_connections" => self.database.min_connections = parse(key, value)?,
+            "database.acquire_timeout_secs" => self.database.acquire_timeout_secs = parse(key, value)?,
+            "token.jwt_secret" => self.token.jwt_secret = value.to_string(),
+            "token.access_ttl_minutes" => self.token.access_ttl_minutes = parse(key, value)?,
+            "token.refresh_ttl_days" => self.token.refresh_ttl_days = parse(key, value)?,
+            "mail.transport" => self.mail.transport = parse(key, value)?,
+            "storage.snapshot_path" => self.storage.snapshot_path = optional(value),
+            "storage.snapshot_interval_secs" => self.storage.snapshot_interval_secs = parse(key, value)?,
+            "storage.cache_ttl_secs" => self.storage.cache_ttl_secs = parse(key, value)?,
+            "storage.cache_capacity" => self.storage.cache_capacity = parse(key, value)?,
+            "features.jobs" => self.features.jobs = parse_bool(key, value)?,
+            "features.cache" => self.features.cache = parse_bool(key, value)?,
+            "jobs.purge_interval_secs" => self.jobs.purge_interval_secs = parse(key, value)?,
+            "jobs.user_retention_days" => self.jobs.user_retention_days = parse(key, value)?,
+            "metrics.token" => self.metrics.token = optional(value),
+            "metrics.listen" => self.metrics.listen = optional(value),
+            "rate_limit.enabled" => self.rate_limit.enabled = parse_bool(key, value)?,
+            "rate_limit.store" => self.rate_limit.store = parse(key, value)?,
+            "rate_limit.auth_anonymous" => self.rate_limit.auth_anonymous = parse(key, value)?,
+            "rate_limit.auth_free" => self.rate_limit.auth_free = parse(key, value)?,
+            "rate_limit.auth_premium" => self.rate_limit.auth_premium = parse(key, value)?,
+            "rate_limit.auth_enterprise" => self.rate_limit.auth_enterprise = parse(key, value)?,
+            "rate_limit.admin_anonymous" => self.rate_limit.admin_anonymous = parse(key, value)?,
+            "rate_limit.admin_free" => self.rate_limit.admin_free = parse(key, value)?,
+            "rate_limit.admin_premium" => self.rate_limit.admin_premium = parse(key, value)?,
+            "rate_limit.admin_enterprise" => self.rate_limit.admin_enterprise = parse(key, value)?,
+            "bootstrap.admin_email" => self.bootstrap.admin_email = optional(value),
+            "bootstrap.admin_username" => self.bootstrap.admin_username = optional(value),
+            "bootstrap.admin_password_file" => self.bootstrap.admin_password_file = optional(value),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: Display,
+{
+    value.trim().parse().map_err(|e: T::Err| ConfigError::InvalidValue {
+        key: key.to_string(),
+        message: format!("'{}': {}", value, e),
+    })
+}
+
+/// Comma-separated values; empty is an empty list.
+fn parse_list<T: FromStr>(key: &str, value: &str) -> Result<Vec<T>, ConfigError>
+where
+    T::Err: Display,
+{
+    value.split(',').filter(|item| !item.trim().is_empty()).map(|item| parse(key, item)).collect()
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, ConfigError> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err(ConfigError::InvalidValue {
+            key: key.to_string(),
+            message: format!("'{}': expected true or false", value),
+        }),
+    }
+}
+
+fn optional(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::config::{Environment, MailTransportKind};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_layers_apply_in_order() {
+        let path = env::temp_dir().join(format!("learner-config-{}.toml", uuid::Uuid::new_v4()));
+        fs::write(
+            &path,
+            "[server]\nport = 4000\nhost = \"0.0.0.0\"\n\n[token]\naccess_ttl_minutes = 5\n\n[mail]\ntransport = \"disabled\"\n",
+        )
+        .unwrap();
+
+        let env: HashMap<&str, &str> =
+            [("SERVER_PORT", "5000"), ("ACCESS_TOKEN_TTL_MINUTES", ""), ("JOBS_ENABLED", "0")].into();
+        let (options, rest) =
+            LoadOptions::from_args(&args(&format!("--config {} --server.port=6000 tokens purge", path.display())))
+                .unwrap();
+        let config = Config::load_with(&options, |name| env.get(name).map(|v| v.to_string()));
+        fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(rest, args("tokens purge"));
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 6000);
+        assert_eq!(config.token.access_ttl_minutes, 5);
+        assert_eq!(config.token.refresh_ttl_days, 7);
+        assert_eq!(config.mail.transport, MailTransportKind::Disabled);
+        assert!(!config.features.jobs);
+    }
+
+    #[test]
+    fn test_invalid_values_fail_loading() {
+        let load = |vars: &[(&str, &str)]| {
+            let env: HashMap<&str, &str> = vars.iter().copied().collect();
+            Config::load_with(&LoadOptions::default(), |name| env.get(name).map(|v| v.to_string()))
+        };
+
+        assert!(matches!(
+            load(&[("SERVER_PORT", "eighty")]),
+            Err(ConfigError::InvalidValue { key, .. }) if key == "SERVER_PORT"
+        ));
+        assert!(matches!(load(&[("APP_ENV", "production")]), Err(ConfigError::Invalid(_))));
+
+        let config = load(&[("APP_ENV", "production"), ("JWT_SECRET", &"s".repeat(32))]).unwrap();
+        assert_eq!(config.environment, Environment::Production);
+
+        let mut config = Config::default();
+        assert!(matches!(config.set("server.prot", "80"), Err(ConfigError::UnknownKey(_))));
+        assert!(matches!(
+            LoadOptions::from_args(&args("--config")),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_file_rejects_unknown_keys() {
+        let path = env::temp_dir().join(format!("learner-config-{}.toml", uuid::Uuid::new_v4()));
+        fs::write(&path, "[server]\nprot = 80\n").unwrap();
+        let result = Config::from_file(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ConfigError::File { .. })));
+    }
+}
@@ -1,46 +1,562 @@
-use std::env;
+//! Typed configuration, built from defaults, then an optional TOML file, then
+//! the environment, then command-line flags. See `layers` for how each source
+//! is read and `Config::validate` for what startup refuses.
 
-#[derive(Debug, Clone)]
+pub mod layers;
+
+use std::fmt;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::rate_limit::{GroupLimits, RateLimits};
+use crate::scheduler::SchedulerConfig;
+use crate::storage::{CacheConfig, PoolConfig};
+
+pub use layers::{ENV_VARS, LoadOptions};
+
+/// Signs tokens when nothing else is configured. Refused in production.
+pub const DEFAULT_JWT_SECRET: &str = "dev-secret-change-in-production";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Development,
+    Production,
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "development" | "dev" => Ok(Environment::Development),
+            "production" | "prod" => Ok(Environment::Production),
+            _ => Err("expected development or production".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransportKind {
+    /// Print outgoing mail to stdout.
+    Log,
+    /// Drop outgoing mail.
+    Disabled,
+}
+
+impl FromStr for MailTransportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "log" => Ok(MailTransportKind::Log),
+            "disabled" => Ok(MailTransportKind::Disabled),
+            _ => Err("expected log or disabled".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines, for terminals.
+    Pretty,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("expected pretty or json".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitStoreKind {
+    /// Each instance counts on its own.
+    Memory,
+    /// Instances share counters in the database, which must be Postgres.
+    Postgres,
+}
+
+impl FromStr for RateLimitStoreKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "memory" => Ok(RateLimitStoreKind::Memory),
+            "postgres" => Ok(RateLimitStoreKind::Postgres),
+            _ => Err("expected memory or postgres".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
-    pub database_url: String,
+    pub environment: Environment,
+    pub server: ServerConfig,
+    pub logging: LoggingConfig,
+    pub database: DatabaseConfig,
+    pub token: TokenConfig,
+    pub mail: MailConfig,
+    pub storage: StorageConfig,
+    pub features: FeatureConfig,
+    pub jobs: JobsConfig,
+    pub metrics: MetricsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub bootstrap: BootstrapConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// How long shutdown waits for in-flight requests, and then again for
+    /// background jobs, before giving up on them.
+    pub shutdown_timeout_secs: u64,
+    /// How long shutdown reports unready while still serving, so load
+    /// balancers stop sending requests before the listener closes.
+    pub pre_stop_delay_secs: u64,
+    /// Peers whose `X-Forwarded-For` and `X-Real-IP` headers are believed;
+    /// see `utils::client_ip`. Empty trusts no one.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// `tracing` filter directives, e.g. `info` or `learner=debug,sqlx=warn`.
+    pub filter: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `postgres://…` or `sqlite:…`; unset uses in-memory storage.
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
     pub jwt_secret: String,
-    pub server_host: String,
-    pub server_port: u16,
-    pub access_token_ttl_minutes: i64,
-    pub refresh_token_ttl_days: i64,
+    pub access_ttl_minutes: i64,
+    pub refresh_ttl_days: i64,
 }
 
-impl Config {
-    pub fn from_env() -> Self {
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+    pub transport: MailTransportKind,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Where in-memory storage is persisted; unset keeps it purely in memory.
+    pub snapshot_path: Option<String>,
+    pub snapshot_interval_secs: u64,
+    /// Lifetime of cached user/account/admin lookups; 0 disables the cache.
+    pub cache_ttl_secs: u64,
+    pub cache_capacity: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    /// Whether background maintenance jobs run in this process.
+    pub jobs: bool,
+    /// Whether database-backed stores get a lookup cache in front.
+    pub cache: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub purge_interval_secs: u64,
+    /// Days a soft-deleted user is kept before being purged.
+    pub user_retention_days: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Scrapes of `/metrics` must send `Authorization: Bearer <token>`.
+    pub token: Option<String>,
+    /// Serves `/metrics` on this `host:port` alone, instead of alongside
+    /// the API.
+    pub listen: Option<String>,
+}
+
+/// Requests per minute, per route group and account level. `anonymous` is
+/// for callers without a valid access token, who are limited by IP.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub store: RateLimitStoreKind,
+    pub auth_anonymous: u32,
+    pub auth_free: u32,
+    pub auth_premium: u32,
+    pub auth_enterprise: u32,
+    pub admin_anonymous: u32,
+    pub admin_free: u32,
+    pub admin_premium: u32,
+    pub admin_enterprise: u32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BootstrapConfig {
+    /// Creates this superadmin on first start instead of printing a setup
+    /// token. Requires `admin_password_file`.
+    pub admin_email: Option<String>,
+    /// That superadmin's username; `admin` when unset.
+    pub admin_username: Option<String>,
+    pub admin_password_file: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
         Self {
-            database_url: env::var("DATABASE_URL")
-                .unwrap_or_else(|_| "postgres://localhost/learner".to_string()),
-            jwt_secret: env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "dev-secret-change-in-production".to_string()),
-            server_host: env::var("SERVER_HOST")
-                .unwrap_or_else(|_| "127.0.0.1".to_string()),
-            server_port: env::var("SERVER_PORT")
-                .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or(3000),
-            access_token_ttl_minutes: env::var("ACCESS_TOKEN_TTL_MINUTES")
-                .ok()
-                .and_then(|t| t.parse().ok())
-                .unwrap_or(15),
-            refresh_token_ttl_days: env::var("REFRESH_TOKEN_TTL_DAYS")
-                .ok()
-                .and_then(|t| t.parse().ok())
-                .unwrap_or(7),
+            environment: Environment::Development,
+            server: ServerConfig::default(),
+            logging: LoggingConfig::default(),
+            database: DatabaseConfig::default(),
+            token: TokenConfig::default(),
+            mail: MailConfig::default(),
+            storage: StorageConfig::default(),
+            features: FeatureConfig::default(),
+            jobs: JobsConfig::default(),
+            metrics: MetricsConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            bootstrap: BootstrapConfig::default(),
         }
     }
+}
 
-    pub fn server_addr(&self) -> String {
-        format!("{}:{}", self.server_host, self.server_port)
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            shutdown_timeout_secs: 30,
+            pre_stop_delay_secs: 5,
+            trusted_proxies: Vec::new(),
+        }
     }
 }
 
-impl Default for Config {
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self { format: LogFormat::Pretty, filter: "info".to_string() }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        let pool = PoolConfig::default();
+        Self {
+            url: None,
+            max_connections: pool.max_connections,
+            min_connections: pool.min_connections,
+            acquire_timeout_secs: pool.acquire_timeout.as_secs(),
+        }
+    }
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: DEFAULT_JWT_SECRET.to_string(),
+            access_ttl_minutes: 15,
+            refresh_ttl_days: 7,
+        }
+    }
+}
+
+impl Default for MailConfig {
     fn default() -> Self {
-        Self::from_env()
+        Self { transport: MailTransportKind::Log }
     }
-}
\ No newline at end of file
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        let cache = CacheConfig::default();
+        Self {
+            snapshot_path: None,
+            snapshot_interval_secs: 60,
+            cache_ttl_secs: cache.ttl.as_secs(),
+            cache_capacity: cache.capacity,
+        }
+    }
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self { jobs: true, cache: true }
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        let scheduler = SchedulerConfig::default();
+        Self {
+            purge_interval_secs: scheduler.purge_interval.as_secs(),
+            user_retention_days: scheduler.user_retention.as_secs() / (24 * 60 * 60),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let RateLimits { auth, admin } = RateLimits::default();
+        Self {
+            enabled: true,
+            store: RateLimitStoreKind::Memory,
+            auth_anonymous: auth.anonymous,
+            auth_free: auth.free,
+            auth_premium: auth.premium,
+            auth_enterprise: auth.enterprise,
+            admin_anonymous: admin.anonymous,
+            admin_free: admin.free,
+            admin_premium: admin.premium,
+            admin_enterprise: admin.enterprise,
+        }
+    }
+}
+
+/// Every problem `Config::load` found, so one failed start reports them all.
+#[derive(Debug)]
+pub enum ConfigError {
+    File { path: String, message: String },
+    UnknownKey(String),
+    InvalidValue { key: String, message: String },
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File { path, message } => write!(f, "Failed to read config file {}: {}", path, message),
+            ConfigError::UnknownKey(key) => write!(f, "Unknown configuration key '{}'", key),
+            ConfigError::InvalidValue { key, message } => write!(f, "Invalid value for {}: {}", key, message),
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn is_production(&self) -> bool {
+        self.environment == Environment::Production
+    }
+
+    pub fn uses_default_secret(&self) -> bool {
+        self.token.jwt_secret == DEFAULT_JWT_SECRET
+    }
+
+    /// Checks values no single setting can check on its own. Production also
+    /// refuses the built-in JWT secret and short secrets.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        check(!self.server.host.is_empty(), "server.host must not be empty");
+        check(self.server.shutdown_timeout_secs > 0, "server.shutdown_timeout_secs must be positive");
+        check(
+            tracing_subscriber::EnvFilter::try_new(&self.logging.filter).is_ok(),
+            "logging.filter is not a valid filter",
+        );
+
+        if let Some(url) = &self.database.url {
+            check(
+                ["postgres://", "postgresql://", "sqlite:"].iter().any(|scheme| url.starts_with(scheme)),
+                "database.url must start with postgres://, postgresql:// or sqlite:",
+            );
+        }
+        check(self.database.max_connections > 0, "database.max_connections must be at least 1");
+        check(
+            self.database.min_connections <= self.database.max_connections,
+            "database.min_connections must not exceed database.max_connections",
+        );
+        check(self.database.acquire_timeout_secs > 0, "database.acquire_timeout_secs must be positive");
+
+        check(!self.token.jwt_secret.is_empty(), "token.jwt_secret must not be empty");
+        check(self.token.access_ttl_minutes > 0, "token.access_ttl_minutes must be positive");
+        check(self.token.refresh_ttl_days > 0, "token.refresh_ttl_days must be positive");
+        check(
+            self.token.access_ttl_minutes < self.token.refresh_ttl_days.saturating_mul(24 * 60),
+            "token.access_ttl_minutes must be shorter than token.refresh_ttl_days",
+        );
+
+        check(self.storage.snapshot_interval_secs > 0, "storage.snapshot_interval_secs must be positive");
+        check(self.storage.cache_capacity > 0, "storage.cache_capacity must be at least 1");
+        check(self.jobs.purge_interval_secs > 0, "jobs.purge_interval_secs must be positive");
+
+        if let Some(listen) = &self.metrics.listen {
+            check(listen.to_socket_addrs().is_ok(), "metrics.listen must be a host:port address");
+            check(*listen != self.server_addr(), "metrics.listen must differ from the server address");
+        }
+
+        let r = &self.rate_limit;
+        for (key, limit) in [
+            ("auth_anonymous", r.auth_anonymous),
+            ("auth_free", r.auth_free),
+            ("auth_premium", r.auth_premium),
+            ("auth_enterprise", r.auth_enterprise),
+            ("admin_anonymous", r.admin_anonymous),
+            ("admin_free", r.admin_free),
+            ("admin_premium", r.admin_premium),
+            ("admin_enterprise", r.admin_enterprise),
+        ] {
+            check(limit > 0, &format!("rate_limit.{} must be at least 1 request per minute", key));
+        }
+        if self.rate_limit.enabled && self.rate_limit.store == RateLimitStoreKind::Postgres {
+            check(
+                self.database.url.as_deref().is_some_and(|url| url.starts_with("postgres")),
+                "rate_limit.store = postgres requires a postgres:// database.url",
+            );
+        }
+
+        check(
+            self.bootstrap.admin_email.is_none() || self.bootstrap.admin_password_file.is_some(),
+            "bootstrap.admin_email requires bootstrap.admin_password_file",
+        );
+
+        if self.is_production() {
+            check(!self.uses_default_secret(), "token.jwt_secret must be set in production");
+            check(self.token.jwt_secret.len() >= 32, "token.jwt_secret must be at least 32 characters in production");
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// `None` when caching is disabled.
+    pub fn cache_config(&self) -> Option<CacheConfig> {
+        (self.features.cache && self.storage.cache_ttl_secs > 0).then(|| CacheConfig {
+            ttl: Duration::from_secs(self.storage.cache_ttl_secs),
+            capacity: self.storage.cache_capacity,
+        })
+    }
+
+    pub fn pool_config(&self) -> PoolConfig {
+        PoolConfig {
+            max_connections: self.database.max_connections,
+            min_connections: self.database.min_connections,
+            acquire_timeout: Duration::from_secs(self.database.acquire_timeout_secs),
+        }
+    }
+
+    pub fn scheduler_config(&self) -> SchedulerConfig {
+        SchedulerConfig {
+            enabled: self.features.jobs,
+            purge_interval: Duration::from_secs(self.jobs.purge_interval_secs),
+            user_retention: Duration::from_secs(self.jobs.user_retention_days * 24 * 60 * 60),
+            ..SchedulerConfig::default()
+        }
+    }
+
+    /// `None` when rate limiting is disabled.
+    pub fn rate_limits(&self) -> Option<RateLimits> {
+        self.rate_limit.enabled.then(|| self.rate_limits_unchecked())
+    }
+
+    fn rate_limits_unchecked(&self) -> RateLimits {
+        let r = &self.rate_limit;
+        RateLimits {
+            auth: GroupLimits {
+                anonymous: r.auth_anonymous,
+                free: r.auth_free,
+                premium: r.auth_premium,
+                enterprise: r.auth_enterprise,
+            },
+            admin: GroupLimits {
+                anonymous: r.admin_anonymous,
+                free: r.admin_free,
+                premium: r.admin_premium,
+                enterprise: r.admin_enterprise,
+            },
+        }
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.shutdown_timeout_secs)
+    }
+
+    pub fn pre_stop_delay(&self) -> Duration {
+        Duration::from_secs(self.server.pre_stop_delay_secs)
+    }
+
+    pub fn server_addr(&self) -> String {
+        format!("{}:{}", self.server.host, self.server.port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_valid_outside_production() {
+        let config = Config::default();
+        assert!(config.validate().is_ok());
+        assert!(config.uses_default_secret());
+
+        let production = Config { environment: Environment::Production, ..Config::default() };
+        let Err(ConfigError::Invalid(problems)) = production.validate() else {
+            panic!("production must refuse the default secret");
+        };
+        assert!(problems.iter().any(|p| p.contains("must be set in production")));
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut config = Config::default();
+        config.database.url = Some("mysql://localhost/learner".to_string());
+        config.database.min_connections = config.database.max_connections + 1;
+        config.token.access_ttl_minutes = 0;
+        config.bootstrap.admin_email = Some("root@example.com".to_string());
+        config.rate_limit.store = RateLimitStoreKind::Postgres;
+        config.rate_limit.auth_free = 0;
+
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("expected validation to fail");
+        };
+        assert_eq!(problems.len(), 6, "{:?}", problems);
+        assert!(problems.iter().any(|p| p == "rate_limit.auth_free must be at least 1 request per minute"));
+    }
+}
@@ -0,0 +1,27 @@
+use crate::auth::model::LoginEvent;
+use crate::users::model::User;
+
+use super::EmailMessage;
+
+pub fn new_sign_in(user: &User, event: &LoginEvent) -> EmailMessage {
+    let ip = event.ip_address.as_deref().unwrap_or("unknown");
+    let agent = event.user_agent.as_deref().unwrap_or("unknown");
+
+    EmailMessage {
+        to: user.email.clone(),
+        subject: "New sign-in to your account".to_string(),
+        body: format!(
+            "Hi {},\n\n\
+             We noticed a sign-in to your account from a device we haven't seen before.\n\n\
+             Time:       {}\n\
+             IP address: {}\n\
+             Device:     {}\n\n\
+             If this was you, no action is needed. If not, log out of all devices and \
+             change your password immediately.\n",
+            user.first_name,
+            event.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            ip,
+            agent,
+        ),
+    }
+}
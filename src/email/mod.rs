@@ -1 +1,4 @@
-pub mod sender;
\ No newline at end of file
+pub mod sender;
+pub mod messages;
+
+pub use sender::{DisabledTransport, EmailMessage, LogTransport, MailTransport};
@@ -1 +1,70 @@
-// Email sending logic will go here
\ No newline at end of file
+use async_trait::async_trait;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    InvalidRecipient(String),
+}
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MailError::InvalidRecipient(to) => write!(f, "Invalid recipient: {}", to),
+        }
+    }
+}
+
+impl std::error::Error for MailError {}
+
+#[async_trait]
+pub trait MailTransport: Send + Sync {
+    /// Shown in health reports.
+    fn name(&self) -> &'static str;
+
+    /// Whether mail can be handed off right now. Transports that need no
+    /// connection are always ready.
+    async fn health_check(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailError>;
+}
+
+/// Writes outgoing mail to stdout. Used when no real transport is configured.
+pub struct LogTransport;
+
+#[async_trait]
+impl MailTransport for LogTransport {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailError> {
+        if !message.to.contains('@') {
+            return Err(MailError::InvalidRecipient(message.to.clone()));
+        }
+        tracing::info!(to = %message.to, subject = %message.subject, "mail sent to log");
+        Ok(())
+    }
+}
+
+/// Accepts and drops all mail.
+pub struct DisabledTransport;
+
+#[async_trait]
+impl MailTransport for DisabledTransport {
+    fn name(&self) -> &'static str {
+        "disabled"
+    }
+
+    async fn send(&self, _message: &EmailMessage) -> Result<(), MailError> {
+        Ok(())
+    }
+}
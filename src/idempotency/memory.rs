@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{Claim, IdempotencyStore, ScopedKey, StoredResponse, LOCK_TIMEOUT, RETENTION};
+use crate::storage::DbError;
+
+struct Record {
+    fingerprint: String,
+    response: Option<StoredResponse>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Keeps keys in process memory; they're lost on restart and not shared
+/// between instances. Used alongside `MemoryStorage` and SQLite.
+#[derive(Default)]
+pub struct MemoryIdempotencyStore {
+    records: Mutex<HashMap<ScopedKey, Record>>,
+}
+
+impl MemoryIdempotencyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn begin(&self, key: &ScopedKey, fingerprint: &str, now: DateTime<Utc>) -> Result<Claim, DbError> {
+        let mut records = self.records.lock().unwrap();
+        if let Some(record) = records.get(key).filter(|r| r.expires_at > now) {
+            return Ok(Claim::existing(&record.fingerprint, record.response.clone(), fingerprint));
+        }
+        let record = Record { fingerprint: fingerprint.to_string(), response: None, expires_at: now + LOCK_TIMEOUT };
+        records.insert(key.clone(), record);
+        Ok(Claim::New)
+    }
+
+    async fn complete(&self, key: &ScopedKey, response: &StoredResponse, now: DateTime<Utc>) -> Result<(), DbError> {
+        if let Some(record) = self.records.lock().unwrap().get_mut(key) {
+            record.response = Some(response.clone());
+            record.expires_at = now + RETENTION;
+        }
+        Ok(())
+    }
+
+    async fn release(&self, key: &ScopedKey) -> Result<(), DbError> {
+        self.records.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn prune(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let mut records = self.records.lock().unwrap();
+        let before = records.len();
+        records.retain(|_, record| record.expires_at > now);
+        Ok((before - records.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_claim_replay_and_mismatch() {
+        let store = MemoryIdempotencyStore::new();
+        let key = ScopedKey { scope: "anonymous".to_string(), key: "k1".to_string() };
+        let now = Utc::now();
+
+        assert_eq!(store.begin(&key, "a", now).await.unwrap(), Claim::New);
+        assert_eq!(store.begin(&key, "a", now).await.unwrap(), Claim::InProgress);
+
+        let response = StoredResponse { status: 201, headers: vec![], body: b"{}".to_vec() };
+        store.complete(&key, &response, now).await.unwrap();
+        assert_eq!(store.begin(&key, "a", now).await.unwrap(), Claim::Replay(response));
+        assert_eq!(store.begin(&key, "b", now).await.unwrap(), Claim::Mismatch);
+
+        // Past retention the key is free again.
+        let later = now + RETENTION;
+        assert_eq!(store.prune(later).await.unwrap(), 1);
+        assert_eq!(store.begin(&key, "b", later).await.unwrap(), Claim::New);
+    }
+}
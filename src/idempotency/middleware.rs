@@ -0,0 +1,234 @@
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::Utc;
+
+use super::{fingerprint, Claim, ScopedKey, StoredResponse, IDEMPOTENCY_KEY_HEADER};
+use crate::app::AppState;
+use crate::auth::middleware as auth_middleware;
+use crate::routing::client_ip::ClientIp;
+use crate::routing::error::ApiError;
+
+/// Largest request or response body buffered; the same as axum's default
+/// body limit, so nothing a handler would accept is refused here.
+const MAX_BODY: usize = 2 * 1024 * 1024;
+
+/// Set on replayed responses.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Headers that describe the body's framing rather than the response, and
+/// are recomputed on replay.
+const NOT_STORED: &[&str] = &["content-length", "transfer-encoding"];
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// The scope `key` is kept in: the caller's user id, or else their address.
+/// `None` for an anonymous caller whose address isn't known, whose key
+/// can't be told apart from anyone else's.
+fn scope(state: &AppState, request: &Request) -> Option<String> {
+    if let Some(claims) = auth_middleware::caller(state, request) {
+        return Some(format!("user:{}", claims.sub));
+    }
+    let ClientIp(ip) = ClientIp::resolve(state, request.headers(), request.extensions());
+    ip.map(|ip| format!("ip:{}", ip))
+}
+
+/// Runs the request once per `Idempotency-Key`, storing the response and
+/// replaying it to retries. Requests without a key or a `scope`, and other
+/// methods, pass straight through. Server errors aren't stored, so those
+/// retries run again.
+async fn handle(state: AppState, request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::POST | Method::PUT | Method::PATCH) {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let Some(key) = key.to_str().ok().filter(|k| !k.is_empty() && k.len() <= 255) else {
+        return ApiError::bad_request("INVALID_IDEMPOTENCY_KEY", "Idempotency-Key must be 1 to 255 visible characters")
+            .into_response();
+    };
+    let key = key.to_string();
+    let Some(scope) = scope(&state, &request) else {
+        return next.run(request).await;
+    };
+    let key = ScopedKey { scope, key };
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = body::to_bytes(body, MAX_BODY).await else {
+        return ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "BODY_TOO_LARGE", "Request body is too large")
+            .into_response();
+    };
+    let fingerprint = fingerprint(parts.method.as_str(), parts.uri.path(), &body);
+
+    let store = &state.idempotency;
+    match store.begin(&key, &fingerprint, Utc::now()).await {
+        Ok(Claim::New) => {}
+        Ok(Claim::Replay(stored)) => return replay(stored),
+        Ok(Claim::InProgress) => {
+            return ApiError::new(
+                StatusCode::CONFLICT,
+                "IDEMPOTENCY_KEY_IN_USE",
+                "A request with this Idempotency-Key is still being processed",
+            )
+            .into_response();
+        }
+        Ok(Claim::Mismatch) => {
+            return ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "IDEMPOTENCY_KEY_REUSED",
+                "This Idempotency-Key was used for a different request",
+            )
+            .into_response();
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to claim idempotency key");
+            return ApiError::internal().into_response();
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_server_error() {
+        if let Err(e) = store.release(&key).await {
+            tracing::error!(error = %e, "failed to release idempotency key");
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match body::to_bytes(body, MAX_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to buffer response for idempotency key");
+            if let Err(e) = store.release(&key).await {
+                tracing::error!(error = %e, "failed to release idempotency key");
+            }
+            return ApiError::internal().into_response();
+        }
+    };
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter(|(name, _)| !NOT_STORED.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: body.to_vec(),
+    };
+    if let Err(e) = store.complete(&key, &stored, Utc::now()).await {
+        tracing::error!(error = %e, "failed to store idempotent response");
+        let _ = store.release(&key).await;
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Honors `Idempotency-Key` on every mutating route in `router`.
+pub fn idempotent(router: Router<AppState>, state: &AppState) -> Router<AppState> {
+    router.layer(middleware::from_fn_with_state(
+        state.clone(),
+        |State(state): State<AppState>, request: Request, next: Next| handle(state, request, next),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use axum::extract::ConnectInfo;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::app::create_app_with_config;
+    use crate::storage::MemoryStorage;
+    use crate::test_support;
+
+    async fn send(app: &Router, uri: &str, key: &str, peer: [u8; 4], body: Value) -> Response {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header(IDEMPOTENCY_KEY_HEADER, key)
+            .extension(ConnectInfo(SocketAddr::from((peer, 40000))))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    fn registration(email: &str) -> Value {
+        json!({
+            "email": email,
+            "password": "Correct-horse-9",
+            "username": "retrier",
+            "first_name": "Re",
+            "last_name": "Trier",
+        })
+    }
+
+    async fn register(app: &Router, key: &str, email: &str) -> Response {
+        send(app, "/api/v1/auth/register", key, [203, 0, 113, 9], registration(email)).await
+    }
+
+    async fn app() -> Router {
+        create_app_with_config(Arc::new(MemoryStorage::new()), test_support::config()).await.main
+    }
+
+    #[tokio::test]
+    async fn test_retried_registration_is_replayed() {
+        let app = app().await;
+
+        let first = register(&app, "retry-1", "retry@example.com").await;
+        assert!(first.status().is_success(), "{}", first.status());
+        let first_body = body::to_bytes(first.into_body(), MAX_BODY).await.unwrap();
+
+        let retry = register(&app, "retry-1", "retry@example.com").await;
+        assert_eq!(retry.headers()[REPLAYED_HEADER], "true");
+        assert_eq!(body::to_bytes(retry.into_body(), MAX_BODY).await.unwrap(), first_body);
+
+        let reused = register(&app, "retry-1", "other@example.com").await;
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_keys_are_scoped_by_address() {
+        let app = app().await;
+        let first = register(&app, "shared", "shared@example.com").await;
+        assert!(first.status().is_success(), "{}", first.status());
+
+        // Another client with the same key and body runs the request itself.
+        let body = registration("shared@example.com");
+        let other = send(&app, "/api/v1/auth/register", "shared", [198, 51, 100, 7], body).await;
+        assert!(other.headers().get(REPLAYED_HEADER).is_none());
+        assert_eq!(other.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_responses_are_never_stored() {
+        let app = app().await;
+        let body = json!({ "email": "nobody@example.com", "password": "Correct-horse-9" });
+
+        for _ in 0..2 {
+            let response = send(&app, "/api/v1/auth/login", "sign-in", [203, 0, 113, 9], body.clone()).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(response.headers().get(REPLAYED_HEADER).is_none());
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! `Idempotency-Key` support for POST, PUT and PATCH. The first response to
+//! a key is stored and replayed to retries for `RETENTION`, so a client that
+//! lost a response can safely send the request again.
+//!
+//! Keys are scoped to the caller: the user id for requests with a valid
+//! access token, otherwise the client address (see `routing::client_ip`).
+//! A stored response is only replayed to a request with the same method,
+//! path and body. Sign-in routes aren't covered, since their responses hold
+//! tokens that mustn't be kept.
+
+pub mod memory;
+pub mod middleware;
+pub mod postgres;
+
+pub use memory::MemoryIdempotencyStore;
+pub use middleware::idempotent;
+pub use postgres::PostgresIdempotencyStore;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::storage::DbError;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// How long a completed response is replayed.
+pub const RETENTION: Duration = Duration::hours(24);
+
+/// How long a key stays claimed by a request that hasn't finished, after
+/// which the request is assumed lost and the key can be claimed again.
+pub const LOCK_TIMEOUT: Duration = Duration::minutes(5);
+
+/// A key as sent by one caller.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScopedKey {
+    pub scope: String,
+    pub key: String,
+}
+
+/// Identifies a request, so a key reused for a different one is caught.
+pub fn fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+/// A response as first sent, to be replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// What `begin` found for a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Claim {
+    /// The key is now held by this request.
+    New,
+    /// Another request holds the key and hasn't finished.
+    InProgress,
+    /// The key was used for a different request.
+    Mismatch,
+    /// The key's request finished with this response.
+    Replay(StoredResponse),
+}
+
+impl Claim {
+    /// The claim on a key already held for the request `held`, which has
+    /// finished with `response` if it is set.
+    fn existing(held: &str, response: Option<StoredResponse>, fingerprint: &str) -> Self {
+        match response {
+            _ if held != fingerprint => Claim::Mismatch,
+            Some(response) => Claim::Replay(response),
+            None => Claim::InProgress,
+        }
+    }
+}
+
+/// Where keys and their responses are kept.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claims `key` for the request `fingerprint` until `now + LOCK_TIMEOUT`,
+    /// unless it is held and hasn't expired.
+    async fn begin(&self, key: &ScopedKey, fingerprint: &str, now: DateTime<Utc>) -> Result<Claim, DbError>;
+
+    /// Stores the response to the request holding `key`, to be replayed
+    /// until `now + RETENTION`.
+    async fn complete(&self, key: &ScopedKey, response: &StoredResponse, now: DateTime<Utc>) -> Result<(), DbError>;
+
+    /// Gives `key` up without a response, so a retry runs the request again.
+    async fn release(&self, key: &ScopedKey) -> Result<(), DbError>;
+
+    /// Deletes keys that expired before `now`, returning how many.
+    async fn prune(&self, now: DateTime<Utc>) -> Result<u64, DbError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_covers_method_path_and_body() {
+        let base = fingerprint("POST", "/api/v1/auth/register", b"{}");
+        assert_eq!(base, fingerprint("POST", "/api/v1/auth/register", b"{}"));
+        assert_ne!(base, fingerprint("PUT", "/api/v1/auth/register", b"{}"));
+        assert_ne!(base, fingerprint("POST", "/api/v1/auth/login", b"{}"));
+        assert_ne!(base, fingerprint("POST", "/api/v1/auth/register", b"{ }"));
+    }
+}
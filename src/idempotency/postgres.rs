@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::{Claim, IdempotencyStore, ScopedKey, StoredResponse, LOCK_TIMEOUT, RETENTION};
+use crate::storage::DbError;
+
+/// Keeps keys in the `idempotency_keys` table, so a retry is recognised by
+/// whichever instance receives it.
+pub struct PostgresIdempotencyStore {
+    pool: PgPool,
+}
+
+impl PostgresIdempotencyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn encode_headers(headers: &[(String, String)]) -> Result<String, DbError> {
+    serde_json::to_string(headers).map_err(|e| DbError::Query(format!("Failed to encode response headers: {}", e)))
+}
+
+fn decode_headers(text: &str) -> Result<Vec<(String, String)>, DbError> {
+    serde_json::from_str(text).map_err(|e| DbError::Query(format!("Failed to decode response headers: {}", e)))
+}
+
+type Row = (String, Option<i16>, Option<String>, Option<Vec<u8>>);
+
+#[async_trait]
+impl IdempotencyStore for PostgresIdempotencyStore {
+    async fn begin(&self, key: &ScopedKey, fingerprint: &str, now: DateTime<Utc>) -> Result<Claim, DbError> {
+        // Inserts, or takes over an expired key; either way the key is ours.
+        let claimed = sqlx::query(
+            "INSERT INTO idempotency_keys (scope, key, fingerprint, expires_at) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (scope, key) DO UPDATE
+                 SET fingerprint = EXCLUDED.fingerprint, status = NULL, headers = NULL, body = NULL,
+                     expires_at = EXCLUDED.expires_at
+                 WHERE idempotency_keys.expires_at <= $5",
+        )
+        .bind(&key.scope)
+        .bind(&key.key)
+        .bind(fingerprint)
+        .bind(now + LOCK_TIMEOUT)
+        .bind(now)
+        .execute(&self.pool)
+        .await?
+        .rows_affected()
+            > 0;
+        if claimed {
+            return Ok(Claim::New);
+        }
+
+        let row: Option<Row> = sqlx::query_as(
+            "SELECT fingerprint, status, headers, body FROM idempotency_keys WHERE scope = $1 AND key = $2",
+        )
+        .bind(&key.scope)
+        .bind(&key.key)
+        .fetch_optional(&self.pool)
+        .await?;
+        // Pruned between the two statements; the retry will claim it.
+        let Some((held, status, headers, body)) = row else {
+            return Ok(Claim::InProgress);
+        };
+        let response = match (status, headers) {
+            (Some(status), Some(headers)) => Some(StoredResponse {
+                status: status as u16,
+                headers: decode_headers(&headers)?,
+                body: body.unwrap_or_default(),
+            }),
+            _ => None,
+        };
+        Ok(Claim::existing(&held, response, fingerprint))
+    }
+
+    async fn complete(&self, key: &ScopedKey, response: &StoredResponse, now: DateTime<Utc>) -> Result<(), DbError> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET status = $3, headers = $4, body = $5, expires_at = $6
+             WHERE scope = $1 AND key = $2",
+        )
+        .bind(&key.scope)
+        .bind(&key.key)
+        .bind(response.status as i16)
+        .bind(encode_headers(&response.headers)?)
+        .bind(&response.body)
+        .bind(now + RETENTION)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn release(&self, key: &ScopedKey) -> Result<(), DbError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2")
+            .bind(&key.scope)
+            .bind(&key.key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn prune(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,26 @@
+pub mod app;
+pub mod audit;
+pub mod cli;
+pub mod config;
+pub mod lifecycle;
+pub mod logging;
+pub mod metrics;
+pub mod auth;
+pub mod users;
+pub mod businesses;
+pub mod websites;
+pub mod components;
+pub mod admin;
+pub mod messaging;
+pub mod storage;
+pub mod email;
+pub mod validation;
+pub mod utils;
+pub mod routing;
+pub mod scheduler;
+pub mod models;
+pub mod idempotency;
+pub mod openapi;
+pub mod rate_limit;
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,215 @@
+//! Process shutdown: the OS signals that start it, the draining flag that
+//! readiness reports, and the background tasks that must stop before the
+//! process exits.
+//!
+//! Shutdown goes in two steps so load balancers see the instance go unready
+//! before its listener closes: `begin_drain` flips readiness, and after the
+//! pre-stop delay `stop` lets the servers finish their in-flight requests
+//! and exit. `shut_down` does both.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+pub struct Lifecycle {
+    started: Instant,
+    started_at: DateTime<Utc>,
+    draining: watch::Sender<bool>,
+    stopping: watch::Sender<bool>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        let (draining, _) = watch::channel(false);
+        let (stopping, _) = watch::channel(false);
+        Self {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            draining,
+            stopping,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// Marks the server unready and tells background tasks to stop. The
+    /// server keeps accepting connections until `stop`.
+    pub fn begin_drain(&self) {
+        self.draining.send_replace(true);
+    }
+
+    /// Resolves once `begin_drain` has been called.
+    pub async fn draining(&self) {
+        let mut rx = self.draining.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = rx.wait_for(|&draining| draining).await;
+    }
+
+    /// Starts draining if that hasn't started, and tells the servers to stop
+    /// accepting connections.
+    pub fn stop(&self) {
+        self.begin_drain();
+        self.stopping.send_replace(true);
+    }
+
+    /// Resolves once `stop` has been called; servers shut down gracefully on
+    /// this.
+    pub async fn stopped(&self) {
+        let mut rx = self.stopping.subscribe();
+        let _ = rx.wait_for(|&stopping| stopping).await;
+    }
+
+    /// Drains, waits `pre_stop_delay` for load balancers to notice the
+    /// instance is unready, then stops.
+    pub async fn shut_down(&self, pre_stop_delay: Duration) {
+        self.begin_drain();
+        tokio::time::sleep(pre_stop_delay).await;
+        self.stop();
+    }
+
+    /// Keeps `handle` so `join_tasks` waits for it. The task must exit on its
+    /// own once draining starts.
+    pub fn track(&self, handle: JoinHandle<()>) {
+        self.tasks.lock().unwrap().push(handle);
+    }
+
+    /// Waits for every tracked task to finish. Returns false if some were
+    /// still running after `timeout`; those are aborted.
+    pub async fn join_tasks(&self, timeout: Duration) -> bool {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let aborts: Vec<_> = tasks.iter().map(JoinHandle::abort_handle).collect();
+
+        let joined = tokio::time::timeout(timeout, async {
+            for task in tasks {
+                if let Err(e) = task.await {
+                    tracing::error!(error = %e, "background task failed");
+                }
+            }
+        })
+        .await;
+
+        if joined.is_err() {
+            aborts.iter().for_each(|a| a.abort());
+        }
+        joined.is_ok()
+    }
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM where there is one.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::app::{create_app_with_config, AppConfig};
+    use crate::storage::MemoryStorage;
+    use crate::test_support;
+
+    #[tokio::test]
+    async fn test_drain_releases_waiting_tasks() {
+        let lifecycle = Arc::new(Lifecycle::new());
+        let waiter = lifecycle.clone();
+        lifecycle.track(tokio::spawn(async move { waiter.draining().await }));
+
+        assert!(!lifecycle.is_draining());
+        lifecycle.begin_drain();
+        assert!(lifecycle.is_draining());
+        assert!(lifecycle.join_tasks(Duration::from_secs(1)).await);
+    }
+
+    /// Answers `GET path` from the server at `addr` with its status line.
+    async fn status_line(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_readiness_fails_before_the_listener_closes() {
+        let lifecycle = Arc::new(Lifecycle::new());
+        let config = AppConfig {
+            lifecycle: lifecycle.clone(),
+            ..test_support::config()
+        };
+        let app = create_app_with_config(Arc::new(MemoryStorage::new()), config).await.main;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stopped = lifecycle.clone();
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).with_graceful_shutdown(async move { stopped.stopped().await }).await
+        });
+
+        assert_eq!(status_line(addr, "/health/ready").await, "HTTP/1.1 200 OK");
+
+        let shutdown = lifecycle.clone();
+        let shutdown = tokio::spawn(async move { shutdown.shut_down(Duration::from_millis(300)).await });
+        lifecycle.draining().await;
+        assert_eq!(status_line(addr, "/health/ready").await, "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(status_line(addr, "/health/live").await, "HTTP/1.1 200 OK");
+        assert!(!server.is_finished());
+
+        shutdown.await.unwrap();
+        server.await.unwrap().unwrap();
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_join_gives_up_after_timeout() {
+        let lifecycle = Lifecycle::new();
+        lifecycle.track(tokio::spawn(std::future::pending()));
+
+        lifecycle.begin_drain();
+        assert!(!lifecycle.join_tasks(Duration::from_millis(10)).await);
+    }
+}
@@ -1,87 +1,230 @@
-mod app;
-mod config;
-mod auth;
-mod users;
-mod businesses;
-mod websites;
-mod components;
-mod admin;
-mod messaging;
-mod storage;
-mod email;
-mod validation;
-mod utils;
-mod routing;
-mod models;
-
 use std::env;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
 
-use config::Config;
-use storage::{MemoryStorage, PostgresStorage, StorageLayer};
+use learner::admin::setup::{self, Bootstrap};
+use learner::app::{self, AppConfig};
+use learner::audit::{AuditSink, MemoryAuditSink, PostgresAuditSink, SqliteAuditSink};
+use learner::config::{Config, RateLimitStoreKind};
+use learner::idempotency::{IdempotencyStore, PostgresIdempotencyStore};
+use learner::lifecycle::{self, Lifecycle};
+use learner::logging;
+use learner::rate_limit::{PostgresRateLimitStore, RateLimitStore};
+use learner::storage::{self, CachedStorage, MemoryStorage, PostgresStorage, SqliteStorage, StorageLayer};
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
-    let config = Config::from_env();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config = match Config::load(&args) {
+        Ok((config, rest)) if rest.is_empty() => config,
+        Ok((_, rest)) => {
+            eprintln!("Unexpected argument '{}'", rest[0]);
+            std::process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    logging::init(&config.logging);
+    if config.uses_default_secret() {
+        tracing::warn!("signing tokens with the built-in JWT secret; set JWT_SECRET outside development");
+    }
 
-    let storage: Arc<dyn StorageLayer> = if env::var("DATABASE_URL").is_ok() {
-        match setup_postgres(&config).await {
+    let database_url = config.database.url.as_deref();
+    let mut shared_rate_limits: Option<Arc<dyn RateLimitStore>> = None;
+    let mut idempotency: Option<Arc<dyn IdempotencyStore>> = None;
+    let database: Option<(Arc<dyn StorageLayer>, Arc<dyn AuditSink>)> = if let Some(url) = database_url.filter(|u| u.starts_with("sqlite:")) {
+        match setup_sqlite(url, &config).await {
+            Ok(sqlite) => {
+                tracing::info!("connected to SQLite database");
+                let audit = Arc::new(SqliteAuditSink::new(sqlite.pool().clone()));
+                Some((with_cache(sqlite, &config), audit))
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to open database; falling back to in-memory storage");
+                None
+            }
+        }
+    } else if let Some(url) = database_url {
+        match setup_postgres(url, &config).await {
             Ok(pg) => {
-                println!("Connected to PostgreSQL database");
-                Arc::new(pg)
+                tracing::info!("connected to PostgreSQL database");
+                let audit = Arc::new(PostgresAuditSink::new(pg.pool().clone()));
+                idempotency = Some(Arc::new(PostgresIdempotencyStore::new(pg.pool().clone())));
+                if config.rate_limit.store == RateLimitStoreKind::Postgres {
+                    shared_rate_limits = Some(Arc::new(PostgresRateLimitStore::new(pg.pool().clone())));
+                }
+                Some((with_cache(pg, &config), audit))
             }
             Err(e) => {
-                eprintln!("Failed to connect to database: {}", e);
-                eprintln!("Falling back to in-memory storage");
-                create_memory_storage()
+                tracing::error!(error = %e, "failed to connect to database; falling back to in-memory storage");
+                None
             }
         }
     } else {
-        println!("No DATABASE_URL set, using in-memory storage");
-        create_memory_storage()
+        tracing::info!("no database configured, using in-memory storage");
+        None
     };
 
-    println!("===========================================");
-    println!("  Default admin credentials for testing:");
-    println!("  Email:    admin@example.com");
-    println!("  Password: admin123");
-    println!("===========================================");
+    let mut snapshots = None;
+    let (storage, audit): (Arc<dyn StorageLayer>, Arc<dyn AuditSink>) = match database {
+        Some(database) => database,
+        None => {
+            let (storage, task) = create_memory_storage(&config);
+            snapshots = task;
+            (storage, Arc::new(MemoryAuditSink::new()))
+        }
+    };
 
-    let app = app::create_app(storage).await;
     let addr = config.server_addr();
+    let setup_token = match setup::bootstrap(&*storage, &*audit, &config).await {
+        Ok(Bootstrap::Ready) => None,
+        Ok(Bootstrap::Created(email)) => {
+            tracing::info!(email = %email, "created superadmin from BOOTSTRAP_ADMIN_EMAIL");
+            None
+        }
+        Ok(Bootstrap::Pending(token)) => {
+            // Printed rather than logged: the token is a credential.
+            println!("===========================================");
+            println!("  No superadmin exists yet. Create one at:");
+            println!("  http://{}/admin/setup?token={}", addr, token);
+            println!("  The token works once and only for this run.");
+            println!("===========================================");
+            Some(token)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to bootstrap the first superadmin");
+            std::process::exit(1);
+        }
+    };
+
+    let lifecycle = Arc::new(Lifecycle::new());
+    let mut app_config = AppConfig { audit, setup_token, lifecycle: lifecycle.clone(), ..AppConfig::from(&config) };
+    match shared_rate_limits {
+        Some(store) => app_config.rate_limit_store = store,
+        None if config.rate_limit.enabled && config.rate_limit.store == RateLimitStoreKind::Postgres => {
+            tracing::warn!("no database for shared rate limits; each instance limits on its own");
+        }
+        None => {}
+    }
+    if let Some(store) = idempotency {
+        app_config.idempotency = store;
+    }
+
+    let app = app::create_app_with_config(storage.clone(), app_config).await;
 
-    println!("Server running at http://{}", addr);
-    println!("Admin panel: http://{}/admin/login", addr);
+    tracing::info!(addr = %addr, "server running; admin panel at /admin/login");
 
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .expect("Failed to bind to address");
 
-    axum::serve(listener, app)
-        .await
-        .expect("Failed to start server");
+    if let (Some(metrics), Some(metrics_addr)) = (app.metrics, config.metrics.listen.clone()) {
+        let metrics_listener = tokio::net::TcpListener::bind(&metrics_addr)
+            .await
+            .expect("Failed to bind the metrics address");
+        tracing::info!(addr = %metrics_addr, "metrics listening");
+        let drain = lifecycle.clone();
+        tokio::spawn(async move {
+            let served = axum::serve(metrics_listener, metrics)
+                .with_graceful_shutdown(async move { drain.stopped().await })
+                .await;
+            if let Err(e) = served {
+                tracing::error!(error = %e, "metrics listener failed");
+            }
+        });
+    }
+
+    let drain = lifecycle.clone();
+    let mut server = tokio::spawn(
+        axum::serve(listener, app.main.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move { drain.stopped().await })
+            .into_future(),
+    );
+
+    tokio::select! {
+        result = &mut server => {
+            result.expect("Server task panicked").expect("Failed to start server");
+        }
+        _ = lifecycle::shutdown_signal() => {
+            let delay = config.pre_stop_delay();
+            tracing::info!(delay_secs = delay.as_secs(), "shutting down; reporting unready before closing the listener");
+            // The server keeps answering during the delay, only unready.
+            lifecycle.shut_down(delay).await;
+
+            let timeout = config.shutdown_timeout();
+            tracing::info!(timeout_secs = timeout.as_secs(), "draining requests");
+            match tokio::time::timeout(timeout, &mut server).await {
+                Ok(Ok(Ok(()))) => {}
+                Ok(Ok(Err(e))) => tracing::error!(error = %e, "server failed while draining"),
+                Ok(Err(e)) => tracing::error!(error = %e, "server task failed while draining"),
+                Err(_) => {
+                    tracing::warn!("requests still running after the shutdown timeout; dropping them");
+                    server.abort();
+                }
+            }
+        }
+    }
+
+    // Also covers the server stopping on its own, so jobs never outlive it.
+    lifecycle.stop();
+    if !lifecycle.join_tasks(config.shutdown_timeout()).await {
+        tracing::warn!("background jobs still running after the shutdown timeout; aborted them");
+    }
+    if let Some(task) = snapshots {
+        task.abort();
+    }
+    if let Err(e) = storage.close().await {
+        tracing::error!(error = %e, "failed to close storage");
+    }
+    tracing::info!("shutdown complete");
 }
 
-async fn setup_postgres(config: &Config) -> Result<PostgresStorage, storage::DbError> {
-    let pg = PostgresStorage::from_url(&config.database_url).await?;
-    println!("Running database migrations...");
-    pg.run_migrations().await?;
-    println!("Migrations completed successfully");
+/// Puts a lookup cache in front of a database-backed store, unless disabled.
+fn with_cache<S: StorageLayer + 'static>(storage: S, config: &Config) -> Arc<dyn StorageLayer> {
+    match config.cache_config() {
+        Some(cache) => Arc::new(CachedStorage::new(storage, cache)),
+        None => Arc::new(storage),
+    }
+}
 
-    let password_hash = bcrypt::hash("admin123", bcrypt::DEFAULT_COST)
-        .expect("Failed to hash password");
-    pg.seed_admin("admin@example.com", &password_hash).await?;
+async fn setup_postgres(url: &str, config: &Config) -> Result<PostgresStorage, storage::DbError> {
+    let pg = PostgresStorage::connect(url, &config.pool_config()).await?;
+    tracing::info!("running database migrations");
+    pg.run_migrations().await?;
+    tracing::info!("migrations completed");
 
     Ok(pg)
 }
 
-fn create_memory_storage() -> Arc<dyn StorageLayer> {
-    let password_hash = bcrypt::hash("admin123", bcrypt::DEFAULT_COST)
-        .expect("Failed to hash password");
+async fn setup_sqlite(url: &str, config: &Config) -> Result<SqliteStorage, storage::DbError> {
+    let sqlite = SqliteStorage::connect(url, &config.pool_config()).await?;
+    tracing::info!("running database migrations");
+    sqlite.run_migrations().await?;
+    tracing::info!("migrations completed");
+
+    Ok(sqlite)
+}
+
+/// The store and, if it is persisted, its periodic snapshot task. Closing the
+/// store takes the final snapshot.
+fn create_memory_storage(config: &Config) -> (Arc<MemoryStorage>, Option<JoinHandle<()>>) {
+    let Some(path) = &config.storage.snapshot_path else {
+        return (Arc::new(MemoryStorage::new()), None);
+    };
+
+    let memory = Arc::new(MemoryStorage::open(path).expect("Failed to load in-memory storage snapshot"));
+    tracing::info!(path = %path, "persisting in-memory storage");
+
+    let task = memory
+        .clone()
+        .spawn_periodic_snapshots(Duration::from_secs(config.storage.snapshot_interval_secs));
 
-    Arc::new(MemoryStorage::with_default_admin(
-        "admin@example.com",
-        &password_hash,
-    ))
+    (memory, Some(task))
 }
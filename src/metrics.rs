@@ -0,0 +1,169 @@
+//! Request and sign-in counters for the Prometheus `/metrics` endpoint.
+//! Storage calls are counted separately by `storage::StorageMetrics`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::auth::model::AuthMethod;
+use crate::storage::metrics::LATENCY_BUCKETS;
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Per-bucket counts; the last slot counts observations above every bound.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, self.count);
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Debug, Default)]
+struct RouteMetrics {
+    statuses: BTreeMap<u16, u64>,
+    latency: Histogram,
+}
+
+/// Keyed by `(method, route)`, where route is the matched path template so
+/// ids in URLs don't create a series each.
+type RouteKey = (String, String);
+
+/// Keyed by `(auth method, error code)`; the code is `None` for successes.
+type LoginKey = (&'static str, Option<String>);
+
+/// Keyed by `(route group, account level)`; the level is `anonymous` for
+/// callers limited by IP.
+type RateLimitKey = (&'static str, &'static str);
+
+#[derive(Debug, Default)]
+pub struct AppMetrics {
+    routes: Mutex<BTreeMap<RouteKey, RouteMetrics>>,
+    logins: Mutex<BTreeMap<LoginKey, u64>>,
+    rate_limited: Mutex<BTreeMap<RateLimitKey, u64>>,
+}
+
+impl AppMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut routes = self.routes.lock().unwrap();
+        let metrics = routes.entry((method.to_string(), route.to_string())).or_default();
+        *metrics.statuses.entry(status).or_default() += 1;
+        metrics.latency.observe(elapsed.as_secs_f64());
+    }
+
+    /// `code` is the `AuthError` code of a failed sign-in, `None` on success.
+    pub fn record_login(&self, method: &AuthMethod, code: Option<&str>) {
+        let mut logins = self.logins.lock().unwrap();
+        *logins.entry((method.as_str(), code.map(str::to_string))).or_default() += 1;
+    }
+
+    pub fn record_rate_limited(&self, group: &'static str, level: &'static str) {
+        *self.rate_limited.lock().unwrap().entry((group, level)).or_default() += 1;
+    }
+
+    /// Appends the metrics in the Prometheus text exposition format.
+    pub fn render_prometheus(&self, out: &mut String) {
+        let routes = self.routes.lock().unwrap();
+
+        out.push_str("# HELP http_requests_total HTTP requests by method, route and status.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((method, route), m) in routes.iter() {
+            for (status, count) in &m.statuses {
+                let _ = writeln!(
+                    out,
+                    "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    method, escape(route), status, count
+                );
+            }
+        }
+
+        out.push_str("# HELP http_request_duration_seconds HTTP request latency by method and route.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((method, route), m) in routes.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+            m.latency.render(out, "http_request_duration_seconds", &labels);
+        }
+        drop(routes);
+
+        let logins = self.logins.lock().unwrap();
+        out.push_str("# HELP auth_logins_total Sign-in attempts by method and outcome; failures carry the AuthError code.\n");
+        out.push_str("# TYPE auth_logins_total counter\n");
+        for ((method, code), count) in logins.iter() {
+            let (outcome, code) = match code {
+                Some(code) => ("failure", code.as_str()),
+                None => ("success", ""),
+            };
+            let _ = writeln!(
+                out,
+                "auth_logins_total{{method=\"{}\",outcome=\"{}\",code=\"{}\"}} {}",
+                method, outcome, escape(code), count
+            );
+        }
+        drop(logins);
+
+        let rate_limited = self.rate_limited.lock().unwrap();
+        out.push_str("# HELP rate_limited_requests_total Requests refused by the rate limiter, by route group and account level.\n");
+        out.push_str("# TYPE rate_limited_requests_total counter\n");
+        for ((group, level), count) in rate_limited.iter() {
+            let _ = writeln!(out, "rate_limited_requests_total{{group=\"{}\",level=\"{}\"}} {}", group, level, count);
+        }
+    }
+}
+
+/// Escapes a label value for the text format.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_requests_and_logins() {
+        let metrics = AppMetrics::new();
+        metrics.record_request("GET", "/users/:id", 200, Duration::from_millis(3));
+        metrics.record_request("GET", "/users/:id", 404, Duration::from_secs(5));
+        metrics.record_login(&AuthMethod::Password, None);
+        metrics.record_login(&AuthMethod::Password, Some("INVALID_CREDENTIALS"));
+        metrics.record_login(&AuthMethod::Password, Some("INVALID_CREDENTIALS"));
+        metrics.record_rate_limited("auth", "anonymous");
+
+        let mut out = String::new();
+        metrics.render_prometheus(&mut out);
+
+        assert!(out.contains("http_requests_total{method=\"GET\",route=\"/users/:id\",status=\"404\"} 1"));
+        assert!(out.contains("http_request_duration_seconds_bucket{method=\"GET\",route=\"/users/:id\",le=\"0.005\"} 1"));
+        assert!(out.contains("http_request_duration_seconds_bucket{method=\"GET\",route=\"/users/:id\",le=\"+Inf\"} 2"));
+        assert!(out.contains("auth_logins_total{method=\"password\",outcome=\"success\",code=\"\"} 1"));
+        assert!(out.contains(
+            "auth_logins_total{method=\"password\",outcome=\"failure\",code=\"INVALID_CREDENTIALS\"} 2"
+        ));
+        assert!(out.contains("rate_limited_requests_total{group=\"auth\",level=\"anonymous\"} 1"));
+    }
+}
@@ -0,0 +1,197 @@
+//! OpenAPI 3.1 description of the JSON API, served at `/openapi.json` with
+//! a Redoc page at `/docs`.
+//!
+//! Schemas come from the request and response types through `ToSchema`.
+//! The `object!` and `string_enum!` impls destructure or match their type
+//! exhaustively, so adding a field or variant without describing it fails
+//! to compile. `spec.rs` lists the operations; its test fails for any route
+//! the spec doesn't describe.
+
+mod schemas;
+mod spec;
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use askama::Template;
+use axum::{
+    http::header,
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::app::AppState;
+
+pub use spec::document;
+
+/// Named schemas, emitted under `components/schemas`.
+#[derive(Default)]
+pub struct Components {
+    schemas: BTreeMap<String, Value>,
+}
+
+impl Components {
+    /// A `$ref` to the schema called `name`, built by `build` the first time.
+    pub fn register(&mut self, name: &str, build: impl FnOnce(&mut Self) -> Value) -> Value {
+        if !self.schemas.contains_key(name) {
+            // Claimed first so a type that refers to itself doesn't recurse.
+            self.schemas.insert(name.to_string(), Value::Null);
+            let schema = build(self);
+            self.schemas.insert(name.to_string(), schema);
+        }
+        json!({ "$ref": format!("#/components/schemas/{}", name) })
+    }
+
+    /// The registered schema a `$ref` from `register` points at.
+    pub fn resolve<'a>(&'a self, reference: &Value) -> Option<&'a Value> {
+        let name = reference["$ref"].as_str()?.strip_prefix("#/components/schemas/")?;
+        self.schemas.get(name)
+    }
+
+    pub fn into_value(self) -> Value {
+        json!({ "schemas": self.schemas })
+    }
+}
+
+/// A type that can describe its JSON form.
+pub trait ToSchema {
+    fn schema(components: &mut Components) -> Value;
+
+    /// Whether an object field of this type may be missing or null.
+    fn optional() -> bool {
+        false
+    }
+}
+
+macro_rules! primitive {
+    ($($ty:ty => $schema:tt),* $(,)?) => {
+        $(
+            impl ToSchema for $ty {
+                fn schema(_: &mut Components) -> Value {
+                    json!($schema)
+                }
+            }
+        )*
+    };
+}
+
+primitive! {
+    String => { "type": "string" },
+    &'static str => { "type": "string" },
+    bool => { "type": "boolean" },
+    i32 => { "type": "integer", "format": "int32" },
+    i64 => { "type": "integer", "format": "int64" },
+    u32 => { "type": "integer", "format": "int32", "minimum": 0 },
+    u64 => { "type": "integer", "format": "int64", "minimum": 0 },
+    usize => { "type": "integer", "minimum": 0 },
+    f64 => { "type": "number", "format": "double" },
+    Uuid => { "type": "string", "format": "uuid" },
+    DateTime<Utc> => { "type": "string", "format": "date-time" },
+    Value => {},
+}
+
+impl<T: ToSchema> ToSchema for Option<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "anyOf": [T::schema(components), { "type": "null" }] })
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: ToSchema> ToSchema for Vec<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components) })
+    }
+}
+
+/// `ToSchema` for a struct, as a named object schema. `Option` fields and
+/// those marked `= default` (`#[serde(default)]`) aren't required; fields
+/// listed under `skip` are `#[serde(skip)]` and left out.
+///
+/// ```ignore
+/// object!(UpdateUserRequest { email: Option<String>, ... } skip { password_hash });
+/// ```
+macro_rules! object {
+    (
+        $name:ident { $($field:ident: $ty:ty $(= $default:ident)?),* $(,)? }
+        $(skip { $($skipped:ident),* $(,)? })?
+    ) => {
+        impl $crate::openapi::ToSchema for $name {
+            fn schema(components: &mut $crate::openapi::Components) -> serde_json::Value {
+                components.register(stringify!($name), |components| {
+                    // Fails to compile once the struct and this list disagree.
+                    let _ = |value: &$name| {
+                        let $name { $($field,)* $($($skipped: _,)*)? } = value;
+                        $(let _: &$ty = $field;)*
+                    };
+
+                    let mut properties = serde_json::Map::new();
+                    let mut required: Vec<&str> = Vec::new();
+                    $(
+                        properties.insert(
+                            stringify!($field).to_string(),
+                            <$ty as $crate::openapi::ToSchema>::schema(components),
+                        );
+                        let defaulted = false $(|| stringify!($default) == "default")?;
+                        if !defaulted && !<$ty as $crate::openapi::ToSchema>::optional() {
+                            required.push(stringify!($field));
+                        }
+                    )*
+                    serde_json::json!({ "type": "object", "properties": properties, "required": required })
+                })
+            }
+        }
+    };
+}
+
+/// `ToSchema` for a fieldless enum, as a named string schema listing the
+/// serialized variant names.
+macro_rules! string_enum {
+    ($name:ident { $($variant:ident => $value:literal),* $(,)? }) => {
+        impl $crate::openapi::ToSchema for $name {
+            fn schema(components: &mut $crate::openapi::Components) -> serde_json::Value {
+                components.register(stringify!($name), |_| {
+                    // Fails to compile once a variant is added.
+                    let _ = |value: &$name| match value {
+                        $($name::$variant => ()),*
+                    };
+                    serde_json::json!({ "type": "string", "enum": [$($value),*] })
+                })
+            }
+        }
+    };
+}
+
+pub(crate) use object;
+pub(crate) use string_enum;
+
+#[derive(Template)]
+#[template(path = "docs.html")]
+struct DocsTemplate {
+    spec_url: &'static str,
+}
+
+fn cached_document() -> &'static Value {
+    static DOCUMENT: OnceLock<Value> = OnceLock::new();
+    DOCUMENT.get_or_init(document)
+}
+
+async fn openapi_json() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], cached_document().to_string())
+}
+
+async fn docs() -> impl IntoResponse {
+    Html(DocsTemplate { spec_url: "/openapi.json" }.render().unwrap_or_default())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(docs))
+}
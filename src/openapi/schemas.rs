@@ -0,0 +1,296 @@
+//! `ToSchema` for every type the API reads or writes.
+
+use serde_json::{json, Value};
+
+use super::{object, string_enum, Components, ToSchema};
+use crate::admin::model::{Admin, AdminRole, UpdateAdminRequest};
+use crate::audit::{AuditEvent, AuditPage, ChainVerification};
+use crate::auth::history::{LoginHistoryQuery, LoginHistoryResponse};
+use crate::auth::model::{
+    AccountInfo, AccountLevel, AccountStatus, AuthMethod, LoginEvent, LoginRequest, LoginResponse,
+    UpdateAccountRequest, UserAccount,
+};
+use crate::auth::new::{RegisterRequest, RegisterResponse};
+use crate::auth::out::LogoutResponse;
+use crate::auth::r#in::AdminLoginRequest;
+use crate::routing::error::FieldError;
+use crate::routing::health::{CheckSummary, ComponentHealth, ReadinessResponse, SystemHealth};
+use crate::routing::private_routes::{AuditQuery, ListUsersQuery};
+use crate::routing::resp_structures::{ApiResponse, HealthResponse, HealthStatus};
+use crate::scheduler::runner::{JobOutcome, JobStatus};
+use crate::storage::{CacheStats, PoolStats};
+use crate::users::model::{SortDirection, UpdateUserRequest, UserLoginResponse, UserProfile, UserSortField};
+
+impl<T: ToSchema> ToSchema for ApiResponse<T> {
+    fn schema(components: &mut Components) -> Value {
+        let _ = |value: &ApiResponse<T>| {
+            let ApiResponse { success, data } = value;
+            let _: (&bool, &Option<T>) = (success, data);
+        };
+        json!({
+            "type": "object",
+            "properties": {
+                "success": { "type": "boolean", "const": true },
+                "data": T::schema(components),
+            },
+            "required": ["success", "data"],
+        })
+    }
+}
+
+/// The body of every error; see `routing::error::ApiError`.
+pub struct Problem;
+
+impl ToSchema for Problem {
+    fn schema(components: &mut Components) -> Value {
+        components.register("Problem", |components| {
+            json!({
+                "type": "object",
+                "description": "RFC 7807 problem details. `code` is stable; match on it rather than on `detail`.",
+                "properties": {
+                    "type": { "type": "string", "const": "about:blank" },
+                    "title": { "type": "string" },
+                    "status": { "type": "integer" },
+                    "detail": { "type": "string" },
+                    "code": { "type": "string" },
+                    "errors": Vec::<FieldError>::schema(components),
+                    "request_id": { "type": "string" },
+                },
+                "required": ["type", "title", "status", "detail", "code"],
+            })
+        })
+    }
+}
+
+impl ToSchema for JobOutcome {
+    fn schema(components: &mut Components) -> Value {
+        components.register("JobOutcome", |_| {
+            let _ = |value: &JobOutcome| match value {
+                JobOutcome::Succeeded { summary } => { let _: &String = summary; }
+                JobOutcome::Failed { error } => { let _: &String = error; }
+                JobOutcome::Skipped => {}
+            };
+            let variant = |status: &str, field: Option<&str>| {
+                let mut properties = json!({ "status": { "type": "string", "const": status } });
+                let mut required = vec!["status"];
+                if let Some(field) = field {
+                    properties[field] = json!({ "type": "string" });
+                    required.push(field);
+                }
+                json!({ "type": "object", "properties": properties, "required": required })
+            };
+            json!({ "oneOf": [
+                variant("succeeded", Some("summary")),
+                variant("failed", Some("error")),
+                variant("skipped", None),
+            ]})
+        })
+    }
+}
+
+string_enum!(AccountLevel { Free => "Free", Premium => "Premium", Enterprise => "Enterprise" });
+string_enum!(AccountStatus {
+    Active => "Active",
+    Pending => "Pending",
+    Suspended => "Suspended",
+    Banned => "Banned",
+    Deactivated => "Deactivated",
+});
+string_enum!(AdminRole { SuperAdmin => "SuperAdmin", Admin => "Admin", Moderator => "Moderator" });
+string_enum!(AuthMethod { Password => "Password", AdminPassword => "AdminPassword", AdminPanel => "AdminPanel" });
+string_enum!(HealthStatus { Ok => "ok", Error => "error", Unavailable => "unavailable", Draining => "draining" });
+string_enum!(UserSortField { CreatedAt => "created_at", Email => "email", Username => "username" });
+string_enum!(SortDirection { Asc => "asc", Desc => "desc" });
+
+object!(FieldError { field: String, message: String });
+
+// Auth
+object!(LoginRequest { email: String, password: String, remember_me: Option<bool> });
+object!(AdminLoginRequest { email: String, password: String, device_info: Option<String> });
+object!(LoginResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: i64,
+    user_profile: UserProfile,
+    account_info: AccountInfo,
+});
+object!(AccountInfo { level: AccountLevel, status: AccountStatus, capabilities: Vec<String> });
+object!(RegisterRequest { email: String, password: String, username: String, first_name: String, last_name: String });
+object!(RegisterResponse { success: bool, message: String, user_id: Option<uuid::Uuid> });
+object!(LogoutResponse { success: bool, message: String });
+object!(LoginHistoryQuery { limit: Option<i64> });
+object!(LoginHistoryResponse { events: Vec<LoginEvent> });
+object!(LoginEvent {
+    id: uuid::Uuid,
+    user_id: Option<uuid::Uuid>,
+    email: String,
+    success: bool,
+    failure_reason: Option<String>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    auth_method: AuthMethod,
+    device_fingerprint: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+});
+
+// Users and accounts
+object!(UserProfile {
+    id: uuid::Uuid,
+    email: String,
+    username: String,
+    first_name: String,
+    last_name: String,
+    is_active: bool,
+    created_at: chrono::DateTime<chrono::Utc>,
+});
+object!(UserLoginResponse { users: Vec<UserProfile>, total: i64, page: i32, per_page: i32 });
+object!(ListUsersQuery {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    status: Option<AccountStatus>,
+    account_level: Option<AccountLevel>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+    search: Option<String>,
+    deleted: bool = default,
+    sort: UserSortField = default,
+    direction: SortDirection = default,
+});
+object!(UpdateUserRequest {
+    email: Option<String>,
+    username: Option<String>,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    is_active: Option<bool>,
+} skip { password_hash });
+object!(UserAccount {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    account_level: AccountLevel,
+    account_status: AccountStatus,
+    capabilities: Vec<String>,
+    status_reason: Option<String>,
+    status_changed_at: Option<chrono::DateTime<chrono::Utc>>,
+    status_changed_by: Option<uuid::Uuid>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    version: i64,
+});
+object!(UpdateAccountRequest {
+    account_level: Option<AccountLevel>,
+    account_status: Option<AccountStatus>,
+    capabilities: Option<Vec<String>>,
+    status_reason: Option<String>,
+} skip { changed_by });
+
+// Admins
+object!(Admin {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    role: AdminRole,
+    permissions: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    created_by: Option<uuid::Uuid>,
+    version: i64,
+});
+object!(UpdateAdminRequest { role: Option<AdminRole>, permissions: Option<Vec<String>> });
+
+// Audit
+object!(AuditQuery {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    actor_id: Option<uuid::Uuid>,
+    action: Option<String>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+});
+object!(AuditEvent {
+    seq: i64,
+    id: uuid::Uuid,
+    actor_id: Option<uuid::Uuid>,
+    action: String,
+    target_type: String,
+    target_id: Option<String>,
+    before: Option<Value>,
+    after: Option<Value>,
+    ip_address: Option<String>,
+    request_id: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    prev_hash: String,
+    hash: String,
+});
+object!(AuditPage { events: Vec<AuditEvent>, total: i64 });
+object!(ChainVerification { events_checked: u64, broken_at: Option<i64> });
+
+// System
+object!(HealthResponse { status: HealthStatus });
+object!(CheckSummary { name: &'static str, status: HealthStatus });
+object!(ReadinessResponse { status: HealthStatus, checks: Vec<CheckSummary> });
+object!(ComponentHealth { name: &'static str, status: HealthStatus, latency_ms: u64, detail: Option<String> });
+object!(PoolStats { size: u32, idle: usize, max_connections: u32 });
+object!(SystemHealth {
+    status: HealthStatus,
+    version: &'static str,
+    started_at: chrono::DateTime<chrono::Utc>,
+    uptime_secs: u64,
+    draining: bool,
+    components: Vec<ComponentHealth>,
+    pool: Option<PoolStats>,
+});
+object!(CacheStats { name: &'static str, hits: u64, misses: u64, entries: usize, capacity: usize });
+object!(JobStatus {
+    name: &'static str,
+    interval_secs: u64,
+    running: bool,
+    runs: u64,
+    failures: u64,
+    skipped: u64,
+    last_started_at: Option<chrono::DateTime<chrono::Utc>>,
+    last_finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    last_duration_ms: Option<u64>,
+    last_outcome: Option<JobOutcome>,
+    next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+});
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    /// The enum values in the schema must be what serde writes.
+    fn assert_serialized_values<T: ToSchema + Serialize>(variants: &[T]) {
+        let mut components = Components::default();
+        let reference = T::schema(&mut components);
+        let listed = components.resolve(&reference).unwrap()["enum"].clone();
+        let serialized: Vec<Value> = variants.iter().map(|v| serde_json::to_value(v).unwrap()).collect();
+        assert_eq!(listed, Value::Array(serialized));
+    }
+
+    #[test]
+    fn test_enum_schemas_match_serde() {
+        use AccountStatus::*;
+        assert_serialized_values(&[AccountLevel::Free, AccountLevel::Premium, AccountLevel::Enterprise]);
+        assert_serialized_values(&[Active, Pending, Suspended, Banned, Deactivated]);
+        assert_serialized_values(&[AdminRole::SuperAdmin, AdminRole::Admin, AdminRole::Moderator]);
+        assert_serialized_values(&[AuthMethod::Password, AuthMethod::AdminPassword, AuthMethod::AdminPanel]);
+        assert_serialized_values(&[
+            HealthStatus::Ok,
+            HealthStatus::Error,
+            HealthStatus::Unavailable,
+            HealthStatus::Draining,
+        ]);
+    }
+
+    #[test]
+    fn test_optional_fields_are_not_required() {
+        let mut components = Components::default();
+        let reference = LoginRequest::schema(&mut components);
+        let schema = components.resolve(&reference).unwrap();
+        assert_eq!(schema["required"], json!(["email", "password"]));
+        assert_eq!(schema["properties"]["remember_me"]["anyOf"][1], json!({ "type": "null" }));
+    }
+}
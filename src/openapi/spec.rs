@@ -0,0 +1,472 @@
+//! The operations of the API. Keep in step with the routers; the test below
+//! reads their `.route(...)` calls and fails on any difference.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use super::schemas::Problem;
+use super::{Components, ToSchema};
+use crate::admin::model::{Admin, UpdateAdminRequest};
+use crate::audit::{AuditPage, ChainVerification};
+use crate::auth::history::{LoginHistoryQuery, LoginHistoryResponse};
+use crate::auth::model::{LoginEvent, LoginRequest, LoginResponse, UpdateAccountRequest, UserAccount};
+use crate::auth::new::{RegisterRequest, RegisterResponse};
+use crate::auth::out::LogoutResponse;
+use crate::auth::r#in::AdminLoginRequest;
+use crate::routing::health::{ReadinessResponse, SystemHealth};
+use crate::routing::private_routes::{AuditQuery, ListUsersQuery};
+use crate::routing::resp_structures::{ApiResponse, HealthResponse};
+use crate::scheduler::runner::JobStatus;
+use crate::storage::CacheStats;
+use crate::users::model::{UpdateUserRequest, UserLoginResponse, UserProfile};
+
+#[derive(Default)]
+struct Spec {
+    components: Components,
+    paths: BTreeMap<String, Map<String, Value>>,
+}
+
+struct Operation<'a> {
+    spec: &'a mut Spec,
+    method: &'static str,
+    path: &'static str,
+    value: Value,
+}
+
+impl Spec {
+    /// Starts an operation. Path parameters are taken from `{...}` segments,
+    /// which are all ids; every error is a `Problem`. Operations under
+    /// `/api/<version>` also take `Accept-Version`, and POST, PUT and PATCH
+    /// take `Idempotency-Key` unless the response `issues_tokens`.
+    fn operation(&mut self, method: &'static str, path: &'static str, tag: &str, summary: &str) -> Operation<'_> {
+        let mut parameters: Vec<Value> = path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .map(|name| {
+                json!({ "name": name, "in": "path", "required": true, "schema": Uuid::schema(&mut self.components) })
+            })
+            .collect();
+        if path.starts_with("/api/") {
+            parameters.push(json!({
+                "name": "Accept-Version",
+                "in": "header",
+                "required": false,
+                "description": "The version the client expects, e.g. `v1`; refused if it isn't the one in the path.",
+                "schema": { "type": "string" },
+            }));
+        }
+        if matches!(method, "post" | "put" | "patch") {
+            parameters.push(json!({
+                "name": "Idempotency-Key",
+                "in": "header",
+                "required": false,
+                "description": "Makes retries safe: the first response is replayed, marked `Idempotent-Replayed`, \
+                                to requests with the same key and body for 24 hours.",
+                "schema": { "type": "string", "minLength": 1, "maxLength": 255 },
+            }));
+        }
+        let problem = Problem::schema(&mut self.components);
+        let value = json!({
+            "tags": [tag],
+            "summary": summary,
+            "parameters": parameters,
+            "responses": {
+                "default": {
+                    "description": "Error",
+                    "content": { "application/problem+json": { "schema": problem } },
+                },
+            },
+        });
+        Operation { spec: self, method, path, value }
+    }
+
+    /// Repeats every operation under `from` at `to`, marked deprecated.
+    fn alias(&mut self, from: &str, to: &str) {
+        let aliased: Vec<_> = self
+            .paths
+            .iter()
+            .filter_map(|(path, operations)| Some((format!("{}{}", to, path.strip_prefix(from)?), operations.clone())))
+            .collect();
+        for (path, mut operations) in aliased {
+            for operation in operations.values_mut() {
+                operation["deprecated"] = json!(true);
+                if let Some(parameters) = operation["parameters"].as_array_mut() {
+                    parameters.retain(|p| p["name"] != "Accept-Version");
+                }
+            }
+            self.paths.insert(path, operations);
+        }
+    }
+}
+
+impl Operation<'_> {
+    fn describe(mut self, description: &str) -> Self {
+        self.value["description"] = json!(description);
+        self
+    }
+
+    /// The response carries tokens, so `Idempotency-Key` isn't honoured.
+    fn issues_tokens(mut self) -> Self {
+        self.value["parameters"].as_array_mut().unwrap().retain(|p| p["name"] != "Idempotency-Key");
+        self
+    }
+
+    fn bearer(mut self) -> Self {
+        self.value["security"] = json!([{ "bearerAuth": [] }]);
+        self
+    }
+
+    fn body<T: ToSchema>(mut self) -> Self {
+        let schema = T::schema(&mut self.spec.components);
+        self.value["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": schema } },
+        });
+        self
+    }
+
+    /// One query parameter per field of `T`.
+    fn query<T: ToSchema>(mut self) -> Self {
+        let reference = T::schema(&mut self.spec.components);
+        let schema = self.spec.components.resolve(&reference).cloned().unwrap_or(reference);
+        let required = schema["required"].as_array().cloned().unwrap_or_default();
+        let parameters = self.value["parameters"].as_array_mut().unwrap();
+        for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+            parameters.push(json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&json!(name)),
+                "schema": property,
+            }));
+        }
+        self
+    }
+
+    /// The update is refused unless `If-Match` names the current version.
+    fn if_match(mut self) -> Self {
+        self.value["parameters"].as_array_mut().unwrap().push(json!({
+            "name": "If-Match",
+            "in": "header",
+            "required": true,
+            "description": "The `ETag` of the version the changes are based on.",
+            "schema": { "type": "string" },
+        }));
+        self
+    }
+
+    fn json<T: ToSchema>(mut self, description: &str) -> Self {
+        let schema = T::schema(&mut self.spec.components);
+        self.value["responses"]["200"] = json!({
+            "description": description,
+            "content": { "application/json": { "schema": schema } },
+        });
+        self
+    }
+
+    /// The `200` response carries the record's version as its `ETag`.
+    fn etag(mut self) -> Self {
+        self.value["responses"]["200"]["headers"] = json!({
+            "ETag": { "description": "The record's version.", "schema": { "type": "string" } },
+        });
+        self
+    }
+
+    fn text(mut self, description: &str) -> Self {
+        self.value["responses"]["200"] = json!({
+            "description": description,
+            "content": { "text/plain": { "schema": { "type": "string" } } },
+        });
+        self
+    }
+
+    fn add(self) {
+        self.spec.paths.entry(self.path.to_string()).or_default().insert(self.method.to_string(), self.value);
+    }
+}
+
+/// The OpenAPI document for the API.
+pub fn document() -> Value {
+    let mut spec = Spec::default();
+    let s = &mut spec;
+
+    // Health and metrics
+    s.operation("get", "/health", "system", "Readiness (kept for older probes)")
+        .json::<ReadinessResponse>("Ready")
+        .add();
+    s.operation("get", "/health/live", "system", "Liveness").json::<HealthResponse>("The process answers").add();
+    s.operation("get", "/health/ready", "system", "Readiness").json::<ReadinessResponse>("Ready").add();
+    let mut metrics = s
+        .operation("get", "/metrics", "system", "Prometheus metrics")
+        .describe("Served on `metrics.listen` instead when that is set.")
+        .text("Metrics in the Prometheus text format");
+    metrics.value["security"] = json!([{}, { "metricsToken": [] }]);
+    metrics.add();
+
+    // Auth
+    s.operation("post", "/api/v1/auth/register", "auth", "Register a user")
+        .body::<RegisterRequest>()
+        .json::<RegisterResponse>("Registered; the account is pending activation")
+        .add();
+    s.operation("post", "/api/v1/auth/login", "auth", "Sign in")
+        .issues_tokens()
+        .body::<LoginRequest>()
+        .json::<LoginResponse>("Signed in")
+        .add();
+    s.operation("post", "/api/v1/auth/admin/login", "auth", "Sign in as an admin")
+        .issues_tokens()
+        .body::<AdminLoginRequest>()
+        .json::<LoginResponse>("Signed in")
+        .add();
+    s.operation("post", "/api/v1/auth/logout", "auth", "Sign out this session")
+        .bearer()
+        .json::<LogoutResponse>("Signed out")
+        .add();
+    s.operation("post", "/api/v1/auth/logout-all", "auth", "Sign out every session")
+        .bearer()
+        .json::<LogoutResponse>("Signed out")
+        .add();
+    s.operation("get", "/api/v1/auth/login-history", "auth", "The caller's recent sign-ins")
+        .bearer()
+        .query::<LoginHistoryQuery>()
+        .json::<LoginHistoryResponse>("Newest first")
+        .add();
+    // `/auth` predates `/api/v1` and still answers, deprecated.
+    s.alias("/api/v1/auth", "/auth");
+
+    // Admin: users
+    s.operation("get", "/admin/api/users", "users", "List users")
+        .bearer()
+        .query::<ListUsersQuery>()
+        .json::<ApiResponse<UserLoginResponse>>("One page of users")
+        .add();
+    s.operation("post", "/admin/api/users", "users", "Create a user (not implemented)").bearer().add();
+    s.operation("get", "/admin/api/users/{id}", "users", "Get a user")
+        .bearer()
+        .json::<ApiResponse<UserProfile>>("The user")
+        .etag()
+        .add();
+    s.operation("put", "/admin/api/users/{id}", "users", "Update a user")
+        .bearer()
+        .if_match()
+        .body::<UpdateUserRequest>()
+        .json::<ApiResponse<UserProfile>>("The updated user")
+        .etag()
+        .add();
+    s.operation("delete", "/admin/api/users/{id}", "users", "Soft-delete a user and end their sessions")
+        .bearer()
+        .json::<ApiResponse<Uuid>>("The deleted user's id")
+        .add();
+    s.operation("post", "/admin/api/users/{id}/restore", "users", "Restore a soft-deleted user")
+        .bearer()
+        .json::<ApiResponse<UserProfile>>("The restored user")
+        .add();
+    s.operation("get", "/admin/api/users/{id}/account", "users", "Get a user's account")
+        .bearer()
+        .json::<ApiResponse<UserAccount>>("The account")
+        .etag()
+        .add();
+    s.operation("put", "/admin/api/users/{id}/account", "users", "Update a user's account")
+        .bearer()
+        .if_match()
+        .body::<UpdateAccountRequest>()
+        .json::<ApiResponse<UserAccount>>("The updated account")
+        .etag()
+        .add();
+    s.operation("get", "/admin/api/users/{id}/login-history", "users", "A user's recent sign-ins")
+        .bearer()
+        .query::<LoginHistoryQuery>()
+        .json::<ApiResponse<Vec<LoginEvent>>>("Newest first")
+        .add();
+
+    // Admin: admins
+    s.operation("get", "/admin/api/admins/{id}", "admins", "Get an admin")
+        .bearer()
+        .json::<ApiResponse<Admin>>("The admin")
+        .etag()
+        .add();
+    s.operation("put", "/admin/api/admins/{id}", "admins", "Change an admin's role or permissions (superadmins only)")
+        .bearer()
+        .if_match()
+        .body::<UpdateAdminRequest>()
+        .json::<ApiResponse<Admin>>("The updated admin")
+        .etag()
+        .add();
+
+    // Admin: system
+    s.operation("get", "/admin/api/system/status", "system", "Whether storage answers")
+        .bearer()
+        .json::<ApiResponse<String>>("Operational")
+        .add();
+    s.operation("get", "/admin/api/system/health", "system", "Dependency report")
+        .bearer()
+        .json::<ApiResponse<SystemHealth>>("Healthy")
+        .add();
+    s.operation("get", "/admin/api/system/cache", "system", "Cache statistics")
+        .bearer()
+        .json::<ApiResponse<Vec<CacheStats>>>("One entry per cache")
+        .add();
+    s.operation("get", "/admin/api/system/metrics", "system", "Storage metrics")
+        .bearer()
+        .text("Storage metrics in the Prometheus text format")
+        .add();
+    s.operation("get", "/admin/api/system/jobs", "system", "Background job statuses")
+        .bearer()
+        .json::<ApiResponse<Vec<JobStatus>>>("One entry per job")
+        .add();
+
+    // Admin: audit
+    s.operation("get", "/admin/api/audit", "audit", "Search the audit log")
+        .bearer()
+        .query::<AuditQuery>()
+        .json::<ApiResponse<AuditPage>>("One page of events")
+        .add();
+    s.operation("get", "/admin/api/audit/verify", "audit", "Verify the audit log's hash chain")
+        .bearer()
+        .json::<ApiResponse<ChainVerification>>("The result of the check")
+        .add();
+
+    let mut components = spec.components.into_value();
+    components["securitySchemes"] = json!({
+        "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+        "metricsToken": { "type": "http", "scheme": "bearer" },
+    });
+    json!({
+        "openapi": "3.1.0",
+        "info": { "title": "Learner API", "version": env!("CARGO_PKG_VERSION") },
+        "paths": spec.paths,
+        "components": components,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+
+    use axum::{body::Body, extract::Request, http::StatusCode, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::app::{create_app_with_config, AppConfig};
+    use crate::storage::MemoryStorage;
+    use crate::test_support;
+
+    /// Served routes that aren't part of the JSON API.
+    const NOT_IN_SPEC: &[(&str, &str)] = &[
+        ("get", "/"),
+        ("get", "/openapi.json"),
+        ("get", "/docs"),
+        ("get", "/admin/login"),
+        ("post", "/admin/login"),
+        ("post", "/admin/logout"),
+        ("get", "/admin/setup"),
+        ("post", "/admin/setup"),
+        ("get", "/admin/dashboard"),
+        ("get", "/admin/users"),
+        ("get", "/admin/metrics"),
+        ("get", "/admin/audit"),
+    ];
+
+    /// Every path registered on `app`, as `{name}` templates. Axum doesn't
+    /// list its routes, but its route table's `Debug` output names each path.
+    /// That output isn't a stable interface, so finding fewer than `floor`
+    /// paths fails rather than quietly checking less.
+    fn registered_paths(app: &Router, floor: usize) -> BTreeSet<String> {
+        let paths: BTreeSet<String> = format!("{:?}", app)
+            .split("RouteId(")
+            .skip(1)
+            .filter_map(|entry| {
+                let path = entry.split_once(": \"")?.1;
+                Some(path[..path.find('"')?].to_string())
+            })
+            .map(|path| {
+                path.split('/')
+                    .map(|s| s.strip_prefix(':').map_or(s.to_string(), |name| format!("{{{}}}", name)))
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .filter(|path| !path.contains("__private__"))
+            .collect();
+        assert!(
+            paths.len() >= floor,
+            "found {} of at least {} routes in the router's Debug output; has its format changed? {:?}",
+            paths.len(),
+            floor,
+            paths
+        );
+        paths
+    }
+
+    /// `(method, path)` of every route the app serves: each registered path,
+    /// with every method it doesn't answer with 405. `/admin/api` checks the
+    /// caller before the method, so it is asked as a superadmin. At least
+    /// `floor` paths must be found.
+    async fn served_routes(floor: usize) -> BTreeSet<(String, String)> {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = AppConfig { rate_limits: None, ..test_support::config() };
+        let bearer = format!("Bearer {}", test_support::superadmin_token(&*storage, &config).await);
+        let app = create_app_with_config(storage, config).await.main;
+
+        let mut routes = BTreeSet::new();
+        for path in registered_paths(&app, floor) {
+            let uri = path
+                .split('/')
+                .map(|s| if s.starts_with('{') { Uuid::nil().to_string() } else { s.to_string() })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in ["get", "post", "put", "delete", "patch"] {
+                let mut request = Request::builder().method(method.to_uppercase().as_str()).uri(&uri);
+                if path.starts_with("/admin/api/") {
+                    request = request.header("authorization", &bearer);
+                }
+                let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+                if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                    routes.insert((method.to_string(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    fn documented_routes(document: &Value) -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        for (path, operations) in document["paths"].as_object().unwrap() {
+            for method in operations.as_object().unwrap().keys() {
+                routes.insert((method.clone(), path.clone()));
+            }
+        }
+        routes
+    }
+
+    #[tokio::test]
+    async fn test_every_route_is_documented() {
+        let documented = documented_routes(&document());
+        let excluded: BTreeSet<_> = NOT_IN_SPEC.iter().map(|(m, p)| (m.to_string(), p.to_string())).collect();
+        // Every path the spec documents or excuses is served, so at least that
+        // many must be found.
+        let known: BTreeSet<_> = documented.iter().chain(&excluded).map(|(_, path)| path).collect();
+
+        let served = served_routes(known.len()).await;
+        assert!(served.contains(&("post".to_string(), "/api/v1/auth/login".to_string())), "{:?}", served);
+
+        let missing: Vec<_> = served.difference(&documented).filter(|r| !excluded.contains(*r)).collect();
+        assert!(missing.is_empty(), "routes missing from the OpenAPI spec: {:?}", missing);
+        let stale: Vec<_> = documented.difference(&served).collect();
+        assert!(stale.is_empty(), "documented routes that aren't served: {:?}", stale);
+    }
+
+    #[test]
+    fn test_every_reference_resolves() {
+        const PREFIX: &str = "\"#/components/schemas/";
+        let document = document();
+        let text = document.to_string();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        for (i, _) in text.match_indices(PREFIX) {
+            let rest = &text[i + PREFIX.len()..];
+            let name = &rest[..rest.find('"').unwrap()];
+            assert!(schemas.get(name).is_some_and(|s| !s.is_null()), "unresolved $ref to {}", name);
+        }
+    }
+}
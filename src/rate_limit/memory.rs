@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{take, Decision, Quota, RateLimitStore};
+use crate::storage::DbError;
+
+/// Keeps buckets in process memory, so each instance limits on its own.
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    /// When each bucket is next full.
+    buckets: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn acquire(&self, key: &str, quota: Quota, now: DateTime<Utc>) -> Result<Decision, DbError> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let (full_at, decision) = take(buckets.get(key).copied(), quota, now);
+        buckets.insert(key.to_string(), full_at);
+        Ok(decision)
+    }
+
+    async fn prune(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let before = buckets.len();
+        buckets.retain(|_, full_at| *full_at > now);
+        Ok((before - buckets.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_buckets_are_per_key_and_pruned_once_full() {
+        let store = MemoryRateLimitStore::new();
+        let quota = Quota::per_minute(1);
+        let now = Utc::now();
+
+        assert!(store.acquire("auth:ip:10.0.0.1", quota, now).await.unwrap().allowed);
+        assert!(!store.acquire("auth:ip:10.0.0.1", quota, now).await.unwrap().allowed);
+        assert!(store.acquire("auth:ip:10.0.0.2", quota, now).await.unwrap().allowed);
+
+        assert_eq!(store.prune(now).await.unwrap(), 0);
+        assert_eq!(store.prune(now + chrono::Duration::seconds(60)).await.unwrap(), 2);
+    }
+}
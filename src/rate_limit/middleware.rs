@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::Utc;
+
+use super::{Decision, RouteGroup};
+use crate::app::AppState;
+use crate::auth::middleware as auth_middleware;
+use crate::routing::client_ip::ClientIp;
+use crate::routing::error::ApiError;
+
+/// Whole seconds, rounded up so a client waiting that long is never early.
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+/// `RateLimit-*` headers as in the IETF draft, plus `Retry-After` when
+/// refused.
+fn set_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let quota = decision.quota;
+    let mut set = |name: &'static str, value: String| {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    };
+    set("ratelimit-policy", format!("{};w={}", quota.capacity, quota.period.as_secs()));
+    set("ratelimit-limit", quota.capacity.to_string());
+    set("ratelimit-remaining", decision.remaining.to_string());
+    set("ratelimit-reset", seconds(decision.reset).to_string());
+    if let Some(retry_after) = decision.retry_after {
+        set("retry-after", seconds(retry_after).to_string());
+    }
+}
+
+/// Takes a request from the caller's bucket for `group`, refusing with 429
+/// once it is empty. If the store fails, sign-in routes answer 503 so they
+/// can't be brute-forced meanwhile; other routes let the request through.
+async fn enforce(group: RouteGroup, state: AppState, request: Request, next: Next) -> Response {
+    let Some(limiter) = state.rate_limiter.clone() else {
+        return next.run(request).await;
+    };
+
+    let claims = auth_middleware::caller(&state, &request);
+    let key = match &claims {
+        Some(claims) => format!("{}:user:{}", group.as_str(), claims.sub),
+        None => {
+            let ClientIp(ip) = ClientIp::resolve(&state, request.headers(), request.extensions());
+            let ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+            format!("{}:ip:{}", group.as_str(), ip)
+        }
+    };
+    let level = claims.as_ref().map(|claims| &claims.account_level);
+    let quota = limiter.limits.group(group).quota(level);
+
+    let decision = match limiter.store.acquire(&key, quota, Utc::now()).await {
+        Ok(decision) => decision,
+        Err(e) if group == RouteGroup::Auth => {
+            tracing::error!(group = group.as_str(), error = %e, "rate limit store failed; refusing request");
+            return ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "RATE_LIMIT_UNAVAILABLE",
+                "Sign-in is temporarily unavailable; retry later",
+            )
+            .into_response();
+        }
+        Err(e) => {
+            tracing::warn!(group = group.as_str(), error = %e, "rate limit store failed; allowing request");
+            return next.run(request).await;
+        }
+    };
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        let level = level.map_or("anonymous", |level| level.display_name());
+        state.metrics.record_rate_limited(group.as_str(), level);
+        ApiError::new(StatusCode::TOO_MANY_REQUESTS, "RATE_LIMITED", "Too many requests; retry later")
+            .into_response()
+    };
+    set_headers(response.headers_mut(), &decision);
+    response
+}
+
+/// Limits every route in `router` as part of `group`.
+pub fn limited(router: Router<AppState>, state: &AppState, group: RouteGroup) -> Router<AppState> {
+    router.layer(middleware::from_fn_with_state(
+        state.clone(),
+        move |State(state): State<AppState>, request: Request, next: Next| enforce(group, state, request, next),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use axum::body::Body;
+    use chrono::DateTime;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::app::{create_app_with_config, AppConfig};
+    use crate::rate_limit::{Quota, RateLimitStore, RateLimits};
+    use crate::storage::{DbError, MemoryStorage};
+    use crate::test_support;
+
+    struct FailingStore;
+
+    #[async_trait]
+    impl RateLimitStore for FailingStore {
+        async fn acquire(&self, _key: &str, _quota: Quota, _now: DateTime<Utc>) -> Result<Decision, DbError> {
+            Err(DbError::Connection("unavailable".to_string()))
+        }
+
+        async fn prune(&self, _now: DateTime<Utc>) -> Result<u64, DbError> {
+            Err(DbError::Connection("unavailable".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failing_store_closes_sign_in_and_opens_the_rest() {
+        let config = AppConfig {
+            rate_limits: Some(RateLimits::default()),
+            rate_limit_store: Arc::new(FailingStore),
+            ..test_support::config()
+        };
+        let app = create_app_with_config(Arc::new(MemoryStorage::new()), config).await.main;
+        let status = |method: &str, uri: &str| {
+            let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(status("POST", "/api/v1/auth/login").await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status("POST", "/admin/login").await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status("GET", "/admin/api/users").await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_headers_round_up_and_add_retry_after_when_refused() {
+        let decision = Decision {
+            allowed: false,
+            quota: Quota::per_minute(30),
+            remaining: 0,
+            reset: Duration::from_millis(59_500),
+            retry_after: Some(Duration::from_millis(1_500)),
+        };
+        let mut headers = HeaderMap::new();
+        set_headers(&mut headers, &decision);
+
+        assert_eq!(headers["ratelimit-policy"], "30;w=60");
+        assert_eq!(headers["ratelimit-remaining"], "0");
+        assert_eq!(headers["ratelimit-reset"], "60");
+        assert_eq!(headers["retry-after"], "2");
+    }
+}
@@ -0,0 +1,224 @@
+//! Token-bucket rate limiting. Each caller has a bucket per route group,
+//! keyed by user id when the request carries a valid access token and by
+//! client IP otherwise (see `routing::client_ip`). Bucket sizes come from the
+//! caller's `AccountLevel`; see `RateLimits`. The service issues no API keys,
+//! so there is no per-key bucket; a key would be limited as its user.
+//!
+//! A bucket is stored as the time it will next be full, so a bucket that has
+//! refilled is the same as one that doesn't exist and can be forgotten.
+
+pub mod memory;
+pub mod middleware;
+pub mod postgres;
+
+pub use memory::MemoryRateLimitStore;
+pub use middleware::limited;
+pub use postgres::PostgresRateLimitStore;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::auth::model::AccountLevel;
+use crate::storage::DbError;
+
+/// A bucket of `capacity` requests that refills completely over `period`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl Quota {
+    pub fn per_minute(capacity: u32) -> Self {
+        Self { capacity, period: Duration::from_secs(60) }
+    }
+
+    /// How long one request takes to refill.
+    fn interval(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.period / self.capacity.max(1)).unwrap_or(chrono::Duration::MAX)
+    }
+
+    fn period(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.period).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+/// The outcome of taking a request from a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub quota: Quota,
+    /// Requests left before the bucket is empty.
+    pub remaining: u32,
+    /// Until the bucket is full again.
+    pub reset: Duration,
+    /// Until the next request would be allowed; set when this one wasn't.
+    pub retry_after: Option<Duration>,
+}
+
+/// Takes one request from the bucket that is next full at `full_at` (`None`
+/// for a bucket never used), returning the bucket's new `full_at`.
+/// A bucket of no requests refuses every one.
+pub fn take(full_at: Option<DateTime<Utc>>, quota: Quota, now: DateTime<Utc>) -> (DateTime<Utc>, Decision) {
+    let to_std = |d: chrono::Duration| d.to_std().unwrap_or_default();
+    if quota.capacity == 0 {
+        let decision =
+            Decision { allowed: false, quota, remaining: 0, reset: Duration::ZERO, retry_after: Some(quota.period) };
+        return (full_at.unwrap_or(now), decision);
+    }
+    let (interval, period) = (quota.interval(), quota.period());
+    let full_at = full_at.map_or(now, |full_at| full_at.max(now));
+    let taken = full_at + interval;
+
+    if taken - now > period {
+        let decision = Decision {
+            allowed: false,
+            quota,
+            remaining: 0,
+            reset: to_std(full_at - now),
+            retry_after: Some(to_std(taken - now - period)),
+        };
+        return (full_at, decision);
+    }
+
+    let spare = (period - (taken - now)).num_milliseconds() / interval.num_milliseconds().max(1);
+    let decision = Decision {
+        allowed: true,
+        quota,
+        remaining: spare.clamp(0, quota.capacity as i64) as u32,
+        reset: to_std(taken - now),
+        retry_after: None,
+    };
+    (taken, decision)
+}
+
+/// Where buckets are kept. In memory each instance limits on its own;
+/// Postgres shares the buckets between instances.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Takes one request from the bucket `key`, which holds `quota`.
+    async fn acquire(&self, key: &str, quota: Quota, now: DateTime<Utc>) -> Result<Decision, DbError>;
+
+    /// Forgets buckets that are full again by `now`, returning how many.
+    async fn prune(&self, now: DateTime<Utc>) -> Result<u64, DbError>;
+}
+
+/// Routes that share a bucket. Limits are set per group because signing in
+/// is worth limiting far harder than reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    /// `/api/v1/auth`, the `/auth` aliases and the admin sign-in form. Refused
+    /// while the store is failing, rather than left unlimited.
+    Auth,
+    /// `/admin/api`.
+    Admin,
+}
+
+impl RouteGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Auth => "auth",
+            RouteGroup::Admin => "admin",
+        }
+    }
+}
+
+/// Requests per minute for one route group, by who is calling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupLimits {
+    /// Callers without a valid access token, limited by IP.
+    pub anonymous: u32,
+    pub free: u32,
+    pub premium: u32,
+    pub enterprise: u32,
+}
+
+impl GroupLimits {
+    pub fn quota(&self, level: Option<&AccountLevel>) -> Quota {
+        Quota::per_minute(match level {
+            None => self.anonymous,
+            Some(AccountLevel::Free) => self.free,
+            Some(AccountLevel::Premium) => self.premium,
+            Some(AccountLevel::Enterprise) => self.enterprise,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    pub auth: GroupLimits,
+    pub admin: GroupLimits,
+}
+
+impl RateLimits {
+    pub fn group(&self, group: RouteGroup) -> &GroupLimits {
+        match group {
+            RouteGroup::Auth => &self.auth,
+            RouteGroup::Admin => &self.admin,
+        }
+    }
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            auth: GroupLimits { anonymous: 20, free: 30, premium: 60, enterprise: 120 },
+            admin: GroupLimits { anonymous: 30, free: 300, premium: 600, enterprise: 1200 },
+        }
+    }
+}
+
+/// Limits and the store that enforces them.
+pub struct RateLimiter {
+    pub limits: RateLimits,
+    pub store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits, store: Arc<dyn RateLimitStore>) -> Self {
+        Self { limits, store }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let quota = Quota::per_minute(3);
+        let start = Utc::now();
+
+        let mut full_at = None;
+        for remaining in [2, 1, 0] {
+            let (next, decision) = take(full_at, quota, start);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+            full_at = Some(next);
+        }
+
+        let (_, refused) = take(full_at, quota, start);
+        assert!(!refused.allowed);
+        assert_eq!(refused.retry_after, Some(Duration::from_secs(20)));
+        assert_eq!(refused.reset, Duration::from_secs(60));
+
+        // One request refills every 20 seconds.
+        let (_, decision) = take(full_at, quota, start + chrono::Duration::seconds(20));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+
+        // A bucket past its full time behaves like a new one.
+        let (_, decision) = take(full_at, quota, start + chrono::Duration::seconds(90));
+        assert_eq!(decision.remaining, 2);
+    }
+
+    #[test]
+    fn test_empty_quota_refuses_everything() {
+        let (_, decision) = take(None, Quota::per_minute(0), Utc::now());
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Some(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::{take, Decision, Quota, RateLimitStore};
+use crate::storage::DbError;
+
+/// Keeps buckets in the `rate_limit_buckets` table so every instance draws
+/// from the same ones. Each take locks its bucket's row.
+pub struct PostgresRateLimitStore {
+    pool: PgPool,
+}
+
+impl PostgresRateLimitStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresRateLimitStore {
+    async fn acquire(&self, key: &str, quota: Quota, now: DateTime<Utc>) -> Result<Decision, DbError> {
+        let mut tx = self.pool.begin().await?;
+        // A new bucket starts full, so its row can be created as of `now`
+        // and then locked like any other.
+        sqlx::query("INSERT INTO rate_limit_buckets (key, full_at) VALUES ($1, $2) ON CONFLICT (key) DO NOTHING")
+            .bind(key)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        let (full_at,): (DateTime<Utc>,) =
+            sqlx::query_as("SELECT full_at FROM rate_limit_buckets WHERE key = $1 FOR UPDATE")
+                .bind(key)
+                .fetch_one(&mut *tx)
+                .await?;
+
+        let (full_at, decision) = take(Some(full_at), quota, now);
+        sqlx::query("UPDATE rate_limit_buckets SET full_at = $2 WHERE key = $1")
+            .bind(key)
+            .bind(full_at)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(decision)
+    }
+
+    async fn prune(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM rate_limit_buckets WHERE full_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,35 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+
+use crate::app::AppState;
+use crate::auth::history as auth_history;
+use crate::auth::middleware::require_auth;
+use crate::auth::new as auth_new;
+use crate::auth::out as auth_out;
+use crate::auth::r#in as auth_in;
+use crate::idempotency;
+use crate::rate_limit::{self, RouteGroup};
+
+/// Sign-up, sign-in and sessions. Mounted by `versions`, under each API
+/// version and at the legacy `/auth`; every mount shares the same rate limit
+/// buckets and idempotency keys.
+pub fn router(state: &AppState) -> Router<AppState> {
+    // Their responses carry tokens, which mustn't be kept for replay.
+    let sign_in = Router::new()
+        .route("/login", post(auth_in::user_login))
+        .route("/admin/login", post(auth_in::admin_login));
+    let routes = Router::new()
+        .route("/register", post(auth_new::register))
+        .route("/logout", post(auth_out::logout))
+        .route("/logout-all", post(auth_out::logout_all))
+        .route(
+            "/login-history",
+            get(auth_history::login_history)
+                .route_layer(middleware::from_fn_with_state(state.clone(), require_auth)),
+        );
+    let routes = idempotency::idempotent(routes, state).merge(sign_in);
+    rate_limit::limited(routes, state, RouteGroup::Auth)
+}
@@ -0,0 +1,39 @@
+//! The caller's address, for login history, audit events and rate limits.
+
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, Extensions, HeaderMap},
+};
+
+use crate::app::AppState;
+use crate::utils;
+
+/// Resolved by `utils::client_ip`, trusting forwarding headers only from the
+/// configured proxies. `None` when the server wasn't given peer addresses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl ClientIp {
+    pub fn resolve(state: &AppState, headers: &HeaderMap, extensions: &Extensions) -> Self {
+        let peer = extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+        Self(utils::client_ip(headers, peer, &state.trusted_proxies))
+    }
+
+    /// As stored in `ip_address` columns.
+    pub fn to_column(self) -> Option<String> {
+        self.0.map(|ip| ip.to_string())
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Infallible> {
+        Ok(Self::resolve(state, &parts.headers, &parts.extensions))
+    }
+}
@@ -0,0 +1,233 @@
+//! The one error type every JSON handler returns. Errors are rendered as RFC
+//! 7807 `application/problem+json`, extended with a stable `code`, optional
+//! per-field `errors` and the `request_id`.
+
+use std::fmt;
+
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Request,
+    },
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use super::request_id::current_request_id;
+use crate::storage::DbError;
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// Stable, `SCREAMING_SNAKE_CASE` identifier clients can match on.
+    pub code: &'static str,
+    /// Safe to show to the client; never carries internal details.
+    pub message: String,
+    pub fields: Vec<FieldError>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self { status, code, message: message.into(), fields: Vec::new() }
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn unauthorized(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, code, message)
+    }
+
+    pub fn forbidden(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, code, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "NOT_FOUND", message)
+    }
+
+    pub fn internal() -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", "Internal server error")
+    }
+
+    pub fn not_implemented() -> Self {
+        Self::new(StatusCode::NOT_IMPLEMENTED, "NOT_IMPLEMENTED", "Not implemented")
+    }
+
+    /// Blames `field` of the request for the error.
+    pub fn field(mut self, field: impl Into<String>, message: impl Into<String>) -> Self {
+        self.fields.push(FieldError { field: field.into(), message: message.into() });
+        self
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.status.as_u16(), self.code, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// The problem document. `type` is `about:blank`, so `title` is the status
+/// phrase and `code` carries the specific problem.
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: &'a str,
+    code: &'static str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    errors: &'a [FieldError],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let problem = Problem {
+            kind: "about:blank",
+            title: self.status.canonical_reason().unwrap_or("Error"),
+            status: self.status.as_u16(),
+            detail: &self.message,
+            code: self.code,
+            errors: &self.fields,
+            request_id: current_request_id(),
+        };
+        (self.status, [(header::CONTENT_TYPE, PROBLEM_CONTENT_TYPE)], Json(problem)).into_response()
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(err: DbError) -> Self {
+        let error = Self::new(err.http_status(), err.error_code(), err.public_message());
+        match &err {
+            DbError::Duplicate { field, .. } => error.field(field, err.public_message()),
+            _ => error,
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), "INVALID_BODY", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "INVALID_QUERY", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "INVALID_PATH", rejection.body_text())
+    }
+}
+
+/// Runs the extractor `E` and reports its rejection as an `ApiError`, so a
+/// malformed body, query or path parses like any other error.
+pub struct WithApiError<E>(pub E);
+
+#[async_trait]
+impl<E, S> FromRequest<S> for WithApiError<E>
+where
+    E: FromRequest<S>,
+    E::Rejection: Into<ApiError>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, ApiError> {
+        E::from_request(request, state).await.map(Self).map_err(Into::into)
+    }
+}
+
+#[async_trait]
+impl<E, S> FromRequestParts<S> for WithApiError<E>
+where
+    E: FromRequestParts<S>,
+    E::Rejection: Into<ApiError>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        E::from_request_parts(parts, state).await.map(Self).map_err(Into::into)
+    }
+}
+
+/// For requests no route matched.
+pub async fn not_found() -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, "ROUTE_NOT_FOUND", "No such endpoint")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn body(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_renders_problem_json() {
+        let response = ApiError::from(DbError::duplicate("users", "email")).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_CONTENT_TYPE);
+
+        let body = body(response).await;
+        assert_eq!(body["type"], "about:blank");
+        assert_eq!(body["title"], "Conflict");
+        assert_eq!(body["status"], 409);
+        assert_eq!(body["code"], "EMAIL_EXISTS");
+        assert_eq!(body["detail"], "Email already registered");
+        assert_eq!(body["errors"][0]["field"], "email");
+    }
+
+    #[tokio::test]
+    async fn test_extractor_rejections_become_problems() {
+        #[derive(Deserialize)]
+        struct Payload {
+            #[allow(dead_code)]
+            name: String,
+        }
+
+        let app = Router::new().route(
+            "/",
+            post(|WithApiError(Json(_)): WithApiError<Json<Payload>>| async { StatusCode::OK }),
+        );
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"nmae":"x"}"#))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = body(response).await;
+        assert_eq!(body["code"], "INVALID_BODY");
+        assert!(body.get("errors").is_none());
+    }
+}
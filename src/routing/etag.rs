@@ -0,0 +1,70 @@
+//! Optimistic concurrency over HTTP. Versioned records are served with their
+//! version as a strong `ETag`, and updates must send it back in `If-Match` so
+//! the store can refuse to overwrite changes the client hasn't seen.
+
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use super::error::ApiError;
+use super::resp_structures::ApiResponse;
+
+pub fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// The version named by the request's `If-Match` header. A missing header is
+/// refused with 428, and one that can't name a version with 412.
+pub fn if_match(headers: &HeaderMap) -> Result<i64, ApiError> {
+    let value = headers.get(header::IF_MATCH).ok_or_else(|| {
+        ApiError::new(StatusCode::PRECONDITION_REQUIRED, "IF_MATCH_REQUIRED", "If-Match header required")
+    })?;
+
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| v.trim().strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::PRECONDITION_FAILED,
+                "INVALID_IF_MATCH",
+                "If-Match does not name a version of this record",
+            )
+        })
+}
+
+/// A successful response for a record at `version`.
+pub fn versioned<T: Serialize>(version: i64, data: T) -> Response {
+    (StatusCode::OK, [(header::ETAG, etag(version))], Json(ApiResponse::success(data))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(if_match: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, HeaderValue::from_static(if_match));
+        headers
+    }
+
+    #[test]
+    fn test_if_match_round_trips_etag() {
+        let tag = etag(7);
+        let mut map = HeaderMap::new();
+        map.insert(header::IF_MATCH, HeaderValue::from_str(&tag).unwrap());
+        assert_eq!(if_match(&map).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_if_match_rejects_missing_and_unusable_values() {
+        assert_eq!(if_match(&HeaderMap::new()).unwrap_err().status, StatusCode::PRECONDITION_REQUIRED);
+        for value in ["*", "W/\"7\"", "7", "\"seven\""] {
+            assert_eq!(if_match(&headers(value)).unwrap_err().status, StatusCode::PRECONDITION_FAILED, "{}", value);
+        }
+    }
+}
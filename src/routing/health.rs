@@ -0,0 +1,170 @@
+//! Health probes. Liveness only says the process answers; readiness checks
+//! the dependencies a request needs; the admin report adds timings, pool
+//! usage and process details.
+
+use std::future::Future;
+use std::time::Instant;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::resp_structures::{ApiResponse, HealthResponse, HealthStatus};
+use crate::app::AppState;
+use crate::storage::PoolStats;
+
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub name: &'static str,
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    /// What went wrong, or what was found. Only shown to admins.
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CheckSummary {
+    pub name: &'static str,
+    pub status: HealthStatus,
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub checks: Vec<CheckSummary>,
+}
+
+#[derive(Serialize)]
+pub struct SystemHealth {
+    pub status: HealthStatus,
+    pub version: &'static str,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+    pub draining: bool,
+    pub components: Vec<ComponentHealth>,
+    pub pool: Option<PoolStats>,
+}
+
+async fn timed<F>(name: &'static str, check: F) -> ComponentHealth
+where
+    F: Future<Output = (HealthStatus, Option<String>)>,
+{
+    let start = Instant::now();
+    let (status, detail) = check.await;
+    ComponentHealth { name, status, latency_ms: start.elapsed().as_millis() as u64, detail }
+}
+
+/// Storage reachable, every shipped migration applied, mail transport ready.
+pub async fn check_components(state: &AppState) -> Vec<ComponentHealth> {
+    let storage = timed("storage", async {
+        if state.storage.health_check().await {
+            (HealthStatus::Ok, None)
+        } else {
+            (HealthStatus::Unavailable, Some("health check query failed".to_string()))
+        }
+    })
+    .await;
+
+    let migrations = timed("migrations", async {
+        match state.storage.migration_status().await {
+            Ok(migrations) => match migrations.iter().filter(|m| !m.applied).count() {
+                0 => (HealthStatus::Ok, Some(format!("{} applied", migrations.len()))),
+                pending => (HealthStatus::Error, Some(format!("{} pending", pending))),
+            },
+            Err(e) => (HealthStatus::Error, Some(e.to_string())),
+        }
+    })
+    .await;
+
+    let mail = timed("mail", async {
+        let name = state.mailer.name();
+        if state.mailer.health_check().await {
+            (HealthStatus::Ok, Some(format!("transport {}", name)))
+        } else {
+            (HealthStatus::Unavailable, Some(format!("transport {} is not ready", name)))
+        }
+    })
+    .await;
+
+    vec![storage, migrations, mail]
+}
+
+/// The worst of `components`; draining outranks them all.
+fn overall(state: &AppState, components: &[ComponentHealth]) -> HealthStatus {
+    if state.lifecycle.is_draining() {
+        return HealthStatus::Draining;
+    }
+    let failed = |status: HealthStatus| components.iter().any(|c| c.status == status);
+    if failed(HealthStatus::Unavailable) {
+        HealthStatus::Unavailable
+    } else if failed(HealthStatus::Error) {
+        HealthStatus::Error
+    } else {
+        HealthStatus::Ok
+    }
+}
+
+fn status_code(status: HealthStatus) -> StatusCode {
+    match status {
+        HealthStatus::Ok => StatusCode::OK,
+        _ => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+pub async fn live() -> impl IntoResponse {
+    (StatusCode::OK, Json(HealthResponse { status: HealthStatus::Ok }))
+}
+
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let components = check_components(&state).await;
+    let status = overall(&state, &components);
+    let checks = components
+        .into_iter()
+        .map(|c| CheckSummary { name: c.name, status: c.status })
+        .collect();
+    (status_code(status), Json(ReadinessResponse { status, checks }))
+}
+
+pub async fn system_health(State(state): State<AppState>) -> impl IntoResponse {
+    let components = check_components(&state).await;
+    let status = overall(&state, &components);
+    let health = SystemHealth {
+        status,
+        version: env!("CARGO_PKG_VERSION"),
+        started_at: state.lifecycle.started_at(),
+        uptime_secs: state.lifecycle.uptime().as_secs(),
+        draining: state.lifecycle.is_draining(),
+        components,
+        pool: state.storage.pool_stats(),
+    };
+    (status_code(status), Json(ApiResponse::success(health)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::storage::{MemoryStorage, SqliteStorage};
+
+    #[tokio::test]
+    async fn test_unmigrated_database_is_not_ready() {
+        let sqlite = SqliteStorage::from_url("sqlite::memory:").await.unwrap();
+        let state = AppState::for_tests(Arc::new(sqlite));
+
+        let components = check_components(&state).await;
+        assert_eq!(components[0].status, HealthStatus::Ok);
+        assert_eq!(components[1].status, HealthStatus::Error);
+        assert_eq!(overall(&state, &components), HealthStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn test_draining_overrides_healthy_components() {
+        let state = AppState::for_tests(Arc::new(MemoryStorage::new()));
+        let components = check_components(&state).await;
+        assert_eq!(overall(&state, &components), HealthStatus::Ok);
+
+        state.lifecycle.begin_drain();
+        assert_eq!(overall(&state, &components), HealthStatus::Draining);
+    }
+}
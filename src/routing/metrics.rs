@@ -0,0 +1,159 @@
+//! `/metrics` in the Prometheus text format, and the middleware that feeds
+//! its HTTP counters. The endpoint can require a bearer token, and can be
+//! moved to its own listen address (see `MetricsConfig`).
+
+use std::fmt::Write;
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+
+use super::error::ApiError;
+use crate::app::AppState;
+use crate::auth::tokens::TokenService;
+use crate::validation::model::TokenType;
+
+pub async fn track_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    state
+        .metrics
+        .record_request(&method, &route, response.status().as_u16(), start.elapsed());
+    response
+}
+
+/// Compares in constant time so the token can't be guessed byte by byte.
+fn token_matches(expected: &str, headers: &HeaderMap) -> bool {
+    let Some(given) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(TokenService::extract_bearer_token)
+    else {
+        return false;
+    };
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn prometheus(State(state): State<AppState>) -> Response {
+    let mut body = String::new();
+    state.metrics.render_prometheus(&mut body);
+
+    body.push_str("# HELP auth_tokens_issued_total Tokens issued by type and role.\n");
+    body.push_str("# TYPE auth_tokens_issued_total counter\n");
+    let issued = [
+        ("access", "user", TokenType::Access),
+        ("access", "admin", TokenType::AdminAccess),
+        ("refresh", "user", TokenType::Refresh),
+        ("refresh", "admin", TokenType::AdminRefresh),
+    ];
+    for (kind, role, token_type) in issued {
+        let count = state.token_service.issued_count(token_type);
+        let _ = writeln!(body, "auth_tokens_issued_total{{type=\"{}\",role=\"{}\"}} {}", kind, role, count);
+    }
+
+    if let Ok(active) = state.storage.count_active_tokens(Utc::now()).await {
+        body.push_str("# HELP auth_active_sessions Refresh tokens neither revoked nor expired.\n");
+        body.push_str("# TYPE auth_active_sessions gauge\n");
+        let _ = writeln!(body, "auth_active_sessions {}", active);
+    }
+
+    if let Some(pool) = state.storage.pool_stats() {
+        body.push_str("# HELP db_pool_connections Open database connections by state.\n");
+        body.push_str("# TYPE db_pool_connections gauge\n");
+        let in_use = (pool.size as usize).saturating_sub(pool.idle);
+        let _ = writeln!(body, "db_pool_connections{{state=\"idle\"}} {}", pool.idle);
+        let _ = writeln!(body, "db_pool_connections{{state=\"in_use\"}} {}", in_use);
+        body.push_str("# HELP db_pool_max_connections Configured database pool size.\n");
+        body.push_str("# TYPE db_pool_max_connections gauge\n");
+        let _ = writeln!(body, "db_pool_max_connections {}", pool.max_connections);
+    }
+
+    state.storage_metrics.render_prometheus(&mut body);
+
+    body.push_str("# HELP process_uptime_seconds Seconds since the process started.\n");
+    body.push_str("# TYPE process_uptime_seconds gauge\n");
+    let _ = writeln!(body, "process_uptime_seconds {}", state.lifecycle.uptime().as_secs());
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+}
+
+/// With a `token`, scrapes must send it as `Authorization: Bearer <token>`.
+pub fn router(token: Option<String>) -> Router<AppState> {
+    let router = Router::new().route("/metrics", get(prometheus));
+    let Some(token) = token else {
+        return router;
+    };
+
+    router.route_layer(middleware::from_fn(move |request: Request, next: Next| {
+        let authorized = token_matches(&token, request.headers());
+        async move {
+            if authorized {
+                next.run(request).await
+            } else {
+                ApiError::unauthorized("INVALID_TOKEN", "Missing or invalid metrics token").into_response()
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::storage::{MemoryStorage, StorageLayer};
+    use crate::users::model::CreateUserRequest;
+
+    #[test]
+    fn test_token_must_match_exactly() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert!(token_matches("scrape-me", &headers("Bearer scrape-me")));
+        assert!(!token_matches("scrape-me", &headers("Bearer scrape-m")));
+        assert!(!token_matches("scrape-me", &headers("Basic scrape-me")));
+        assert!(!token_matches("scrape-me", &HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_issued_tokens_are_counted_by_type_and_role() {
+        let storage = Arc::new(MemoryStorage::new());
+        let req = CreateUserRequest {
+            email: "counted@example.com".to_string(),
+            password: String::new(),
+            username: "counted".to_string(),
+            first_name: String::new(),
+            last_name: String::new(),
+        };
+        let user = storage.create_user(&req, "hash").await.unwrap();
+        let account = storage.create_account(user.id).await.unwrap();
+        let state = AppState::for_tests(storage);
+        state.token_service.generate_user_tokens(&user, &account).unwrap();
+
+        let response = prometheus(State(state)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("auth_tokens_issued_total{type=\"access\",role=\"user\"} 1\n"));
+        assert!(body.contains("auth_tokens_issued_total{type=\"refresh\",role=\"user\"} 1\n"));
+        assert!(body.contains("auth_tokens_issued_total{type=\"access\",role=\"admin\"} 0\n"));
+        assert!(body.contains("auth_tokens_issued_total{type=\"refresh\",role=\"admin\"} 0\n"));
+    }
+}
@@ -1,3 +1,11 @@
 pub mod public_routes;
 pub mod resp_structures;
 pub mod private_routes;
+pub mod etag;
+pub mod error;
+pub mod auth_routes;
+pub mod client_ip;
+pub mod health;
+pub mod metrics;
+pub mod request_id;
+pub mod versions;
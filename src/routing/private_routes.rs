@@ -1,46 +1,345 @@
+use super::client_ip::ClientIp;
+use super::error::{ApiError, WithApiError};
+use super::etag::{if_match, versioned};
+use super::health;
 use super::resp_structures::ApiResponse;
 use axum::{
     routing::{get, post, put, delete},
     Router,
     Json,
-    extract::State,
-    response::IntoResponse,
-    http::StatusCode,
+    extract::{Extension, Path, Query, State},
+    response::{IntoResponse, Response},
+    http::{header, HeaderMap, StatusCode},
 };
 
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::admin::model::{AdminRole, UpdateAdminRequest};
 use crate::app::AppState;
+use crate::audit::{self, actions, AuditContext, AuditEntry, AuditFilter, AuditPage, ChainVerification};
+use crate::auth::history::LoginHistoryQuery;
+use crate::auth::middleware::AuthenticatedUser;
+use crate::auth::sessions;
+use crate::auth::model::{AccountLevel, AccountStatus, LoginEvent, UpdateAccountRequest};
+use crate::users::model::{
+    PageRequest, SortDirection, UpdateUserRequest, UserFilter, UserLoginResponse, UserProfile, UserSort,
+    UserSortField,
+};
 
 
 // User
-async fn list_users(State(_app_state): State<AppState>) -> impl IntoResponse {
-    (StatusCode::NOT_IMPLEMENTED, Json(ApiResponse::<()>::not_implemented()))
+#[derive(Debug, Deserialize)]
+pub struct ListUsersQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub status: Option<AccountStatus>,
+    pub account_level: Option<AccountLevel>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub search: Option<String>,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub sort: UserSortField,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+async fn list_users(
+    State(app_state): State<AppState>,
+    WithApiError(Query(query)): WithApiError<Query<ListUsersQuery>>,
+) -> Result<Json<ApiResponse<UserLoginResponse>>, ApiError> {
+    let page = PageRequest::new(query.page, query.per_page);
+    let sort = UserSort { field: query.sort, direction: query.direction };
+    let filter = UserFilter {
+        status: query.status,
+        account_level: query.account_level,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        search: query.search,
+        deleted: query.deleted,
+    };
+
+    let result = app_state.storage.list_users(&filter, sort, page).await?;
+    Ok(Json(ApiResponse::success(UserLoginResponse::from_page(result, page))))
+}
+
+fn audit_context(
+    headers: &HeaderMap,
+    client_ip: ClientIp,
+    caller: &AuthenticatedUser,
+) -> AuditContext {
+    AuditContext::from_request(headers, client_ip, Some(caller.claims.sub))
+}
+
+async fn get_user(
+    State(app_state): State<AppState>,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+) -> Result<Response, ApiError> {
+    let user = app_state
+        .storage
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    Ok(versioned(user.version, UserProfile::from(user)))
+}
+
+async fn create_user(State(_app_state): State<AppState>) -> ApiError {
+    ApiError::not_implemented()
+}
+
+/// Applies the changes if the user is still at the version named in
+/// `If-Match`, so a concurrent edit isn't silently overwritten. Deactivating
+/// the user ends their sessions.
+async fn update_user(
+    State(app_state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    client_ip: ClientIp,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+    headers: HeaderMap,
+    WithApiError(Json(changes)): WithApiError<Json<UpdateUserRequest>>,
+) -> Result<Response, ApiError> {
+    let version = if_match(&headers)?;
+    if changes.email.as_deref().is_some_and(|e| !e.contains('@') || !e.contains('.')) {
+        return Err(ApiError::bad_request("INVALID_EMAIL", "Invalid email format").field("email", "Invalid email format"));
+    }
+    let before = app_state
+        .storage
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+
+    let user = app_state.storage.update_user(user_id, &changes, version).await?;
+    let entry = AuditEntry::new(actions::USER_UPDATED, "user", user_id)
+        .context(&audit_context(&headers, client_ip, &admin))
+        .changes(&before, &user);
+    audit::record(&*app_state.audit, entry).await;
+
+    if before.is_active && !user.is_active {
+        sessions::end_all(&*app_state.storage, Some(&app_state.validation), user_id).await?;
+    }
+    Ok(versioned(user.version, UserProfile::from(user)))
+}
+
+/// Soft-deletes the user and ends their sessions. The row is kept until the
+/// retention job purges it, and can be brought back with `restore_user`.
+async fn delete_user(
+    State(app_state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+) -> Result<Json<ApiResponse<Uuid>>, ApiError> {
+    if admin.claims.sub == user_id {
+        return Err(ApiError::bad_request("CANNOT_DELETE_SELF", "Cannot delete your own account"));
+    }
+
+    sessions::end_all(&*app_state.storage, Some(&app_state.validation), user_id).await?;
+    app_state.storage.soft_delete_user(user_id).await?;
+
+    let entry = AuditEntry::new(actions::USER_DELETED, "user", user_id)
+        .context(&audit_context(&headers, client_ip, &admin));
+    audit::record(&*app_state.audit, entry).await;
+
+    Ok(Json(ApiResponse::success(user_id)))
+}
+
+async fn restore_user(
+    State(app_state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+) -> Result<Json<ApiResponse<UserProfile>>, ApiError> {
+    let user = app_state.storage.restore_user(user_id).await?;
+    let entry = AuditEntry::new(actions::USER_RESTORED, "user", user_id)
+        .context(&audit_context(&headers, client_ip, &admin));
+    audit::record(&*app_state.audit, entry).await;
+    Ok(Json(ApiResponse::success(UserProfile::from(user))))
+}
+
+async fn get_user_account(
+    State(app_state): State<AppState>,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+) -> Result<Response, ApiError> {
+    let account = app_state
+        .storage
+        .get_account_by_user_id(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Account not found"))?;
+    Ok(versioned(account.version, account))
+}
+
+/// Like `update_user`. A new level without explicit capabilities brings the
+/// level's default capabilities with it, and an account that is no longer
+/// active loses its sessions.
+async fn update_user_account(
+    State(app_state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    client_ip: ClientIp,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+    headers: HeaderMap,
+    WithApiError(Json(mut changes)): WithApiError<Json<UpdateAccountRequest>>,
+) -> Result<Response, ApiError> {
+    let version = if_match(&headers)?;
+
+    if changes.capabilities.is_none() {
+        changes.capabilities = changes.account_level.as_ref().map(AccountLevel::default_capabilities);
+    }
+    changes.changed_by = Some(admin.claims.sub);
+    let before = app_state
+        .storage
+        .get_account_by_user_id(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Account not found"))?;
+
+    let ctx = audit_context(&headers, client_ip, &admin);
+    let account = sessions::update_account(
+        &*app_state.storage,
+        &*app_state.audit,
+        Some(&app_state.validation),
+        &before,
+        &changes,
+        version,
+        &ctx,
+    )
+    .await?;
+    Ok(versioned(account.version, account))
+}
+
+async fn user_login_history(
+    State(app_state): State<AppState>,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+    WithApiError(Query(query)): WithApiError<Query<LoginHistoryQuery>>,
+) -> Result<Json<ApiResponse<Vec<LoginEvent>>>, ApiError> {
+    if app_state.storage.get_user_by_id(user_id).await?.is_none() {
+        return Err(ApiError::not_found("User not found"));
+    }
+
+    let events = app_state.storage.get_login_events(user_id, query.limit()).await?;
+    Ok(Json(ApiResponse::success(events)))
+}
+
+
+// Admins
+async fn get_admin(
+    State(app_state): State<AppState>,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+) -> Result<Response, ApiError> {
+    let admin = app_state
+        .storage
+        .get_admin_by_user_id(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Admin not found"))?;
+    Ok(versioned(admin.version, admin))
 }
 
-async fn get_user(State(_app_state): State<AppState>) -> impl IntoResponse {
-    (StatusCode::NOT_IMPLEMENTED, Json(ApiResponse::<()>::not_implemented()))
+/// Changes an admin's role or permissions. Only superadmins may do this, and
+/// the last superadmin can't be demoted, or nobody could manage admins and
+/// the next start would reopen `/admin/setup`.
+async fn update_admin(
+    State(app_state): State<AppState>,
+    Extension(caller): Extension<AuthenticatedUser>,
+    client_ip: ClientIp,
+    WithApiError(Path(user_id)): WithApiError<Path<Uuid>>,
+    headers: HeaderMap,
+    WithApiError(Json(changes)): WithApiError<Json<UpdateAdminRequest>>,
+) -> Result<Response, ApiError> {
+    if !matches!(caller.claims.admin_role, Some(AdminRole::SuperAdmin)) {
+        return Err(ApiError::forbidden("NOT_SUPERADMIN", "Superadmin access required"));
+    }
+    let version = if_match(&headers)?;
+
+    let before = app_state
+        .storage
+        .get_admin_by_user_id(user_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Admin not found"))?;
+
+    let demotes = changes.role.as_ref().is_some_and(|role| *role != AdminRole::SuperAdmin);
+    let admin = app_state
+        .storage
+        .transaction(|tx| {
+            Box::pin(async move {
+                if demotes && tx.superadmin_ids().await? == [user_id] {
+                    return Ok(None);
+                }
+                tx.update_admin(user_id, &changes, version).await.map(Some)
+            })
+        })
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::CONFLICT, "LAST_SUPERADMIN", "The last superadmin can't be demoted"))?;
+
+    let entry = AuditEntry::new(actions::ADMIN_UPDATED, "admin", user_id)
+        .context(&audit_context(&headers, client_ip, &caller))
+        .changes(&before, &admin);
+    audit::record(&*app_state.audit, entry).await;
+    Ok(versioned(admin.version, admin))
 }
 
-async fn create_user(State(_app_state): State<AppState>) -> impl IntoResponse {
-    (StatusCode::NOT_IMPLEMENTED, Json(ApiResponse::<()>::not_implemented()))
+
+// Audit
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub actor_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
 }
 
-async fn update_user(State(_app_state): State<AppState>) -> impl IntoResponse {
-    (StatusCode::NOT_IMPLEMENTED, Json(ApiResponse::<()>::not_implemented()))
+async fn list_audit_events(
+    State(app_state): State<AppState>,
+    WithApiError(Query(query)): WithApiError<Query<AuditQuery>>,
+) -> Result<Json<ApiResponse<AuditPage>>, ApiError> {
+    let page = PageRequest::new(query.page, query.per_page);
+    let filter = AuditFilter {
+        actor_id: query.actor_id,
+        action: query.action,
+        target_type: query.target_type,
+        target_id: query.target_id,
+        since: query.since,
+        until: query.until,
+    };
+
+    let result = app_state.audit.list(&filter, page).await?;
+    Ok(Json(ApiResponse::success(result)))
 }
 
-async fn delete_user(State(_app_state): State<AppState>) -> impl IntoResponse {
-    (StatusCode::NOT_IMPLEMENTED, Json(ApiResponse::<()>::not_implemented()))   
+/// Re-checks the whole hash chain; `broken_at` names the first event that was
+/// tampered with.
+async fn verify_audit_log(State(app_state): State<AppState>) -> Result<Json<ApiResponse<ChainVerification>>, ApiError> {
+    let result = app_state.audit.verify().await?;
+    Ok(Json(ApiResponse::success(result)))
 }
 
 
 // System Status
-async fn system_status(State(app_stae): State<AppState>) -> impl IntoResponse {
-    let healthy = app_stae.storage.health_check().await;
-    if healthy {
-        (StatusCode::OK, Json(ApiResponse::success("System operational")))
-    } else {
-        (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::error("System unavailable")))
+async fn system_status(State(app_stae): State<AppState>) -> Result<Json<ApiResponse<&'static str>>, ApiError> {
+    if !app_stae.storage.health_check().await {
+        return Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "SERVICE_UNAVAILABLE", "System unavailable"));
     }
+    Ok(Json(ApiResponse::success("System operational")))
+}
+
+/// Storage metrics in the Prometheus text format.
+async fn storage_metrics(State(app_state): State<AppState>) -> impl IntoResponse {
+    let mut body = String::new();
+    app_state.storage_metrics.render_prometheus(&mut body);
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+async fn cache_stats(State(app_state): State<AppState>) -> impl IntoResponse {
+    (StatusCode::OK, Json(ApiResponse::success(app_state.storage.cache_stats())))
+}
+
+async fn job_statuses(State(app_state): State<AppState>) -> impl IntoResponse {
+    (StatusCode::OK, Json(ApiResponse::success(app_state.scheduler.statuses())))
 }
 
 
@@ -53,7 +352,62 @@ pub fn router() -> Router<AppState> {
         .route("/users", post(create_user))
         .route("/users/:id", put(update_user))
         .route("/users/:id", delete(delete_user))
+        .route("/users/:id/restore", post(restore_user))
+        .route("/users/:id/account", get(get_user_account).put(update_user_account))
+        .route("/users/:id/login-history", get(user_login_history))
+
+        // Admin management
+        .route("/admins/:id", get(get_admin).put(update_admin))
 
         // System status
         .route("/system/status", get(system_status))
+        .route("/system/health", get(health::system_health))
+        .route("/system/cache", get(cache_stats))
+        .route("/system/metrics", get(storage_metrics))
+        .route("/system/jobs", get(job_statuses))
+        .route("/audit", get(list_audit_events))
+        .route("/audit/verify", get(verify_audit_log))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::extract::Request;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::app::create_app_with_config;
+    use crate::routing::etag::etag;
+    use crate::storage::{seed_admin, MemoryStorage, StorageLayer};
+    use crate::test_support;
+
+    async fn set_role(app: &Router, token: &str, user_id: Uuid, version: i64, role: &str) -> StatusCode {
+        let request = Request::put(format!("/admin/api/admins/{}", user_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::IF_MATCH, etag(version))
+            .body(Body::from(json!({ "role": role }).to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_last_superadmin_cant_be_demoted() {
+        let storage = Arc::new(MemoryStorage::new());
+        let config = test_support::config();
+        let token = test_support::superadmin_token(&*storage, &config).await;
+        let app = create_app_with_config(storage.clone(), config).await.main;
+        let root = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+
+        assert_eq!(set_role(&app, &token, root.id, 1, "Admin").await, StatusCode::CONFLICT);
+        assert!(storage.has_superadmin().await.unwrap());
+
+        seed_admin(&*storage, "second@example.com", "second", "hash").await.unwrap();
+        assert_eq!(set_role(&app, &token, root.id, 1, "Admin").await, StatusCode::OK);
+        let admin = storage.get_admin_by_user_id(root.id).await.unwrap().unwrap();
+        assert_eq!(admin.role, AdminRole::Admin);
+    }
 }
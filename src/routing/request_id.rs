@@ -0,0 +1,145 @@
+//! Request ids and the per-request span. Every request gets an id, taken from
+//! `X-Request-Id` when the caller sent a usable one, echoed in the response
+//! and in `ApiError` problems.
+
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::{field, Instrument, Span};
+use uuid::Uuid;
+
+use crate::logging::redact_query;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request being handled, if called while handling one.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Inserted into request extensions for handlers that want the id.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Records the authenticated user on the current request span.
+pub fn record_user(user_id: Uuid) {
+    Span::current().record("user_id", field::display(user_id));
+}
+
+/// Ids from the caller are kept only if they are short printable ASCII, so
+/// they can't forge log lines or bloat them.
+fn accept(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+pub async fn trace_requests(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| accept(v))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let query = request.uri().query().map(redact_query);
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        route = %route,
+        query = field::Empty,
+        user_id = field::Empty,
+        status = field::Empty,
+        latency_ms = field::Empty,
+    );
+    if let Some(query) = &query {
+        span.record("query", query.as_str());
+    }
+
+    request.extensions_mut().insert(RequestId(id.clone()));
+    let start = Instant::now();
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(request))
+        .instrument(span.clone())
+        .await;
+
+    let status = response.status();
+    span.record("status", status.as_u16());
+    span.record("latency_ms", start.elapsed().as_millis() as u64);
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::error!("request failed");
+        } else {
+            tracing::info!("request completed");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::routing::error::ApiError;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/fail",
+                get(|| async { ApiError::bad_request("BAD_INPUT", "Bad input") }),
+            )
+            .layer(middleware::from_fn(trace_requests))
+    }
+
+    async fn call(header: Option<&str>) -> (Option<String>, serde_json::Value) {
+        let mut request = Request::builder().uri("/fail");
+        if let Some(id) = header {
+            request = request.header(REQUEST_ID_HEADER, id);
+        }
+        let response = app().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let echoed = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (echoed, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_echoed_in_header_and_error() {
+        let (echoed, body) = call(Some("abc-123")).await;
+        assert_eq!(echoed.as_deref(), Some("abc-123"));
+        assert_eq!(body["request_id"], "abc-123");
+    }
+
+    #[tokio::test]
+    async fn test_unusable_request_id_is_replaced() {
+        let (echoed, body) = call(Some("has spaces")).await;
+        let echoed = echoed.unwrap();
+        assert!(Uuid::parse_str(&echoed).is_ok());
+        assert_eq!(body["request_id"], echoed.as_str());
+    }
+}
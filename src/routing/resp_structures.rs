@@ -5,40 +5,23 @@ pub struct HealthResponse {
     pub status: HealthStatus,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum HealthStatus {
     Ok,
+    /// Reachable, but something is wrong, such as pending migrations.
     Error,
     Unavailable,
+    /// Shutting down; finishing in-flight requests but taking no new ones.
+    Draining,
 }
 
 
-#[derive(Serialize)]
-pub struct InvalidResponse {
-    pub error: &'static str,
-}
-
-
-#[derive(Serialize)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-pub enum ResponseCode {
-    Ok,
-    NotFound,
-    NotImplemented,
-    BadRequest,
-    Unauthorized,
-    Forbidden,
-    InternalError,
-    ServiceUnavailable,
-}
-
-// Helpers
+/// Envelope for successful responses. Errors are `ApiError` problems.
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
-    pub  success: bool,
+    pub success: bool,
     pub data: Option<T>,
-    pub error: Option<&'static str>,
 }
 
 impl<T> ApiResponse<T> {
@@ -46,23 +29,6 @@ impl<T> ApiResponse<T> {
         Self {
             success: true,
             data: Some(data),
-            error: None,
-        }
-    }
-
-    pub fn error(error_msg: &'static str) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(error_msg),
-        }
-    }
-
-    pub fn not_implemented() -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some("Not implemented"),
         }
     }
 }
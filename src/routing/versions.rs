@@ -0,0 +1,215 @@
+//! API versions. Each version is mounted at `/api/<version>`; a client may
+//! also name the version it expects in `Accept-Version`, and is refused
+//! rather than silently served another one. Responses carry `API-Version`,
+//! and deprecated versions add `Deprecation`, `Sunset` and a `Link` to their
+//! successor.
+//!
+//! The unversioned `/auth` routes predate `/api/v1` and stay as deprecated
+//! aliases of it.
+
+use axum::{
+    extract::{OriginalUri, Request},
+    http::HeaderValue,
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+
+use super::auth_routes;
+use super::error::ApiError;
+use crate::app::AppState;
+
+pub const ACCEPT_VERSION_HEADER: &str = "accept-version";
+pub const API_VERSION_HEADER: &str = "api-version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+}
+
+/// Marks something clients should move off.
+#[derive(Debug, Clone, Copy)]
+pub struct Deprecation {
+    /// Unix time the deprecation took effect, sent as `Deprecation: @<secs>`.
+    pub since: i64,
+    /// HTTP-date after which it may stop answering, sent as `Sunset`.
+    pub sunset: Option<&'static str>,
+}
+
+/// The unversioned `/auth` aliases, deprecated since 2026-10-18.
+pub const ALIAS_DEPRECATION: Deprecation = Deprecation { since: 1_792_281_600, sunset: None };
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 1] = [ApiVersion::V1];
+    pub const CURRENT: ApiVersion = ApiVersion::V1;
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+        }
+    }
+
+    pub fn prefix(self) -> String {
+        format!("/api/{}", self.as_str())
+    }
+
+    /// Accepts `v1` as well as `1`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let value = value.strip_prefix(['v', 'V']).unwrap_or(value);
+        Self::ALL.into_iter().find(|v| v.as_str()[1..] == *value)
+    }
+
+    pub fn deprecation(self) -> Option<Deprecation> {
+        match self {
+            ApiVersion::V1 => None,
+        }
+    }
+}
+
+/// How a router is mounted: under its version's prefix, or as an old
+/// unversioned alias of that version.
+#[derive(Debug, Clone, Copy)]
+enum Mount {
+    Versioned(ApiVersion),
+    Alias(ApiVersion),
+}
+
+impl Mount {
+    fn version(self) -> ApiVersion {
+        match self {
+            Mount::Versioned(version) | Mount::Alias(version) => version,
+        }
+    }
+
+    fn deprecation(self) -> Option<Deprecation> {
+        match self {
+            Mount::Versioned(version) => version.deprecation(),
+            Mount::Alias(_) => Some(ALIAS_DEPRECATION),
+        }
+    }
+}
+
+fn supported() -> String {
+    ApiVersion::ALL.map(ApiVersion::as_str).join(", ")
+}
+
+async fn negotiate(mount: Mount, request: Request, next: Next) -> Result<Response, ApiError> {
+    let version = mount.version();
+    if let Some(requested) = request.headers().get(ACCEPT_VERSION_HEADER) {
+        let requested = requested.to_str().ok().and_then(ApiVersion::parse).ok_or_else(|| {
+            ApiError::bad_request("UNSUPPORTED_VERSION", format!("Supported versions: {}", supported()))
+        })?;
+        if requested != version {
+            let message = format!(
+                "This endpoint serves {}; {} is under {}",
+                version.as_str(),
+                requested.as_str(),
+                requested.prefix()
+            );
+            return Err(ApiError::bad_request("VERSION_MISMATCH", message));
+        }
+    }
+
+    // Where the same endpoint lives in the current version.
+    let successor = match mount {
+        Mount::Alias(_) => request.extensions().get::<OriginalUri>().map(|uri| uri.path().to_string()),
+        Mount::Versioned(_) => Some(request.uri().path().to_string()),
+    }
+    .map(|path| format!("{}{}", ApiVersion::CURRENT.prefix(), path));
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(API_VERSION_HEADER, HeaderValue::from_static(version.as_str()));
+    if let Some(deprecation) = mount.deprecation() {
+        if let Ok(value) = HeaderValue::from_str(&format!("@{}", deprecation.since)) {
+            headers.insert("deprecation", value);
+        }
+        if let Some(sunset) = deprecation.sunset {
+            headers.insert("sunset", HeaderValue::from_static(sunset));
+        }
+        let link = successor.map(|s| format!("<{}>; rel=\"successor-version\"", s));
+        if let Some(value) = link.and_then(|link| HeaderValue::from_str(&link).ok()) {
+            headers.insert("link", value);
+        }
+    }
+    Ok(response)
+}
+
+/// Everything under `/api/v1`. New resources are added here.
+fn v1(state: &AppState) -> Router<AppState> {
+    Router::new().nest("/auth", auth_routes::router(state))
+}
+
+fn mounted<S: Clone + Send + Sync + 'static>(router: Router<S>, mount: Mount) -> Router<S> {
+    router.layer(middleware::from_fn(move |request: Request, next: Next| negotiate(mount, request, next)))
+}
+
+pub fn router(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .nest("/api/v1", mounted(v1(state), Mount::Versioned(ApiVersion::V1)))
+        .nest("/auth", mounted(auth_routes::router(state), Mount::Alias(ApiVersion::V1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{body::Body, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::app::create_app_with_config;
+    use crate::storage::MemoryStorage;
+    use crate::test_support;
+
+    #[test]
+    fn test_parse_accepts_bare_and_prefixed_numbers() {
+        assert_eq!(ApiVersion::parse("v1"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::parse(" 1 "), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::parse("v2"), None);
+        assert_eq!(ApiVersion::parse("latest"), None);
+    }
+
+    fn app() -> Router {
+        let routes = Router::new().route("/ping", get(|| async { "pong" }));
+        Router::new().nest("/api/v1", mounted(routes, Mount::Versioned(ApiVersion::V1)))
+    }
+
+    async fn get_with(uri: &str, version: Option<&str>) -> Response {
+        let mut request = Request::builder().uri(uri);
+        if let Some(version) = version {
+            request = request.header(ACCEPT_VERSION_HEADER, version);
+        }
+        app().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_versioned_route_is_current_and_not_deprecated() {
+        let response = get_with("/api/v1/ping", Some("1")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[API_VERSION_HEADER], "v1");
+        assert!(response.headers().get("deprecation").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_auth_alias_is_deprecated_and_links_successor() {
+        let app = create_app_with_config(Arc::new(MemoryStorage::new()), test_support::config()).await.main;
+        let get = |uri: &'static str| app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        let alias = get("/auth/login-history").await.unwrap();
+        assert_eq!(alias.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(alias.headers()["deprecation"], "@1792281600");
+        assert_eq!(alias.headers()["link"], "</api/v1/auth/login-history>; rel=\"successor-version\"");
+
+        let successor = get("/api/v1/auth/login-history").await.unwrap();
+        assert_eq!(successor.status(), StatusCode::UNAUTHORIZED);
+        assert!(successor.headers().get("deprecation").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_accept_version_is_refused() {
+        let response = get_with("/api/v1/ping", Some("v9")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
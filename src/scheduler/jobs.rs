@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+
+use super::Job;
+use crate::app::AppState;
+use crate::storage::DbError;
+
+/// Deletes stored auth tokens past their expiry.
+pub struct PurgeExpiredTokens;
+
+#[async_trait]
+impl Job for PurgeExpiredTokens {
+    fn name(&self) -> &'static str {
+        "purge_expired_tokens"
+    }
+
+    async fn run(&self, state: &AppState) -> Result<String, DbError> {
+        let purged = state.storage.purge_expired_tokens(Utc::now()).await?;
+        Ok(format!("purged {} tokens", purged))
+    }
+}
+
+/// Deletes stored validation keys past their expiry.
+pub struct PurgeExpiredValidationKeys;
+
+#[async_trait]
+impl Job for PurgeExpiredValidationKeys {
+    fn name(&self) -> &'static str {
+        "purge_expired_validation_keys"
+    }
+
+    async fn run(&self, state: &AppState) -> Result<String, DbError> {
+        let purged = state.storage.purge_expired_validation_keys(Utc::now()).await?;
+        Ok(format!("purged {} validation keys", purged))
+    }
+}
+
+/// Permanently deletes users that have been soft-deleted for longer than
+/// `retention`.
+pub struct PurgeDeletedUsers {
+    pub retention: Duration,
+}
+
+#[async_trait]
+impl Job for PurgeDeletedUsers {
+    fn name(&self) -> &'static str {
+        "purge_deleted_users"
+    }
+
+    async fn run(&self, state: &AppState) -> Result<String, DbError> {
+        let retention = chrono::Duration::from_std(self.retention)
+            .map_err(|e| DbError::Other(format!("invalid retention: {}", e)))?;
+        let purged = state.storage.purge_deleted_users(Utc::now() - retention).await?;
+        Ok(format!("purged {} deleted users", purged))
+    }
+}
+
+/// Clears expired keys, tokens and blacklisted token ids out of this
+/// process's `ValidationStore`. Every instance has its own, so the job isn't
+/// exclusive.
+pub struct PurgeValidationStore;
+
+#[async_trait]
+impl Job for PurgeValidationStore {
+    fn name(&self) -> &'static str {
+        "purge_validation_store"
+    }
+
+    fn exclusive(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, state: &AppState) -> Result<String, DbError> {
+        let (keys, tokens) = state.validation.cleanup_expired();
+        let jtis = state.validation.cleanup_blacklist();
+        Ok(format!(
+            "purged {} keys, {} tokens and {} blacklisted token ids",
+            keys, tokens, jtis
+        ))
+    }
+}
+
+/// Forgets rate limit buckets that have refilled. Not exclusive: in-memory
+/// buckets belong to each instance, and pruning shared ones twice is harmless.
+pub struct PurgeRateLimitBuckets;
+
+#[async_trait]
+impl Job for PurgeRateLimitBuckets {
+    fn name(&self) -> &'static str {
+        "purge_rate_limit_buckets"
+    }
+
+    fn exclusive(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, state: &AppState) -> Result<String, DbError> {
+        let Some(limiter) = &state.rate_limiter else {
+            return Ok("rate limiting is disabled".to_string());
+        };
+        let purged = limiter.store.prune(Utc::now()).await?;
+        Ok(format!("purged {} rate limit buckets", purged))
+    }
+}
+
+/// Deletes idempotency keys whose replay window or lock has run out. Not
+/// exclusive, for the same reasons as `PurgeRateLimitBuckets`.
+pub struct PurgeIdempotencyKeys;
+
+#[async_trait]
+impl Job for PurgeIdempotencyKeys {
+    fn name(&self) -> &'static str {
+        "purge_idempotency_keys"
+    }
+
+    fn exclusive(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, state: &AppState) -> Result<String, DbError> {
+        let purged = state.idempotency.prune(Utc::now()).await?;
+        Ok(format!("purged {} idempotency keys", purged))
+    }
+}
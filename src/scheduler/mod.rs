@@ -0,0 +1,111 @@
+pub mod runner;
+pub mod jobs;
+
+pub use runner::{Job, Scheduler, SchedulerConfig};
+
+use std::sync::Arc;
+
+use crate::app::AppState;
+
+/// Starts the built-in maintenance jobs on `state.scheduler`. They stop when
+/// `state.lifecycle` starts draining, and shutdown waits for them.
+pub fn start_maintenance(state: &AppState, config: &SchedulerConfig) {
+    let jobs: [Arc<dyn Job>; 6] = [
+        Arc::new(jobs::PurgeExpiredTokens),
+        Arc::new(jobs::PurgeExpiredValidationKeys),
+        Arc::new(jobs::PurgeValidationStore),
+        Arc::new(jobs::PurgeDeletedUsers { retention: config.user_retention }),
+        Arc::new(jobs::PurgeRateLimitBuckets),
+        Arc::new(jobs::PurgeIdempotencyKeys),
+    ];
+    for job in jobs {
+        let handle = state.scheduler.spawn(job, config.purge_interval, state.clone());
+        state.lifecycle.track(handle);
+    }
+
+    let (scheduler, lifecycle) = (state.scheduler.clone(), state.lifecycle.clone());
+    tokio::spawn(async move {
+        lifecycle.draining().await;
+        scheduler.shutdown();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::runner::JobOutcome;
+    use crate::storage::{DbError, MemoryStorage};
+
+    struct FixedJob(Result<&'static str, &'static str>);
+
+    #[async_trait]
+    impl Job for FixedJob {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        async fn run(&self, _state: &AppState) -> Result<String, DbError> {
+            self.0.map(str::to_string).map_err(|e| DbError::Other(e.to_string()))
+        }
+    }
+
+    /// Spawns `job` with a long interval, so it runs exactly once, and waits
+    /// for that run to finish.
+    async fn run_once(job: FixedJob) -> runner::JobStatus {
+        let state = AppState::for_tests(Arc::new(MemoryStorage::new()));
+        let handle = state.scheduler.spawn(Arc::new(job), Duration::from_secs(3600), state.clone());
+
+        for _ in 0..100 {
+            let status = state.scheduler.statuses().remove(0);
+            if status.runs > 0 {
+                state.scheduler.shutdown();
+                handle.await.unwrap();
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("job never ran");
+    }
+
+    #[tokio::test]
+    async fn test_successful_run_is_recorded() {
+        let status = run_once(FixedJob(Ok("did things"))).await;
+
+        assert_eq!(status.name, "fixed");
+        assert_eq!(status.interval_secs, 3600);
+        assert_eq!(status.failures, 0);
+        assert!(!status.running);
+        assert!(status.last_finished_at.is_some());
+        assert!(matches!(status.last_outcome, Some(JobOutcome::Succeeded { ref summary }) if summary == "did things"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_run_is_counted() {
+        let status = run_once(FixedJob(Err("boom"))).await;
+
+        assert_eq!(status.runs, 1);
+        assert_eq!(status.failures, 1);
+        assert!(matches!(status.last_outcome, Some(JobOutcome::Failed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_validation_store_purge_drops_expired_blacklist_entries() {
+        let state = AppState::for_tests(Arc::new(MemoryStorage::new()));
+        let expired = Uuid::new_v4();
+        let live = Uuid::new_v4();
+        state.validation.blacklist_jti(expired, Utc::now() - chrono::Duration::minutes(1));
+        state.validation.blacklist_jti(live, Utc::now() + chrono::Duration::minutes(15));
+
+        let summary = jobs::PurgeValidationStore.run(&state).await.unwrap();
+
+        assert!(summary.contains("1 blacklisted token ids"), "{}", summary);
+        assert!(!state.validation.is_jti_blacklisted(&expired));
+        assert!(state.validation.is_jti_blacklisted(&live));
+    }
+}
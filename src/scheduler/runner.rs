@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::app::AppState;
+use crate::storage::DbError;
+
+/// A periodic background task.
+#[async_trait]
+pub trait Job: Send + Sync {
+    /// Unique name; also identifies the job's cross-instance lock.
+    fn name(&self) -> &'static str;
+
+    /// Whether only one instance may run the job at a time. Jobs that only
+    /// touch this process's state should return `false`.
+    fn exclusive(&self) -> bool {
+        true
+    }
+
+    /// Does one pass of the job, returning a short summary of what it did.
+    async fn run(&self, state: &AppState) -> Result<String, DbError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub enabled: bool,
+    /// How often the purge jobs run.
+    pub purge_interval: Duration,
+    /// How long soft-deleted users are kept before being purged for good.
+    pub user_retention: Duration,
+    /// Each run is delayed by a random fraction of the interval up to this
+    /// much, so instances started together don't run jobs in lockstep.
+    pub jitter: f64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            purge_interval: Duration::from_secs(15 * 60),
+            user_retention: Duration::from_secs(30 * 24 * 60 * 60),
+            jitter: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobOutcome {
+    Succeeded { summary: String },
+    Failed { error: String },
+    /// Another instance held the job's lock.
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: &'static str,
+    pub interval_secs: u64,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    pub skipped: u64,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_outcome: Option<JobOutcome>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+impl JobStatus {
+    fn new(name: &'static str, interval: Duration) -> Self {
+        Self {
+            name,
+            interval_secs: interval.as_secs(),
+            running: false,
+            runs: 0,
+            failures: 0,
+            skipped: 0,
+            last_started_at: None,
+            last_finished_at: None,
+            last_duration_ms: None,
+            last_outcome: None,
+            next_run_at: None,
+        }
+    }
+}
+
+/// Runs registered jobs on their own tasks and keeps their last-run status.
+pub struct Scheduler {
+    jitter: f64,
+    statuses: RwLock<BTreeMap<&'static str, JobStatus>>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Scheduler {
+    pub fn new(jitter: f64) -> Self {
+        let (shutdown, _) = watch::channel(false);
+        Self {
+            jitter: jitter.clamp(0.0, 1.0),
+            statuses: RwLock::new(BTreeMap::new()),
+            shutdown,
+        }
+    }
+
+    /// Starts running `job` every `interval` until `shutdown` is called. The
+    /// first run happens after the jitter delay alone.
+    pub fn spawn(self: &Arc<Self>, job: Arc<dyn Job>, interval: Duration, state: AppState) -> JoinHandle<()> {
+        self.update(job.name(), interval, |_| {});
+
+        let scheduler = self.clone();
+        let mut shutdown = self.shutdown.subscribe();
+        tokio::spawn(async move {
+            let mut delay = scheduler.jittered(Duration::ZERO, interval);
+            loop {
+                scheduler.update(job.name(), interval, |s| {
+                    s.next_run_at = chrono::Duration::from_std(delay).ok().map(|d| Utc::now() + d);
+                });
+
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.changed() => break,
+                }
+                if *shutdown.borrow() {
+                    break;
+                }
+
+                scheduler.run_once(job.as_ref(), interval, &state).await;
+                delay = scheduler.jittered(interval, interval);
+            }
+        })
+    }
+
+    /// Status of every registered job, ordered by name.
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.statuses.read().unwrap().values().cloned().collect()
+    }
+
+    /// Stops every job loop. A run already in progress finishes first.
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    async fn run_once(&self, job: &dyn Job, interval: Duration, state: &AppState) {
+        let name = job.name();
+
+        let lock = if job.exclusive() {
+            match state.storage.try_lock_job(name).await {
+                Ok(Some(lock)) => Some(lock),
+                Ok(None) => {
+                    self.update(name, interval, |s| {
+                        s.skipped += 1;
+                        s.last_outcome = Some(JobOutcome::Skipped);
+                    });
+                    return;
+                }
+                Err(e) => {
+                    tracing::error!(job = name, error = %e, "failed to lock job");
+                    self.update(name, interval, |s| {
+                        s.failures += 1;
+                        s.last_outcome = Some(JobOutcome::Failed { error: e.to_string() });
+                    });
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+
+        self.update(name, interval, |s| {
+            s.running = true;
+            s.last_started_at = Some(Utc::now());
+        });
+
+        let started = Instant::now();
+        let result = job.run(state).await;
+        let released = match lock {
+            Some(lock) => lock.release().await,
+            None => Ok(()),
+        };
+        if let Err(e) = released {
+            tracing::error!(job = name, error = %e, "failed to release job lock");
+        }
+
+        let elapsed = started.elapsed();
+        self.update(name, interval, |s| {
+            s.running = false;
+            s.runs += 1;
+            s.last_finished_at = Some(Utc::now());
+            s.last_duration_ms = Some(elapsed.as_millis() as u64);
+            s.last_outcome = Some(match result {
+                Ok(summary) => JobOutcome::Succeeded { summary },
+                Err(e) => {
+                    tracing::error!(job = name, error = %e, "job failed");
+                    s.failures += 1;
+                    JobOutcome::Failed { error: e.to_string() }
+                }
+            });
+        });
+    }
+
+    fn jittered(&self, base: Duration, interval: Duration) -> Duration {
+        let max = interval.mul_f64(self.jitter);
+        if max.is_zero() {
+            return base;
+        }
+        base + max.mul_f64(rand::thread_rng().gen_range(0.0..1.0))
+    }
+
+    fn update(&self, name: &'static str, interval: Duration, f: impl FnOnce(&mut JobStatus)) {
+        let mut statuses = self.statuses.write().unwrap();
+        f(statuses.entry(name).or_insert_with(|| JobStatus::new(name, interval)));
+    }
+}
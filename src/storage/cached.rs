@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{DbError, JobLock, MigrationStatus, PoolStats, StorageLayer, Transaction};
+use crate::users::model::{User, CreateUserRequest, UpdateUserRequest, UserFilter, UserSort, PageRequest, UserPage};
+use crate::auth::model::{UserAccount, CreateAccountRequest, UpdateAccountRequest, LoginEvent};
+use crate::admin::model::{Admin, CreateAdminRequest, UpdateAdminRequest};
+use crate::validation::model::AuthToken;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheConfig {
+    pub ttl: Duration,
+    pub capacity: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(30),
+            capacity: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub name: &'static str,
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+/// A TTL and size-bounded map. Absent records are cached too, so repeated
+/// lookups of a user with no account or admin row stay off the database.
+struct TtlCache<K, V> {
+    name: &'static str,
+    config: CacheConfig,
+    entries: Mutex<HashMap<K, (V, Instant)>>,
+    /// Bumped by every invalidation. A lookup only fills the cache if no
+    /// invalidation happened while it was reading from the inner store.
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Eq + Hash + Copy, V: Clone> TtlCache<K, V> {
+    fn new(name: &'static str, config: CacheConfig) -> Self {
+        Self {
+            name,
+            config,
+            entries: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    async fn get_or_load<F>(&self, key: K, load: F) -> Result<V, DbError>
+    where
+        F: std::future::Future<Output = Result<V, DbError>>,
+    {
+        if let Some(value) = self.get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let value = load.await?;
+        self.insert(key, value.clone(), generation);
+        Ok(value)
+    }
+
+    fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((value, expires_at)) if *expires_at > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: K, value: V, generation: u64) {
+        let mut entries = self.entries.lock().unwrap();
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+
+        if entries.len() >= self.config.capacity && !entries.contains_key(&key) {
+            let now = Instant::now();
+            entries.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        if entries.len() >= self.config.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (_, expires_at))| *expires_at)
+                .map(|(k, _)| *k);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key, (value, Instant::now() + self.config.ttl));
+    }
+
+    fn invalidate(&self, key: &K) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.remove(key);
+    }
+
+    fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.name,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+            capacity: self.config.capacity,
+        }
+    }
+}
+
+struct Caches {
+    users: TtlCache<Uuid, Option<User>>,
+    accounts: TtlCache<Uuid, Option<UserAccount>>,
+    admins: TtlCache<Uuid, Option<Admin>>,
+}
+
+/// Caches the per-request lookups (user by id, account, admin) in front of
+/// another store. Writes made through this layer invalidate the entries they
+/// touch; writes made directly against the database, or by another instance,
+/// become visible once the TTL expires.
+pub struct CachedStorage<S: StorageLayer> {
+    inner: S,
+    caches: Caches,
+}
+
+impl<S: StorageLayer> CachedStorage<S> {
+    pub fn new(inner: S, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            caches: Caches {
+                users: TtlCache::new("users", config),
+                accounts: TtlCache::new("accounts", config),
+                admins: TtlCache::new("admins", config),
+            },
+        }
+    }
+}
+
+/// Passes writes through to the inner transaction and invalidates the
+/// affected cache entries once it commits.
+struct CachedTransaction<'a> {
+    inner: Box<dyn Transaction + 'a>,
+    caches: &'a Caches,
+    users: Vec<Uuid>,
+    accounts: Vec<Uuid>,
+    admins: Vec<Uuid>,
+}
+
+#[async_trait]
+impl Transaction for CachedTransaction<'_> {
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<User>, DbError> {
+        self.inner.get_user_by_email(email).await
+    }
+
+    async fn create_user(&mut self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
+        let user = self.inner.create_user(req, password_hash).await?;
+        self.users.push(user.id);
+        Ok(user)
+    }
+
+    async fn create_account(&mut self, req: &CreateAccountRequest) -> Result<UserAccount, DbError> {
+        self.accounts.push(req.user_id);
+        self.inner.create_account(req).await
+    }
+
+    async fn create_admin(&mut self, req: &CreateAdminRequest) -> Result<Admin, DbError> {
+        self.admins.push(req.user_id);
+        self.inner.create_admin(req).await
+    }
+
+    async fn superadmin_ids(&mut self) -> Result<Vec<Uuid>, DbError> {
+        self.inner.superadmin_ids().await
+    }
+
+    async fn update_admin(&mut self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        self.admins.push(user_id);
+        self.inner.update_admin(user_id, changes, expected_version).await
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DbError> {
+        let CachedTransaction { inner, caches, users, accounts, admins } = *self;
+        let result = inner.commit().await;
+        for id in &users {
+            caches.users.invalidate(id);
+        }
+        for id in &accounts {
+            caches.accounts.invalidate(id);
+        }
+        for id in &admins {
+            caches.admins.invalidate(id);
+        }
+        result
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DbError> {
+        self.inner.rollback().await
+    }
+}
+
+#[async_trait]
+impl<S: StorageLayer> StorageLayer for CachedStorage<S> {
+    async fn health_check(&self) -> bool {
+        self.inner.health_check().await
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, DbError> {
+        self.inner.migration_status().await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.inner.pool_stats()
+    }
+
+    async fn close(&self) -> Result<(), DbError> {
+        self.inner.close().await
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.caches.users.stats(),
+            self.caches.accounts.stats(),
+            self.caches.admins.stats(),
+        ]
+    }
+
+    async fn begin(&self) -> Result<Box<dyn Transaction + '_>, DbError> {
+        Ok(Box::new(CachedTransaction {
+            inner: self.inner.begin().await?,
+            caches: &self.caches,
+            users: Vec::new(),
+            accounts: Vec::new(),
+            admins: Vec::new(),
+        }))
+    }
+
+    async fn try_lock_job(&self, job: &str) -> Result<Option<Box<dyn JobLock + '_>>, DbError> {
+        self.inner.try_lock_job(job).await
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
+        self.inner.get_user_by_email(email).await
+    }
+
+    async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, DbError> {
+        self.caches.users.get_or_load(id, self.inner.get_user_by_id(id)).await
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, DbError> {
+        self.inner.get_user_by_username(username).await
+    }
+
+    async fn create_user(&self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
+        let user = self.inner.create_user(req, password_hash).await?;
+        self.caches.users.invalidate(&user.id);
+        Ok(user)
+    }
+
+    async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError> {
+        let result = self.inner.update_user_last_login(user_id).await;
+        self.caches.users.invalidate(&user_id);
+        result
+    }
+
+    async fn list_users(&self, filter: &UserFilter, sort: UserSort, page: PageRequest) -> Result<UserPage, DbError> {
+        self.inner.list_users(filter, sort, page).await
+    }
+
+    async fn update_user(&self, user_id: Uuid, changes: &UpdateUserRequest, expected_version: i64) -> Result<User, DbError> {
+        let result = self.inner.update_user(user_id, changes, expected_version).await;
+        self.caches.users.invalidate(&user_id);
+        result
+    }
+
+    async fn soft_delete_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        let result = self.inner.soft_delete_user(user_id).await;
+        self.caches.users.invalidate(&user_id);
+        result
+    }
+
+    async fn restore_user(&self, user_id: Uuid) -> Result<User, DbError> {
+        let result = self.inner.restore_user(user_id).await;
+        self.caches.users.invalidate(&user_id);
+        result
+    }
+
+    async fn purge_deleted_users(&self, before: DateTime<Utc>) -> Result<u64, DbError> {
+        let purged = self.inner.purge_deleted_users(before).await?;
+        // The purged ids aren't known here, so drop everything that could
+        // still describe one of them.
+        if purged > 0 {
+            self.caches.accounts.clear();
+            self.caches.admins.clear();
+        }
+        Ok(purged)
+    }
+
+    async fn get_account_by_user_id(&self, user_id: Uuid) -> Result<Option<UserAccount>, DbError> {
+        self.caches
+            .accounts
+            .get_or_load(user_id, self.inner.get_account_by_user_id(user_id))
+            .await
+    }
+
+    async fn create_account(&self, user_id: Uuid) -> Result<UserAccount, DbError> {
+        let result = self.inner.create_account(user_id).await;
+        self.caches.accounts.invalidate(&user_id);
+        result
+    }
+
+    async fn update_account(&self, user_id: Uuid, changes: &UpdateAccountRequest, expected_version: i64) -> Result<UserAccount, DbError> {
+        let result = self.inner.update_account(user_id, changes, expected_version).await;
+        self.caches.accounts.invalidate(&user_id);
+        result
+    }
+
+    async fn get_admin_by_user_id(&self, user_id: Uuid) -> Result<Option<Admin>, DbError> {
+        self.caches
+            .admins
+            .get_or_load(user_id, self.inner.get_admin_by_user_id(user_id))
+            .await
+    }
+
+    async fn is_admin(&self, user_id: Uuid) -> Result<bool, DbError> {
+        Ok(self.get_admin_by_user_id(user_id).await?.is_some())
+    }
+
+    async fn has_superadmin(&self) -> Result<bool, DbError> {
+        self.inner.has_superadmin().await
+    }
+
+    async fn update_admin(&self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        let result = self.inner.update_admin(user_id, changes, expected_version).await;
+        self.caches.admins.invalidate(&user_id);
+        result
+    }
+
+    async fn delete_admin(&self, user_id: Uuid) -> Result<(), DbError> {
+        let result = self.inner.delete_admin(user_id).await;
+        self.caches.admins.invalidate(&user_id);
+        result
+    }
+
+    async fn store_token(&self, token: &AuthToken) -> Result<(), DbError> {
+        self.inner.store_token(token).await
+    }
+
+    async fn get_token_by_hash(&self, token_hash: &str) -> Result<Option<AuthToken>, DbError> {
+        self.inner.get_token_by_hash(token_hash).await
+    }
+
+    async fn revoke_token(&self, token_hash: &str) -> Result<(), DbError> {
+        self.inner.revoke_token(token_hash).await
+    }
+
+    async fn revoke_all_user_tokens(&self, user_id: Uuid) -> Result<(), DbError> {
+        self.inner.revoke_all_user_tokens(user_id).await
+    }
+
+    async fn purge_expired_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        self.inner.purge_expired_tokens(now).await
+    }
+
+    async fn count_active_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        self.inner.count_active_tokens(now).await
+    }
+
+    async fn purge_expired_validation_keys(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        self.inner.purge_expired_validation_keys(now).await
+    }
+
+    async fn record_login_event(&self, event: &LoginEvent) -> Result<(), DbError> {
+        self.inner.record_login_event(event).await
+    }
+
+    async fn get_login_events(&self, user_id: Uuid, limit: i64) -> Result<Vec<LoginEvent>, DbError> {
+        self.inner.get_login_events(user_id, limit).await
+    }
+
+    async fn is_known_device(&self, user_id: Uuid, fingerprint: &str) -> Result<bool, DbError> {
+        self.inner.is_known_device(user_id, fingerprint).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn user_request(name: &str) -> CreateUserRequest {
+        CreateUserRequest {
+            email: format!("{}@example.com", name),
+            password: "password".to_string(),
+            username: name.to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+        }
+    }
+
+    fn stats(storage: &dyn StorageLayer, name: &str) -> CacheStats {
+        storage.cache_stats().into_iter().find(|s| s.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_repeated_lookups_hit_the_cache() {
+        let storage = CachedStorage::new(MemoryStorage::new(), CacheConfig::default());
+        let user = storage.create_user(&user_request("alice"), "hash").await.unwrap();
+
+        storage.get_user_by_id(user.id).await.unwrap();
+        storage.get_user_by_id(user.id).await.unwrap();
+        storage.is_admin(user.id).await.unwrap();
+        storage.get_admin_by_user_id(user.id).await.unwrap();
+
+        let users = stats(&storage, "users");
+        assert_eq!((users.hits, users.misses, users.entries), (1, 1, 1));
+        let admins = stats(&storage, "admins");
+        assert_eq!((admins.hits, admins.misses), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_writes_invalidate_entries() {
+        let storage = CachedStorage::new(MemoryStorage::new(), CacheConfig::default());
+        let user = storage.create_user(&user_request("bob"), "hash").await.unwrap();
+
+        assert!(storage.get_account_by_user_id(user.id).await.unwrap().is_none());
+        storage.create_account(user.id).await.unwrap();
+        assert!(storage.get_account_by_user_id(user.id).await.unwrap().is_some());
+
+        assert!(storage.get_user_by_id(user.id).await.unwrap().unwrap().last_login.is_none());
+        storage.update_user_last_login(user.id).await.unwrap();
+        assert!(storage.get_user_by_id(user.id).await.unwrap().unwrap().last_login.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_committed_transaction_invalidates_entries() {
+        let storage = CachedStorage::new(MemoryStorage::new(), CacheConfig::default());
+        let user = storage.create_user(&user_request("carol"), "hash").await.unwrap();
+        assert!(!storage.is_admin(user.id).await.unwrap());
+
+        let mut tx = storage.begin().await.unwrap();
+        tx.create_admin(&CreateAdminRequest {
+            user_id: user.id,
+            role: crate::admin::model::AdminRole::Admin,
+            permissions: vec![],
+            created_by: None,
+        })
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+
+        assert!(storage.is_admin(user.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_entries_expire_and_capacity_is_bounded() {
+        let config = CacheConfig { ttl: Duration::from_millis(20), capacity: 2 };
+        let storage = CachedStorage::new(MemoryStorage::new(), config);
+
+        for _ in 0..3 {
+            storage.get_user_by_id(Uuid::new_v4()).await.unwrap();
+        }
+        assert_eq!(stats(&storage, "users").entries, 2);
+
+        let id = Uuid::new_v4();
+        storage.get_user_by_id(id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        storage.get_user_by_id(id).await.unwrap();
+        assert_eq!(stats(&storage, "users").hits, 0);
+    }
+}
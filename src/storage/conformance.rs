@@ -0,0 +1,821 @@
+//! Behavioural test suite shared by every `StorageLayer` implementation.
+//!
+//! Each case in [`cases`] is written once against `dyn StorageLayer` and
+//! instantiated per backend by `conformance_tests!`. Postgres cases connect to
+//! `TEST_DATABASE_URL` (default `postgres://postgres@localhost/postgres`),
+//! create a throwaway database for the test and drop it afterwards. They are
+//! skipped when no server is reachable.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use sqlx::{Connection, Executor, PgConnection};
+use uuid::Uuid;
+
+use super::{CacheConfig, CachedStorage, MemoryStorage, PostgresStorage, SqliteStorage, StorageLayer};
+
+const DEFAULT_TEST_DATABASE_URL: &str = "postgres://postgres@localhost/postgres";
+
+enum Backend {
+    Memory,
+    Sqlite,
+    Postgres,
+    /// SQLite behind `CachedStorage`, which must not change observable behaviour.
+    Cached,
+}
+
+/// Builds a fresh, empty store for one test, returning `None` when the
+/// backend is unavailable. The second value is the Postgres database to drop.
+async fn setup(backend: Backend) -> Option<(Arc<dyn StorageLayer>, Option<String>)> {
+    match backend {
+        Backend::Memory => Some((Arc::new(MemoryStorage::new()), None)),
+        Backend::Sqlite => {
+            let storage = SqliteStorage::from_url("sqlite::memory:").await.unwrap();
+            storage.run_migrations().await.unwrap();
+            Some((Arc::new(storage), None))
+        }
+        Backend::Cached => {
+            let storage = SqliteStorage::from_url("sqlite::memory:").await.unwrap();
+            storage.run_migrations().await.unwrap();
+            Some((Arc::new(CachedStorage::new(storage, CacheConfig::default())), None))
+        }
+        Backend::Postgres => {
+            let (storage, db_name) = postgres_database().await?;
+            Some((Arc::new(storage), Some(db_name)))
+        }
+    }
+}
+
+/// A migrated store on a throwaway Postgres database, and the database's name
+/// for `teardown`. Also used by tests of other Postgres-backed components.
+pub(crate) async fn postgres_database() -> Option<(PostgresStorage, String)> {
+    let admin_url = std::env::var("TEST_DATABASE_URL")
+        .unwrap_or_else(|_| DEFAULT_TEST_DATABASE_URL.to_string());
+    let Ok(mut conn) = PgConnection::connect(&admin_url).await else {
+        eprintln!("skipping postgres test: cannot connect to {}", admin_url);
+        return None;
+    };
+
+    let db_name = format!("learner_conformance_{}", Uuid::new_v4().simple());
+    conn.execute(format!("CREATE DATABASE {}", db_name).as_str()).await.unwrap();
+    conn.close().await.ok();
+
+    let storage = PostgresStorage::from_url(&database_url(&admin_url, &db_name)).await.unwrap();
+    storage.run_migrations().await.unwrap();
+    Some((storage, db_name))
+}
+
+fn database_url(admin_url: &str, db_name: &str) -> String {
+    let (base, query) = match admin_url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (admin_url, None),
+    };
+    let base = match base.rfind('/') {
+        Some(idx) if idx > "postgres://".len() => &base[..idx],
+        _ => base,
+    };
+    match query {
+        Some(query) => format!("{}/{}?{}", base, db_name, query),
+        None => format!("{}/{}", base, db_name),
+    }
+}
+
+pub(crate) async fn teardown(db_name: &str) {
+    let admin_url = std::env::var("TEST_DATABASE_URL")
+        .unwrap_or_else(|_| DEFAULT_TEST_DATABASE_URL.to_string());
+    if let Ok(mut conn) = PgConnection::connect(&admin_url).await {
+        let _ = conn
+            .execute(format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", db_name).as_str())
+            .await;
+    }
+}
+
+/// Runs `case` against a fresh store. The case runs on its own task so the
+/// throwaway database is dropped even when an assertion fails.
+async fn run<F, Fut>(backend: Backend, case: F)
+where
+    F: FnOnce(Arc<dyn StorageLayer>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let Some((storage, db_name)) = setup(backend).await else {
+        return;
+    };
+
+    let result = tokio::spawn(case(storage)).await;
+
+    if let Some(db_name) = db_name {
+        teardown(&db_name).await;
+    }
+    if let Err(err) = result {
+        std::panic::resume_unwind(err.into_panic());
+    }
+}
+
+mod cases {
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use crate::admin::model::{AdminRole, UpdateAdminRequest};
+    use crate::auth::model::{
+        AccountLevel, AccountStatus, AuthMethod, CreateAccountRequest, LoginEvent, UpdateAccountRequest,
+    };
+    use crate::storage::{seed_admin, DbError, StorageLayer};
+    use crate::users::model::{
+        CreateUserRequest, PageRequest, SortDirection, UpdateUserRequest, User, UserFilter, UserSort,
+        UserSortField,
+    };
+    use crate::validation::model::{AuthToken, TokenType};
+
+    fn user_request(email: &str, username: &str) -> CreateUserRequest {
+        CreateUserRequest {
+            email: email.to_string(),
+            password: "password".to_string(),
+            username: username.to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+        }
+    }
+
+    async fn create_user(storage: &dyn StorageLayer, name: &str) -> User {
+        storage
+            .create_user(&user_request(&format!("{}@example.com", name), name), "hash")
+            .await
+            .unwrap()
+    }
+
+    fn token(user_id: Uuid, hash: &str) -> AuthToken {
+        let now = Utc::now();
+        AuthToken {
+            id: Uuid::new_v4(),
+            user_id,
+            token_hash: hash.to_string(),
+            token_type: TokenType::Refresh,
+            expires_at: now + Duration::days(7),
+            created_at: now,
+            revoked_at: None,
+            device_info: None,
+        }
+    }
+
+    fn login_event(user_id: Uuid, success: bool, fingerprint: &str, age_minutes: i64) -> LoginEvent {
+        LoginEvent {
+            id: Uuid::new_v4(),
+            user_id: Some(user_id),
+            email: "user@example.com".to_string(),
+            success,
+            failure_reason: (!success).then(|| "INVALID_CREDENTIALS".to_string()),
+            ip_address: Some("127.0.0.1".to_string()),
+            user_agent: Some("test".to_string()),
+            auth_method: AuthMethod::Password,
+            device_fingerprint: Some(fingerprint.to_string()),
+            created_at: Utc::now() - Duration::minutes(age_minutes),
+        }
+    }
+
+    pub async fn create_and_fetch_user(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "alice").await;
+
+        assert!(user.is_active);
+        assert!(user.last_login.is_none());
+
+        let by_id = storage.get_user_by_id(user.id).await.unwrap().unwrap();
+        let by_email = storage.get_user_by_email("alice@example.com").await.unwrap().unwrap();
+        let by_username = storage.get_user_by_username("alice").await.unwrap().unwrap();
+
+        assert_eq!(by_id.id, user.id);
+        assert_eq!(by_email.id, user.id);
+        assert_eq!(by_username.id, user.id);
+        assert_eq!(by_id.password_hash, "hash");
+    }
+
+    pub async fn duplicate_email_is_rejected(storage: Arc<dyn StorageLayer>) {
+        create_user(&*storage, "bob").await;
+
+        let err = storage
+            .create_user(&user_request("bob@example.com", "bobby"), "hash")
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(err, DbError::Duplicate { ref constraint, ref field }
+                if field == "email" && constraint == "users_email_key"),
+            "got {:?}",
+            err
+        );
+        assert_eq!(err.http_status(), StatusCode::CONFLICT);
+        assert_eq!(err.error_code(), "EMAIL_EXISTS");
+    }
+
+    pub async fn duplicate_username_is_rejected(storage: Arc<dyn StorageLayer>) {
+        create_user(&*storage, "carol").await;
+
+        let err = storage
+            .create_user(&user_request("carol2@example.com", "carol"), "hash")
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(err, DbError::Duplicate { ref constraint, ref field }
+                if field == "username" && constraint == "users_username_key"),
+            "got {:?}",
+            err
+        );
+        assert_eq!(err.error_code(), "USERNAME_EXISTS");
+    }
+
+    pub async fn missing_records_are_none(storage: Arc<dyn StorageLayer>) {
+        let id = Uuid::new_v4();
+
+        assert!(storage.get_user_by_id(id).await.unwrap().is_none());
+        assert!(storage.get_user_by_email("nobody@example.com").await.unwrap().is_none());
+        assert!(storage.get_user_by_username("nobody").await.unwrap().is_none());
+        assert!(storage.get_account_by_user_id(id).await.unwrap().is_none());
+        assert!(storage.get_admin_by_user_id(id).await.unwrap().is_none());
+        assert!(!storage.is_admin(id).await.unwrap());
+        assert!(storage.get_token_by_hash("missing").await.unwrap().is_none());
+        assert!(storage.get_login_events(id, 10).await.unwrap().is_empty());
+    }
+
+    pub async fn last_login_on_missing_user_is_not_found(storage: Arc<dyn StorageLayer>) {
+        let err = storage.update_user_last_login(Uuid::new_v4()).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+    }
+
+    pub async fn last_login_is_recorded(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "dave").await;
+
+        storage.update_user_last_login(user.id).await.unwrap();
+
+        let user = storage.get_user_by_id(user.id).await.unwrap().unwrap();
+        assert!(user.last_login.is_some());
+    }
+
+    pub async fn account_defaults_and_uniqueness(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "erin").await;
+
+        let account = storage.create_account(user.id).await.unwrap();
+        assert!(matches!(account.account_level, AccountLevel::Free));
+        assert_eq!(account.account_status, AccountStatus::Pending);
+        assert!(account.capabilities.is_empty());
+
+        let fetched = storage.get_account_by_user_id(user.id).await.unwrap().unwrap();
+        assert_eq!(fetched.id, account.id);
+
+        let err = storage.create_account(user.id).await.unwrap_err();
+        assert!(matches!(err, DbError::Duplicate { ref field, .. } if field == "user_id"), "got {:?}", err);
+    }
+
+    pub async fn account_for_missing_user_fails(storage: Arc<dyn StorageLayer>) {
+        let err = storage.create_account(Uuid::new_v4()).await.unwrap_err();
+        assert!(matches!(err, DbError::ForeignKey { .. }), "got {:?}", err);
+        assert_eq!(err.http_status(), StatusCode::CONFLICT);
+    }
+
+    pub async fn token_revocation(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "frank").await;
+
+        storage.store_token(&token(user.id, "hash-1")).await.unwrap();
+        let stored = storage.get_token_by_hash("hash-1").await.unwrap().unwrap();
+        assert!(stored.revoked_at.is_none());
+        assert!(matches!(stored.token_type, TokenType::Refresh));
+
+        storage.revoke_token("hash-1").await.unwrap();
+        let revoked = storage.get_token_by_hash("hash-1").await.unwrap().unwrap();
+        assert!(revoked.revoked_at.is_some());
+
+        // Revoking an unknown token is a no-op rather than an error.
+        storage.revoke_token("unknown").await.unwrap();
+    }
+
+    pub async fn expired_tokens_are_purged(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "ivan").await;
+        let expired = AuthToken { expires_at: Utc::now() - Duration::minutes(1), ..token(user.id, "ivan-old") };
+        let revoked = token(user.id, "ivan-revoked");
+
+        storage.store_token(&expired).await.unwrap();
+        storage.store_token(&revoked).await.unwrap();
+        storage.store_token(&token(user.id, "ivan-live")).await.unwrap();
+        storage.revoke_token("ivan-revoked").await.unwrap();
+        assert_eq!(storage.count_active_tokens(Utc::now()).await.unwrap(), 1);
+
+        assert_eq!(storage.purge_expired_tokens(Utc::now()).await.unwrap(), 1);
+        assert!(storage.get_token_by_hash("ivan-old").await.unwrap().is_none());
+        // Revoked tokens stay until they expire so the revocation still counts.
+        assert!(storage.get_token_by_hash("ivan-revoked").await.unwrap().is_some());
+        assert!(storage.get_token_by_hash("ivan-live").await.unwrap().is_some());
+
+        assert_eq!(storage.purge_expired_tokens(Utc::now()).await.unwrap(), 0);
+        storage.purge_expired_validation_keys(Utc::now()).await.unwrap();
+    }
+
+    pub async fn job_lock_can_be_retaken_after_release(storage: Arc<dyn StorageLayer>) {
+        let lock = storage.try_lock_job("conformance").await.unwrap().expect("lock is free");
+        let other = storage.try_lock_job("conformance-other").await.unwrap();
+        assert!(other.is_some(), "locks for different jobs are independent");
+
+        lock.release().await.unwrap();
+        other.unwrap().release().await.unwrap();
+
+        let again = storage.try_lock_job("conformance").await.unwrap();
+        assert!(again.is_some());
+    }
+
+    pub async fn revoke_all_only_touches_one_user(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "grace").await;
+        let other = create_user(&*storage, "heidi").await;
+
+        storage.store_token(&token(user.id, "grace-1")).await.unwrap();
+        storage.store_token(&token(user.id, "grace-2")).await.unwrap();
+        storage.store_token(&token(other.id, "heidi-1")).await.unwrap();
+
+        storage.revoke_token("grace-1").await.unwrap();
+        let first_revocation = storage
+            .get_token_by_hash("grace-1")
+            .await
+            .unwrap()
+            .unwrap()
+            .revoked_at
+            .unwrap();
+
+        storage.revoke_all_user_tokens(user.id).await.unwrap();
+
+        let grace_1 = storage.get_token_by_hash("grace-1").await.unwrap().unwrap();
+        let grace_2 = storage.get_token_by_hash("grace-2").await.unwrap().unwrap();
+        let heidi_1 = storage.get_token_by_hash("heidi-1").await.unwrap().unwrap();
+
+        assert_eq!(grace_1.revoked_at.unwrap(), first_revocation, "already revoked tokens keep their timestamp");
+        assert!(grace_2.revoked_at.is_some());
+        assert!(heidi_1.revoked_at.is_none());
+    }
+
+    pub async fn login_events_are_newest_first(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "ivan").await;
+        let other = create_user(&*storage, "judy").await;
+
+        let oldest = login_event(user.id, true, "device-a", 30);
+        let middle = login_event(user.id, false, "device-b", 20);
+        let newest = login_event(user.id, true, "device-c", 10);
+
+        storage.record_login_event(&middle).await.unwrap();
+        storage.record_login_event(&newest).await.unwrap();
+        storage.record_login_event(&oldest).await.unwrap();
+        storage.record_login_event(&login_event(other.id, true, "device-a", 5)).await.unwrap();
+
+        let events = storage.get_login_events(user.id, 10).await.unwrap();
+        let ids: Vec<Uuid> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![newest.id, middle.id, oldest.id]);
+
+        let limited = storage.get_login_events(user.id, 2).await.unwrap();
+        assert_eq!(limited.len(), 2);
+        assert_eq!(limited[0].id, newest.id);
+    }
+
+    pub async fn known_devices_require_success(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "mallory").await;
+
+        storage.record_login_event(&login_event(user.id, true, "laptop", 10)).await.unwrap();
+        storage.record_login_event(&login_event(user.id, false, "phone", 5)).await.unwrap();
+
+        assert!(storage.is_known_device(user.id, "laptop").await.unwrap());
+        assert!(!storage.is_known_device(user.id, "phone").await.unwrap());
+        assert!(!storage.is_known_device(Uuid::new_v4(), "laptop").await.unwrap());
+    }
+
+    pub async fn transaction_commits_all_writes(storage: Arc<dyn StorageLayer>) {
+        let user = storage
+            .transaction(|tx| {
+                Box::pin(async move {
+                    let user = tx.create_user(&user_request("niaj@example.com", "niaj"), "hash").await?;
+                    tx.create_account(&CreateAccountRequest::default_for(user.id)).await?;
+                    assert!(tx.get_user_by_email("niaj@example.com").await?.is_some());
+                    Ok(user)
+                })
+            })
+            .await
+            .unwrap();
+
+        assert!(storage.get_user_by_id(user.id).await.unwrap().is_some());
+        assert!(storage.get_account_by_user_id(user.id).await.unwrap().is_some());
+    }
+
+    pub async fn transaction_error_rolls_back(storage: Arc<dyn StorageLayer>) {
+        let result: Result<(), DbError> = storage
+            .transaction(|tx| {
+                Box::pin(async move {
+                    tx.create_user(&user_request("olivia@example.com", "olivia"), "hash").await?;
+                    Err(DbError::Other("abort".to_string()))
+                })
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert!(storage.get_user_by_email("olivia@example.com").await.unwrap().is_none());
+    }
+
+    pub async fn failed_step_rolls_back_earlier_writes(storage: Arc<dyn StorageLayer>) {
+        let existing = create_user(&*storage, "peggy").await;
+        storage.create_account(existing.id).await.unwrap();
+
+        let result = storage
+            .transaction(|tx| {
+                Box::pin(async move {
+                    tx.create_user(&user_request("rupert@example.com", "rupert"), "hash").await?;
+                    tx.create_account(&CreateAccountRequest::default_for(existing.id)).await?;
+                    Ok(())
+                })
+            })
+            .await;
+
+        assert!(matches!(result, Err(DbError::Duplicate { .. })));
+        assert!(storage.get_user_by_email("rupert@example.com").await.unwrap().is_none());
+    }
+
+    async fn list_usernames(storage: &dyn StorageLayer, filter: UserFilter) -> (Vec<String>, i64) {
+        let sort = UserSort { field: UserSortField::Username, direction: SortDirection::Asc };
+        let page = storage.list_users(&filter, sort, PageRequest::default()).await.unwrap();
+        (page.users.into_iter().map(|s| s.user.username).collect(), page.total)
+    }
+
+    pub async fn soft_deleted_users_are_hidden(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "judy").await;
+        let other = create_user(&*storage, "ken").await;
+
+        storage.soft_delete_user(user.id).await.unwrap();
+
+        assert!(storage.get_user_by_id(user.id).await.unwrap().is_none());
+        assert!(storage.get_user_by_email("judy@example.com").await.unwrap().is_none());
+        assert!(storage.get_user_by_username("judy").await.unwrap().is_none());
+        let err = storage.update_user_last_login(user.id).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+        let err = storage.soft_delete_user(user.id).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+
+        let live = storage.list_users(&UserFilter::default(), UserSort::default(), PageRequest::default()).await.unwrap();
+        assert_eq!(live.total, 1);
+        assert_eq!(live.users[0].user.id, other.id);
+
+        let deleted = UserFilter { deleted: true, ..Default::default() };
+        let deleted = storage.list_users(&deleted, UserSort::default(), PageRequest::default()).await.unwrap();
+        assert_eq!(deleted.total, 1);
+        assert_eq!(deleted.users[0].user.id, user.id);
+        assert!(deleted.users[0].user.deleted_at.is_some());
+    }
+
+    pub async fn soft_delete_frees_email_and_username(storage: Arc<dyn StorageLayer>) {
+        let original = create_user(&*storage, "leo").await;
+        storage.soft_delete_user(original.id).await.unwrap();
+
+        let replacement = create_user(&*storage, "leo").await;
+        assert_ne!(replacement.id, original.id);
+        assert_eq!(storage.get_user_by_email("leo@example.com").await.unwrap().unwrap().id, replacement.id);
+
+        // The original can't come back while someone else holds its email and
+        // username; backends differ in which one they report first.
+        let err = storage.restore_user(original.id).await.unwrap_err();
+        assert!(matches!(err, DbError::Duplicate { .. }), "got {:?}", err);
+    }
+
+    pub async fn restore_brings_user_back(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "mia").await;
+
+        let err = storage.restore_user(user.id).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "live users can't be restored: {:?}", err);
+
+        storage.soft_delete_user(user.id).await.unwrap();
+        let restored = storage.restore_user(user.id).await.unwrap();
+        assert_eq!(restored.id, user.id);
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(storage.get_user_by_username("mia").await.unwrap().unwrap().id, user.id);
+    }
+
+    pub async fn purge_removes_users_deleted_before_cutoff(storage: Arc<dyn StorageLayer>) {
+        let gone = create_user(&*storage, "ned").await;
+        storage.create_account(gone.id).await.unwrap();
+        storage.store_token(&token(gone.id, "ned-1")).await.unwrap();
+        let kept = create_user(&*storage, "olga").await;
+        storage.create_account(kept.id).await.unwrap();
+
+        storage.soft_delete_user(gone.id).await.unwrap();
+
+        let purged = storage.purge_deleted_users(Utc::now() - Duration::days(1)).await.unwrap();
+        assert_eq!(purged, 0, "deleted too recently");
+
+        assert_eq!(storage.purge_deleted_users(Utc::now()).await.unwrap(), 1);
+        assert!(storage.get_account_by_user_id(gone.id).await.unwrap().is_none());
+        assert!(storage.get_token_by_hash("ned-1").await.unwrap().is_none());
+        let err = storage.restore_user(gone.id).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+
+        assert!(storage.get_user_by_id(kept.id).await.unwrap().is_some());
+        assert!(storage.get_account_by_user_id(kept.id).await.unwrap().is_some());
+    }
+
+    pub async fn update_user_checks_version(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "pia").await;
+        assert_eq!(user.version, 1);
+
+        let changes = UpdateUserRequest { first_name: Some("Pia".to_string()), ..Default::default() };
+        let updated = storage.update_user(user.id, &changes, user.version).await.unwrap();
+        assert_eq!(updated.version, 2);
+        assert_eq!(updated.first_name, "Pia");
+        assert_eq!(updated.last_name, user.last_name, "unset fields are kept");
+
+        // A second writer still holding version 1 must not overwrite the change.
+        let stale = UpdateUserRequest { first_name: Some("Stale".to_string()), ..Default::default() };
+        let err = storage.update_user(user.id, &stale, user.version).await.unwrap_err();
+        assert!(matches!(err, DbError::Conflict), "got {:?}", err);
+        assert_eq!(err.http_status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(storage.get_user_by_id(user.id).await.unwrap().unwrap().first_name, "Pia");
+
+        let err = storage.update_user(Uuid::new_v4(), &changes, 1).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+
+        // Logins aren't edits, so they leave the version alone.
+        storage.update_user_last_login(user.id).await.unwrap();
+        assert_eq!(storage.get_user_by_id(user.id).await.unwrap().unwrap().version, 2);
+    }
+
+    pub async fn update_user_rejects_taken_email(storage: Arc<dyn StorageLayer>) {
+        create_user(&*storage, "quinn").await;
+        let user = create_user(&*storage, "rosa").await;
+
+        let changes = UpdateUserRequest { email: Some("quinn@example.com".to_string()), ..Default::default() };
+        let err = storage.update_user(user.id, &changes, user.version).await.unwrap_err();
+        assert!(matches!(err, DbError::Duplicate { ref field, .. } if field == "email"), "got {:?}", err);
+    }
+
+    pub async fn update_account_checks_version(storage: Arc<dyn StorageLayer>) {
+        let admin = create_user(&*storage, "sam").await;
+        let user = create_user(&*storage, "tess").await;
+        let account = storage.create_account(user.id).await.unwrap();
+        assert_eq!(account.version, 1);
+
+        let changes = UpdateAccountRequest {
+            account_status: Some(AccountStatus::Suspended),
+            status_reason: Some("chargeback".to_string()),
+            changed_by: Some(admin.id),
+            ..Default::default()
+        };
+        let updated = storage.update_account(user.id, &changes, account.version).await.unwrap();
+        assert_eq!(updated.version, 2);
+        assert_eq!(updated.account_status, AccountStatus::Suspended);
+        assert_eq!(updated.account_level, account.account_level);
+        assert_eq!(updated.status_reason.as_deref(), Some("chargeback"));
+        assert_eq!(updated.status_changed_by, Some(admin.id));
+        assert!(updated.status_changed_at.is_some());
+
+        // Without a new status the status bookkeeping is left alone.
+        let changes = UpdateAccountRequest {
+            account_level: Some(AccountLevel::Premium),
+            capabilities: Some(vec!["api_access".to_string()]),
+            status_reason: Some("ignored".to_string()),
+            ..Default::default()
+        };
+        let updated = storage.update_account(user.id, &changes, updated.version).await.unwrap();
+        assert_eq!(updated.version, 3);
+        assert_eq!(updated.account_level, AccountLevel::Premium);
+        assert_eq!(updated.capabilities, vec!["api_access".to_string()]);
+        assert_eq!(updated.status_reason.as_deref(), Some("chargeback"));
+
+        let err = storage.update_account(user.id, &changes, 2).await.unwrap_err();
+        assert!(matches!(err, DbError::Conflict), "got {:?}", err);
+        let err = storage.update_account(admin.id, &changes, 1).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+    }
+
+    pub async fn update_admin_checks_version(storage: Arc<dyn StorageLayer>) {
+        seed_admin(&*storage, "root@example.com", "root", "hash").await.unwrap();
+        let user = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+        let admin = storage.get_admin_by_user_id(user.id).await.unwrap().unwrap();
+        assert_eq!(admin.version, 1);
+
+        let changes = UpdateAdminRequest { role: Some(AdminRole::Moderator), ..Default::default() };
+        let updated = storage.update_admin(user.id, &changes, admin.version).await.unwrap();
+        assert_eq!(updated.version, 2);
+        assert!(matches!(updated.role, AdminRole::Moderator));
+        assert_eq!(updated.permissions, admin.permissions);
+
+        let err = storage.update_admin(user.id, &changes, admin.version).await.unwrap_err();
+        assert!(matches!(err, DbError::Conflict), "got {:?}", err);
+        let err = storage.update_admin(Uuid::new_v4(), &changes, 1).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+    }
+
+    pub async fn transaction_updates_admin_and_sees_superadmins(storage: Arc<dyn StorageLayer>) {
+        seed_admin(&*storage, "root@example.com", "root", "hash").await.unwrap();
+        let root = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+
+        let (before, after) = storage
+            .transaction(|tx| {
+                Box::pin(async move {
+                    let before = tx.superadmin_ids().await?;
+                    let changes = UpdateAdminRequest { role: Some(AdminRole::Moderator), ..Default::default() };
+                    tx.update_admin(root.id, &changes, 1).await?;
+                    Ok((before, tx.superadmin_ids().await?))
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(before, vec![root.id]);
+        assert!(after.is_empty());
+        assert!(!storage.has_superadmin().await.unwrap());
+        assert_eq!(storage.get_admin_by_user_id(root.id).await.unwrap().unwrap().version, 2);
+
+        let err = storage
+            .transaction(|tx| {
+                Box::pin(async move { tx.update_admin(root.id, &UpdateAdminRequest::default(), 1).await })
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::Conflict), "got {:?}", err);
+    }
+
+    pub async fn update_user_sets_password_hash(storage: Arc<dyn StorageLayer>) {
+        let user = create_user(&*storage, "quin").await;
+
+        let changes = UpdateUserRequest { password_hash: Some("new-hash".to_string()), ..Default::default() };
+        let updated = storage.update_user(user.id, &changes, user.version).await.unwrap();
+        assert_eq!(updated.password_hash, "new-hash");
+        assert_eq!(updated.version, 2);
+        assert_eq!(storage.get_user_by_id(user.id).await.unwrap().unwrap().password_hash, "new-hash");
+    }
+
+    pub async fn has_superadmin_ignores_other_roles_and_deleted_users(storage: Arc<dyn StorageLayer>) {
+        assert!(!storage.has_superadmin().await.unwrap());
+
+        seed_admin(&*storage, "root@example.com", "root", "hash").await.unwrap();
+        let root = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+        assert!(storage.has_superadmin().await.unwrap());
+
+        let admin = storage.get_admin_by_user_id(root.id).await.unwrap().unwrap();
+        let changes = UpdateAdminRequest { role: Some(AdminRole::Moderator), ..Default::default() };
+        storage.update_admin(root.id, &changes, admin.version).await.unwrap();
+        assert!(!storage.has_superadmin().await.unwrap());
+
+        let changes = UpdateAdminRequest { role: Some(AdminRole::SuperAdmin), ..Default::default() };
+        storage.update_admin(root.id, &changes, admin.version + 1).await.unwrap();
+        storage.soft_delete_user(root.id).await.unwrap();
+        assert!(!storage.has_superadmin().await.unwrap());
+    }
+
+    pub async fn delete_admin_keeps_user(storage: Arc<dyn StorageLayer>) {
+        seed_admin(&*storage, "root@example.com", "root", "hash").await.unwrap();
+        let user = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+
+        storage.delete_admin(user.id).await.unwrap();
+        assert!(storage.get_admin_by_user_id(user.id).await.unwrap().is_none());
+        assert!(!storage.is_admin(user.id).await.unwrap());
+        assert!(storage.get_user_by_id(user.id).await.unwrap().is_some());
+        assert!(storage.get_account_by_user_id(user.id).await.unwrap().is_some());
+
+        let err = storage.delete_admin(user.id).await.unwrap_err();
+        assert!(matches!(err, DbError::NotFound), "got {:?}", err);
+    }
+
+    pub async fn list_users_filters(storage: Arc<dyn StorageLayer>) {
+        let ann = create_user(&*storage, "ann").await;
+        storage.create_account(ann.id).await.unwrap();
+        storage
+            .transaction(|tx| {
+                Box::pin(async move {
+                    let ben = tx.create_user(&user_request("ben@example.com", "ben"), "hash").await?;
+                    tx.create_account(&CreateAccountRequest {
+                        user_id: ben.id,
+                        account_level: AccountLevel::Premium,
+                        account_status: AccountStatus::Active,
+                        capabilities: vec![],
+                    })
+                    .await?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap();
+        create_user(&*storage, "cat_1").await;
+
+        let all = list_usernames(&*storage, UserFilter::default()).await;
+        assert_eq!(all, (vec!["ann".to_string(), "ben".to_string(), "cat_1".to_string()], 3));
+
+        let active = UserFilter { status: Some(AccountStatus::Active), ..Default::default() };
+        assert_eq!(list_usernames(&*storage, active).await, (vec!["ben".to_string()], 1));
+
+        let free = UserFilter { account_level: Some(AccountLevel::Free), ..Default::default() };
+        assert_eq!(list_usernames(&*storage, free).await, (vec!["ann".to_string()], 1));
+
+        let by_email = UserFilter { search: Some("  BEN@Example".to_string()), ..Default::default() };
+        assert_eq!(list_usernames(&*storage, by_email).await.1, 1);
+
+        let by_name = UserFilter { search: Some("test user".to_string()), ..Default::default() };
+        assert_eq!(list_usernames(&*storage, by_name).await.1, 3);
+
+        let wildcard = UserFilter { search: Some("_".to_string()), ..Default::default() };
+        assert_eq!(list_usernames(&*storage, wildcard).await, (vec!["cat_1".to_string()], 1));
+
+        let future = Utc::now() + Duration::days(1);
+        let after = UserFilter { created_after: Some(future), ..Default::default() };
+        assert_eq!(list_usernames(&*storage, after).await.1, 0);
+        let before = UserFilter { created_before: Some(future), ..Default::default() };
+        assert_eq!(list_usernames(&*storage, before).await.1, 3);
+    }
+
+    pub async fn list_users_sorts_and_pages(storage: Arc<dyn StorageLayer>) {
+        for name in ["u3", "u1", "u4", "u0", "u2"] {
+            create_user(&*storage, name).await;
+        }
+        let filter = UserFilter::default();
+        let names = |page: crate::users::model::UserPage| {
+            page.users.into_iter().map(|s| s.user.username).collect::<Vec<_>>()
+        };
+
+        let by_email = UserSort { field: UserSortField::Email, direction: SortDirection::Asc };
+        let first = storage.list_users(&filter, by_email, PageRequest::new(Some(1), Some(2))).await.unwrap();
+        assert_eq!(first.total, 5);
+        assert_eq!(names(first), vec!["u0", "u1"]);
+
+        let last = storage.list_users(&filter, by_email, PageRequest::new(Some(3), Some(2))).await.unwrap();
+        assert_eq!(names(last), vec!["u4"]);
+
+        let past_end = storage.list_users(&filter, by_email, PageRequest::new(Some(4), Some(2))).await.unwrap();
+        assert_eq!(past_end.total, 5);
+        assert!(past_end.users.is_empty());
+
+        let desc = UserSort { field: UserSortField::Username, direction: SortDirection::Desc };
+        let page = storage.list_users(&filter, desc, PageRequest::new(Some(1), Some(2))).await.unwrap();
+        assert_eq!(names(page), vec!["u4", "u3"]);
+
+        let newest = storage.list_users(&filter, UserSort::default(), PageRequest::new(Some(1), Some(1))).await.unwrap();
+        assert_eq!(names(newest), vec!["u2"]);
+    }
+}
+
+macro_rules! conformance_tests {
+    ($backend:ident, $variant:expr) => {
+        mod $backend {
+            use super::{cases, run};
+
+            conformance_tests!(@cases $variant;
+                create_and_fetch_user,
+                duplicate_email_is_rejected,
+                duplicate_username_is_rejected,
+                missing_records_are_none,
+                last_login_on_missing_user_is_not_found,
+                last_login_is_recorded,
+                account_defaults_and_uniqueness,
+                account_for_missing_user_fails,
+                token_revocation,
+                revoke_all_only_touches_one_user,
+                expired_tokens_are_purged,
+                job_lock_can_be_retaken_after_release,
+                login_events_are_newest_first,
+                known_devices_require_success,
+                transaction_commits_all_writes,
+                transaction_error_rolls_back,
+                failed_step_rolls_back_earlier_writes,
+                list_users_filters,
+                list_users_sorts_and_pages,
+                soft_deleted_users_are_hidden,
+                soft_delete_frees_email_and_username,
+                restore_brings_user_back,
+                purge_removes_users_deleted_before_cutoff,
+                update_user_checks_version,
+                update_user_rejects_taken_email,
+                update_account_checks_version,
+                update_admin_checks_version,
+                transaction_updates_admin_and_sees_superadmins,
+                update_user_sets_password_hash,
+                delete_admin_keeps_user,
+                has_superadmin_ignores_other_roles_and_deleted_users,
+            );
+        }
+    };
+    (@cases $variant:expr; $($case:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $case() {
+                run($variant, cases::$case).await;
+            }
+        )*
+    };
+}
+
+/// Advisory locks are the one place where Postgres must behave differently:
+/// a held lock excludes every other connection.
+#[tokio::test]
+async fn postgres_job_lock_excludes_other_holders() {
+    run(Backend::Postgres, |storage| async move {
+        let lock = storage.try_lock_job("exclusive").await.unwrap().expect("lock is free");
+        assert!(storage.try_lock_job("exclusive").await.unwrap().is_none());
+
+        lock.release().await.unwrap();
+        assert!(storage.try_lock_job("exclusive").await.unwrap().is_some());
+    })
+    .await;
+}
+
+conformance_tests!(memory, super::Backend::Memory);
+conformance_tests!(sqlite, super::Backend::Sqlite);
+conformance_tests!(postgres, super::Backend::Postgres);
+conformance_tests!(cached, super::Backend::Cached);
@@ -1,20 +1,127 @@
 use std::fmt;
 
+use axum::http::StatusCode;
+
 #[derive(Debug)]
 pub enum DbError {
     NotFound,
-    Duplicate(String),
+    /// A unique constraint rejected the write. `constraint` uses Postgres'
+    /// naming (`users_email_key`) on every backend; `field` is the column.
+    Duplicate { constraint: String, field: String },
+    /// A referenced row doesn't exist, or is still referenced.
+    ForeignKey { constraint: String },
+    CheckViolation { constraint: String },
+    /// The transaction lost a race with a concurrent one and can be retried.
+    SerializationFailure,
+    /// A version-checked update was based on a stale copy of the record.
+    Conflict,
+    Timeout,
     Connection(String),
     Query(String),
     Migration(String),
     Other(String),
 }
 
+impl DbError {
+    pub fn duplicate(table: &str, field: &str) -> Self {
+        DbError::Duplicate {
+            constraint: format!("{}_{}_key", table, field),
+            field: field.to_string(),
+        }
+    }
+
+    pub fn foreign_key(table: &str, column: &str) -> Self {
+        DbError::ForeignKey {
+            constraint: format!("{}_{}_fkey", table, column),
+        }
+    }
+
+    /// Stable, lowercase name of the variant, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DbError::NotFound => "not_found",
+            DbError::Duplicate { .. } => "duplicate",
+            DbError::ForeignKey { .. } => "foreign_key",
+            DbError::CheckViolation { .. } => "check_violation",
+            DbError::SerializationFailure => "serialization_failure",
+            DbError::Conflict => "conflict",
+            DbError::Timeout => "timeout",
+            DbError::Connection(_) => "connection",
+            DbError::Query(_) => "query",
+            DbError::Migration(_) => "migration",
+            DbError::Other(_) => "other",
+        }
+    }
+
+    /// HTTP status for a request that failed with this error. Handlers use
+    /// this together with `error_code` and `public_message` rather than
+    /// mapping errors themselves.
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            DbError::NotFound => StatusCode::NOT_FOUND,
+            DbError::Duplicate { .. } | DbError::ForeignKey { .. } => StatusCode::CONFLICT,
+            DbError::CheckViolation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            // Versions reach the store through `If-Match`, so a stale one is a
+            // failed precondition rather than a plain conflict.
+            DbError::Conflict => StatusCode::PRECONDITION_FAILED,
+            DbError::SerializationFailure | DbError::Timeout | DbError::Connection(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            DbError::Query(_) | DbError::Migration(_) | DbError::Other(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            DbError::NotFound => "NOT_FOUND",
+            DbError::Duplicate { field, .. } => match field.as_str() {
+                "email" => "EMAIL_EXISTS",
+                "username" => "USERNAME_EXISTS",
+                _ => "ALREADY_EXISTS",
+            },
+            DbError::ForeignKey { .. } => "INVALID_REFERENCE",
+            DbError::CheckViolation { .. } => "INVALID_VALUE",
+            DbError::SerializationFailure => "RETRY_LATER",
+            DbError::Conflict => "VERSION_CONFLICT",
+            DbError::Timeout => "DATABASE_TIMEOUT",
+            DbError::Connection(_) => "DATABASE_UNAVAILABLE",
+            DbError::Query(_) | DbError::Migration(_) | DbError::Other(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    /// Message safe to show to clients; never includes driver details.
+    pub fn public_message(&self) -> &'static str {
+        match self {
+            DbError::NotFound => "Not found",
+            DbError::Duplicate { field, .. } => match field.as_str() {
+                "email" => "Email already registered",
+                "username" => "Username already taken",
+                _ => "Already exists",
+            },
+            DbError::ForeignKey { .. } => "Referenced record does not exist or is still in use",
+            DbError::CheckViolation { .. } => "Invalid value",
+            DbError::SerializationFailure => "Concurrent update, please retry",
+            DbError::Conflict => "Record was changed by someone else; reload it and try again",
+            DbError::Timeout | DbError::Connection(_) => "Service temporarily unavailable",
+            DbError::Query(_) | DbError::Migration(_) | DbError::Other(_) => "Internal server error",
+        }
+    }
+}
+
 impl fmt::Display for DbError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             DbError::NotFound => write!(f, "Record not found"),
-            DbError::Duplicate(field) => write!(f, "Duplicate value for field: {}", field),
+            DbError::Duplicate { constraint, field } => {
+                write!(f, "Duplicate value for field {} ({})", field, constraint)
+            }
+            DbError::ForeignKey { constraint } => write!(f, "Foreign key violation: {}", constraint),
+            DbError::CheckViolation { constraint } => write!(f, "Check constraint violation: {}", constraint),
+            DbError::SerializationFailure => write!(f, "Could not serialize access due to concurrent update"),
+            DbError::Conflict => write!(f, "Record version does not match"),
+            DbError::Timeout => write!(f, "Database operation timed out"),
             DbError::Connection(msg) => write!(f, "Database connection error: {}", msg),
             DbError::Query(msg) => write!(f, "Query error: {}", msg),
             DbError::Migration(msg) => write!(f, "Migration error: {}", msg),
@@ -29,16 +136,124 @@ impl From<sqlx::Error> for DbError {
     fn from(err: sqlx::Error) -> Self {
         match err {
             sqlx::Error::RowNotFound => DbError::NotFound,
-            sqlx::Error::Database(db_err) => {
-                // Check for unique constraint violations
-                if db_err.code().map(|c| c == "23505").unwrap_or(false) {
-                    DbError::Duplicate(db_err.message().to_string())
-                } else {
-                    DbError::Query(db_err.message().to_string())
-                }
-            }
+            sqlx::Error::PoolTimedOut => DbError::Timeout,
+            sqlx::Error::Database(db_err) => from_database_error(db_err.as_ref()),
             sqlx::Error::Io(io_err) => DbError::Connection(io_err.to_string()),
             _ => DbError::Other(err.to_string()),
         }
     }
 }
+
+// Postgres SQLSTATEs without a sqlx `ErrorKind`.
+const PG_SERIALIZATION_FAILURE: &str = "40001";
+const PG_DEADLOCK_DETECTED: &str = "40P01";
+const PG_QUERY_CANCELED: &str = "57014";
+const PG_LOCK_NOT_AVAILABLE: &str = "55P03";
+// SQLite primary result codes.
+const SQLITE_BUSY: &str = "5";
+const SQLITE_LOCKED: &str = "6";
+
+fn from_database_error(db_err: &dyn sqlx::error::DatabaseError) -> DbError {
+    use sqlx::error::ErrorKind;
+
+    match db_err.kind() {
+        ErrorKind::UniqueViolation => {
+            let (table, field) = violated_column(db_err);
+            let constraint = db_err
+                .constraint()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{}_{}_key", table, field));
+            return DbError::Duplicate { constraint, field };
+        }
+        ErrorKind::ForeignKeyViolation => {
+            return DbError::ForeignKey { constraint: constraint_name(db_err) };
+        }
+        ErrorKind::CheckViolation => {
+            return DbError::CheckViolation { constraint: constraint_name(db_err) };
+        }
+        _ => {}
+    }
+
+    // SQLite extended codes (e.g. 517 SQLITE_BUSY_SNAPSHOT) keep the primary
+    // code in the low byte.
+    let code = db_err.code().unwrap_or_default();
+    let sqlite_primary = code.parse::<i32>().ok().map(|c| (c & 0xff).to_string());
+
+    match (code.as_ref(), sqlite_primary.as_deref()) {
+        (PG_SERIALIZATION_FAILURE | PG_DEADLOCK_DETECTED, _) => DbError::SerializationFailure,
+        (PG_QUERY_CANCELED | PG_LOCK_NOT_AVAILABLE, _) => DbError::Timeout,
+        (_, Some(SQLITE_BUSY | SQLITE_LOCKED)) => DbError::Timeout,
+        _ => DbError::Query(db_err.message().to_string()),
+    }
+}
+
+fn constraint_name(db_err: &dyn sqlx::error::DatabaseError) -> String {
+    if let Some(constraint) = db_err.constraint() {
+        return constraint.to_string();
+    }
+    // SQLite: `CHECK constraint failed: account_level IN (...)`; foreign key
+    // failures don't name the constraint at all.
+    db_err
+        .message()
+        .split_once("constraint failed: ")
+        .map(|(_, detail)| detail.to_string())
+        .unwrap_or_else(|| db_err.message().to_string())
+}
+
+/// Table and column of a unique violation, so every backend reports
+/// `field: "email"` rather than a driver-specific message.
+fn violated_column(db_err: &dyn sqlx::error::DatabaseError) -> (String, String) {
+    // Postgres: detail is `Key (email)=(a@b.c) already exists.`
+    if let Some(pg_err) = db_err.try_downcast_ref::<sqlx::postgres::PgDatabaseError>() {
+        let table = pg_err.table().unwrap_or_default().to_string();
+        if let Some((field, _)) = pg_err
+            .detail()
+            .and_then(|d| d.strip_prefix("Key ("))
+            .and_then(|d| d.split_once(")="))
+        {
+            return (table, field.to_string());
+        }
+        return (table, pg_err.constraint().unwrap_or_default().to_string());
+    }
+
+    // SQLite: message is `UNIQUE constraint failed: users.email`
+    if let Some((_, columns)) = db_err.message().split_once("constraint failed: ") {
+        let first = columns.split(", ").next().unwrap_or(columns);
+        return match first.split_once('.') {
+            Some((table, field)) => (table.to_string(), field.to_string()),
+            None => (String::new(), first.to_string()),
+        };
+    }
+
+    (String::new(), db_err.message().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_maps_to_field_specific_code() {
+        let err = DbError::duplicate("users", "email");
+        assert!(matches!(err, DbError::Duplicate { ref constraint, .. } if constraint == "users_email_key"));
+        assert_eq!(err.http_status(), StatusCode::CONFLICT);
+        assert_eq!(err.error_code(), "EMAIL_EXISTS");
+
+        let err = DbError::duplicate("admins", "user_id");
+        assert_eq!(err.error_code(), "ALREADY_EXISTS");
+    }
+
+    #[test]
+    fn test_transient_errors_are_unavailable() {
+        for err in [DbError::SerializationFailure, DbError::Timeout, DbError::Connection("refused".into())] {
+            assert_eq!(err.http_status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+    }
+
+    #[test]
+    fn test_internal_errors_hide_details() {
+        let err = DbError::Query("relation \"users\" does not exist".into());
+        assert_eq!(err.http_status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.public_message(), "Internal server error");
+    }
+}
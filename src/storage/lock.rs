@@ -0,0 +1,31 @@
+//! Locks that keep a background job from running on two instances at once.
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use super::DbError;
+
+/// Held while a job runs. Dropping it without `release` also gives the lock up.
+#[async_trait]
+pub trait JobLock: Send {
+    async fn release(self: Box<Self>) -> Result<(), DbError>;
+}
+
+/// Lock for stores that belong to a single process, where there is no other
+/// instance to exclude.
+pub struct LocalJobLock;
+
+#[async_trait]
+impl JobLock for LocalJobLock {
+    async fn release(self: Box<Self>) -> Result<(), DbError> {
+        Ok(())
+    }
+}
+
+/// Stable 64-bit key for `job`, used as the Postgres advisory lock id.
+pub fn lock_key(job: &str) -> i64 {
+    let digest = Sha256::digest(format!("learner:job:{}", job).as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    i64::from_be_bytes(bytes)
+}
@@ -1,14 +1,23 @@
-use std::collections::HashMap;
-use std::sync::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
-use super::{DbError, StorageLayer};
-use crate::users::model::{User, CreateUserRequest};
-use crate::auth::model::{UserAccount, AccountLevel, AccountStatus};
-use crate::admin::model::{Admin, AdminRole};
+use super::{DbError, StorageLayer, Transaction};
+use super::snapshot::{Persistence, Recovered, Snapshot, WalRecord, SNAPSHOT_VERSION};
+use crate::users::model::{
+    User, CreateUserRequest, UpdateUserRequest, UserFilter, UserSort, UserSortField, SortDirection, PageRequest,
+    UserPage, UserSummary,
+};
+use crate::auth::model::{
+    UserAccount, CreateAccountRequest, UpdateAccountRequest, LoginEvent,
+};
+use crate::admin::model::{Admin, AdminRole, CreateAdminRequest, UpdateAdminRequest};
 use crate::validation::model::AuthToken;
 
 pub struct MemoryStorage {
@@ -16,6 +25,8 @@ pub struct MemoryStorage {
     accounts: RwLock<HashMap<Uuid, UserAccount>>,
     admins: RwLock<HashMap<Uuid, Admin>>,
     tokens: RwLock<HashMap<String, AuthToken>>,
+    login_events: RwLock<Vec<LoginEvent>>,
+    persistence: Option<Persistence>,
 }
 
 impl MemoryStorage {
@@ -25,62 +36,136 @@ impl MemoryStorage {
             accounts: RwLock::new(HashMap::new()),
             admins: RwLock::new(HashMap::new()),
             tokens: RwLock::new(HashMap::new()),
+            login_events: RwLock::new(Vec::new()),
+            persistence: None,
         }
     }
 
-    pub fn with_default_admin(email: &str, password_hash: &str) -> Self {
-        let storage = Self::new();
+    /// Opens a store persisted at `snapshot_path` (see `storage::snapshot`),
+    /// restoring whatever was saved there. The recovered state is immediately
+    /// written back as a fresh snapshot, which also drops a torn log tail.
+    pub fn open(snapshot_path: impl Into<PathBuf>) -> Result<Self, DbError> {
+        let (persistence, recovered) = Persistence::open(snapshot_path)?;
 
-        let user_id = Uuid::new_v4();
-        let now = Utc::now();
+        let mut storage = Self::new();
+        storage.restore(recovered);
+        storage.persistence = Some(persistence);
+        storage.snapshot()?;
 
-        let user = User {
-            id: user_id,
-            email: email.to_string(),
-            password_hash: password_hash.to_string(),
-            username: "admin".to_string(),
-            first_name: "Admin".to_string(),
-            last_name: "User".to_string(),
-            is_active: true,
-            created_at: now,
-            updated_at: now,
-            last_login: None,
-        };
-        storage.users.write().unwrap().insert(user_id, user);
-
-        let account = UserAccount {
-            id: Uuid::new_v4(),
-            user_id,
-            account_level: AccountLevel::Enterprise,
-            account_status: AccountStatus::Active,
-            capabilities: vec![
-                "create_website".to_string(),
-                "manage_components".to_string(),
-                "send_emails".to_string(),
-                "access_analytics".to_string(),
-                "api_access".to_string(),
-                "priority_support".to_string(),
-            ],
-            status_reason: None,
-            status_changed_at: None,
-            status_changed_by: None,
-            created_at: now,
-            updated_at: now,
+        Ok(storage)
+    }
+
+    fn restore(&mut self, recovered: Recovered) {
+        let users = self.users.get_mut().unwrap();
+        let accounts = self.accounts.get_mut().unwrap();
+        let admins = self.admins.get_mut().unwrap();
+        let tokens = self.tokens.get_mut().unwrap();
+        let login_events = self.login_events.get_mut().unwrap();
+
+        if let Some(snapshot) = recovered.snapshot {
+            users.extend(snapshot.users.into_iter().map(|u| (u.id, u)));
+            accounts.extend(snapshot.accounts.into_iter().map(|a| (a.user_id, a)));
+            admins.extend(snapshot.admins.into_iter().map(|a| (a.user_id, a)));
+            tokens.extend(snapshot.tokens.into_iter().map(|t| (t.token_hash.clone(), t)));
+            *login_events = snapshot.login_events;
+        }
+
+        let mut seen_events: HashSet<Uuid> = login_events.iter().map(|e| e.id).collect();
+        for record in recovered.log {
+            match record {
+                WalRecord::User(user) => {
+                    users.insert(user.id, user);
+                }
+                WalRecord::Account(account) => {
+                    accounts.insert(account.user_id, account);
+                }
+                WalRecord::Admin(admin) => {
+                    admins.insert(admin.user_id, admin);
+                }
+                WalRecord::AdminRemoved(user_id) => {
+                    admins.remove(&user_id);
+                }
+                WalRecord::Token(token) => {
+                    tokens.insert(token.token_hash.clone(), token);
+                }
+                WalRecord::TokensPurged(now) => {
+                    tokens.retain(|_, t| t.expires_at > now);
+                }
+                WalRecord::UsersPurged(before) => {
+                    purge_users(users, accounts, admins, tokens, login_events, before);
+                }
+                WalRecord::LoginEvent(event) => {
+                    if seen_events.insert(event.id) {
+                        login_events.push(event);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes every map to the snapshot file and truncates the write-ahead
+    /// log. Does nothing for a store that isn't persisted.
+    pub fn snapshot(&self) -> Result<(), DbError> {
+        let Some(persistence) = &self.persistence else {
+            return Ok(());
         };
-        storage.accounts.write().unwrap().insert(user_id, account);
-
-        let admin = Admin {
-            id: Uuid::new_v4(),
-            user_id,
-            role: AdminRole::SuperAdmin,
-            permissions: vec!["*".to_string()],
-            created_at: now,
-            updated_at: now,
-            created_by: None,
+
+        let users = self.users.read().unwrap();
+        let accounts = self.accounts.read().unwrap();
+        let admins = self.admins.read().unwrap();
+        let tokens = self.tokens.read().unwrap();
+        let login_events = self.login_events.read().unwrap();
+
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            taken_at: Utc::now(),
+            users: users.values().cloned().collect(),
+            accounts: accounts.values().cloned().collect(),
+            admins: admins.values().cloned().collect(),
+            tokens: tokens.values().cloned().collect(),
+            login_events: login_events.clone(),
         };
-        storage.admins.write().unwrap().insert(user_id, admin);
 
-        storage
+        // Writers append to the log while holding their map lock, so taking
+        // the log lock before releasing ours means every write is either in
+        // this snapshot or lands in the log after it is truncated.
+        let mut wal = persistence.lock_wal();
+        drop((users, accounts, admins, tokens, login_events));
+
+        persistence.replace_snapshot(&snapshot, &mut wal)
+    }
+
+    /// Snapshots the store every `every` while there are unsaved writes.
+    pub fn spawn_periodic_snapshots(self: Arc<Self>, every: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let pending = self.persistence.as_ref().is_some_and(|p| p.has_pending_writes());
+                if !pending {
+                    continue;
+                }
+
+                let storage = self.clone();
+                match tokio::task::spawn_blocking(move || storage.snapshot()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => tracing::error!(error = %e, "failed to snapshot in-memory storage"),
+                    Err(e) => tracing::error!(error = %e, "snapshot task failed"),
+                }
+            }
+        })
+    }
+
+    /// Appends `records` to the write-ahead log, if the store is persisted.
+    /// Called with the affected map's lock held, before the write is applied.
+    fn log(&self, records: &[WalRecord]) -> Result<(), DbError> {
+        match &self.persistence {
+            Some(persistence) => persistence.append(records),
+            None => Ok(()),
+        }
     }
 }
 
@@ -90,63 +175,520 @@ impl Default for MemoryStorage {
     }
 }
 
+fn new_user(req: &CreateUserRequest, password_hash: &str) -> User {
+    let now = Utc::now();
+    User {
+        id: Uuid::new_v4(),
+        email: req.email.clone(),
+        password_hash: password_hash.to_string(),
+        username: req.username.clone(),
+        first_name: req.first_name.clone(),
+        last_name: req.last_name.clone(),
+        is_active: true,
+        created_at: now,
+        updated_at: now,
+        last_login: None,
+        deleted_at: None,
+        version: 1,
+    }
+}
+
+fn new_account(req: &CreateAccountRequest) -> UserAccount {
+    let now = Utc::now();
+    UserAccount {
+        id: Uuid::new_v4(),
+        user_id: req.user_id,
+        account_level: req.account_level.clone(),
+        account_status: req.account_status.clone(),
+        capabilities: req.capabilities.clone(),
+        status_reason: None,
+        status_changed_at: None,
+        status_changed_by: None,
+        created_at: now,
+        updated_at: now,
+        version: 1,
+    }
+}
+
+fn new_admin(req: &CreateAdminRequest) -> Admin {
+    let now = Utc::now();
+    Admin {
+        id: Uuid::new_v4(),
+        user_id: req.user_id,
+        role: req.role.clone(),
+        permissions: req.permissions.clone(),
+        created_at: now,
+        updated_at: now,
+        created_by: req.created_by,
+        version: 1,
+    }
+}
+
+/// Mirrors the unique indexes on `users.email` and `users.username`, which
+/// only cover users that aren't soft-deleted.
+fn user_conflict<'a>(existing: impl Iterator<Item = &'a User>, user: &User) -> Option<DbError> {
+    let mut email_taken = false;
+    let mut username_taken = false;
+    for other in existing.filter(|u| u.id != user.id && u.deleted_at.is_none()) {
+        email_taken |= other.email == user.email;
+        username_taken |= other.username == user.username;
+    }
+
+    if email_taken {
+        Some(DbError::duplicate("users", "email"))
+    } else if username_taken {
+        Some(DbError::duplicate("users", "username"))
+    } else {
+        None
+    }
+}
+
+fn apply_admin_changes(admin: &Admin, changes: &UpdateAdminRequest) -> Admin {
+    let mut updated = admin.clone();
+    if let Some(role) = &changes.role {
+        updated.role = role.clone();
+    }
+    if let Some(permissions) = &changes.permissions {
+        updated.permissions = permissions.clone();
+    }
+    updated.updated_at = Utc::now();
+    updated.version += 1;
+    updated
+}
+
+/// `(user_id, version)` of every superadmin whose user isn't deleted, sorted.
+fn superadmin_versions(users: &HashMap<Uuid, User>, admins: &HashMap<Uuid, Admin>) -> Vec<(Uuid, i64)> {
+    let mut found: Vec<(Uuid, i64)> = admins
+        .values()
+        .filter(|admin| {
+            admin.role == AdminRole::SuperAdmin
+                && users.get(&admin.user_id).is_some_and(|u| u.deleted_at.is_none())
+        })
+        .map(|admin| (admin.user_id, admin.version))
+        .collect();
+    found.sort();
+    found
+}
+
+fn check_version(current: i64, expected: i64) -> Result<(), DbError> {
+    if current == expected {
+        Ok(())
+    } else {
+        Err(DbError::Conflict)
+    }
+}
+
+/// Removes users soft-deleted at or before `before` and everything that
+/// belongs to them, the way the database's foreign keys would.
+fn purge_users(
+    users: &mut HashMap<Uuid, User>,
+    accounts: &mut HashMap<Uuid, UserAccount>,
+    admins: &mut HashMap<Uuid, Admin>,
+    tokens: &mut HashMap<String, AuthToken>,
+    login_events: &mut Vec<LoginEvent>,
+    before: DateTime<Utc>,
+) -> u64 {
+    let purged: HashSet<Uuid> = users
+        .values()
+        .filter(|u| u.deleted_at.is_some_and(|at| at <= before))
+        .map(|u| u.id)
+        .collect();
+    if purged.is_empty() {
+        return 0;
+    }
+
+    users.retain(|id, _| !purged.contains(id));
+    accounts.retain(|id, _| !purged.contains(id));
+    admins.retain(|id, _| !purged.contains(id));
+    tokens.retain(|_, t| !purged.contains(&t.user_id));
+    login_events.retain(|e| !e.user_id.is_some_and(|id| purged.contains(&id)));
+
+    for account in accounts.values_mut() {
+        if account.status_changed_by.is_some_and(|id| purged.contains(&id)) {
+            account.status_changed_by = None;
+        }
+    }
+    for admin in admins.values_mut() {
+        if admin.created_by.is_some_and(|id| purged.contains(&id)) {
+            admin.created_by = None;
+        }
+    }
+    purged.len() as u64
+}
+
+/// Stages writes locally and applies them all at once on commit, re-checking
+/// constraints against anything committed in the meantime.
+pub struct MemoryTransaction<'a> {
+    storage: &'a MemoryStorage,
+    users: Vec<User>,
+    accounts: Vec<UserAccount>,
+    admins: Vec<Admin>,
+    /// Updated admins, each with the committed version it was based on.
+    admin_updates: Vec<(i64, Admin)>,
+    /// The superadmins `superadmin_ids` saw, which must be unchanged at commit.
+    superadmins_read: Option<Vec<(Uuid, i64)>>,
+}
+
+impl MemoryTransaction<'_> {
+    fn user_exists(&self, user_id: Uuid) -> bool {
+        self.users.iter().any(|u| u.id == user_id)
+            || self.storage.users.read().unwrap().contains_key(&user_id)
+    }
+}
+
+#[async_trait]
+impl Transaction for MemoryTransaction<'_> {
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<User>, DbError> {
+        if let Some(user) = self.users.iter().find(|u| u.email == email) {
+            return Ok(Some(user.clone()));
+        }
+        self.storage.get_user_by_email(email).await
+    }
+
+    async fn create_user(&mut self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
+        let user = new_user(req, password_hash);
+        {
+            let users = self.storage.users.read().unwrap();
+            if let Some(err) = user_conflict(users.values().chain(self.users.iter()), &user) {
+                return Err(err);
+            }
+        }
+
+        self.users.push(user.clone());
+        Ok(user)
+    }
+
+    async fn create_account(&mut self, req: &CreateAccountRequest) -> Result<UserAccount, DbError> {
+        if !self.user_exists(req.user_id) {
+            return Err(DbError::foreign_key("user_accounts", "user_id"));
+        }
+        if self.accounts.iter().any(|a| a.user_id == req.user_id)
+            || self.storage.accounts.read().unwrap().contains_key(&req.user_id)
+        {
+            return Err(DbError::duplicate("user_accounts", "user_id"));
+        }
+
+        let account = new_account(req);
+        self.accounts.push(account.clone());
+        Ok(account)
+    }
+
+    async fn create_admin(&mut self, req: &CreateAdminRequest) -> Result<Admin, DbError> {
+        if !self.user_exists(req.user_id) {
+            return Err(DbError::foreign_key("admins", "user_id"));
+        }
+        if self.admins.iter().any(|a| a.user_id == req.user_id)
+            || self.storage.admins.read().unwrap().contains_key(&req.user_id)
+        {
+            return Err(DbError::duplicate("admins", "user_id"));
+        }
+
+        let admin = new_admin(req);
+        self.admins.push(admin.clone());
+        Ok(admin)
+    }
+
+    async fn superadmin_ids(&mut self) -> Result<Vec<Uuid>, DbError> {
+        let users = self.storage.users.read().unwrap();
+        let admins = self.storage.admins.read().unwrap();
+        let committed = superadmin_versions(&users, &admins);
+
+        let mut ids: Vec<Uuid> = committed
+            .iter()
+            .map(|(id, _)| *id)
+            .filter(|id| !self.admin_updates.iter().any(|(_, a)| a.user_id == *id))
+            .collect();
+        let staged = self.admins.iter().chain(self.admin_updates.iter().map(|(_, a)| a));
+        ids.extend(staged.filter(|a| a.role == AdminRole::SuperAdmin).map(|a| a.user_id));
+
+        self.superadmins_read = Some(committed);
+        Ok(ids)
+    }
+
+    async fn update_admin(&mut self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        let (base, current) = match self.admin_updates.iter().find(|(_, a)| a.user_id == user_id) {
+            Some((base, admin)) => (*base, admin.clone()),
+            None => {
+                let admins = self.storage.admins.read().unwrap();
+                let admin = admins.get(&user_id).ok_or(DbError::NotFound)?;
+                (admin.version, admin.clone())
+            }
+        };
+        check_version(current.version, expected_version)?;
+
+        let updated = apply_admin_changes(&current, changes);
+        self.admin_updates.retain(|(_, a)| a.user_id != user_id);
+        self.admin_updates.push((base, updated.clone()));
+        Ok(updated)
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DbError> {
+        let mut users = self.storage.users.write().unwrap();
+        let mut accounts = self.storage.accounts.write().unwrap();
+        let mut admins = self.storage.admins.write().unwrap();
+
+        if self.superadmins_read.as_ref().is_some_and(|read| *read != superadmin_versions(&users, &admins)) {
+            return Err(DbError::SerializationFailure);
+        }
+        for (base, admin) in &self.admin_updates {
+            check_version(admins.get(&admin.user_id).map_or(-1, |a| a.version), *base)?;
+        }
+
+        for (i, user) in self.users.iter().enumerate() {
+            if let Some(err) = user_conflict(users.values().chain(&self.users[..i]), user) {
+                return Err(err);
+            }
+        }
+        let staged_user = |id: Uuid| self.users.iter().any(|u| u.id == id);
+        for account in &self.accounts {
+            if !users.contains_key(&account.user_id) && !staged_user(account.user_id) {
+                return Err(DbError::foreign_key("user_accounts", "user_id"));
+            }
+            if accounts.contains_key(&account.user_id) {
+                return Err(DbError::duplicate("user_accounts", "user_id"));
+            }
+        }
+        for admin in &self.admins {
+            if !users.contains_key(&admin.user_id) && !staged_user(admin.user_id) {
+                return Err(DbError::foreign_key("admins", "user_id"));
+            }
+            if admins.contains_key(&admin.user_id) {
+                return Err(DbError::duplicate("admins", "user_id"));
+            }
+        }
+
+        let records: Vec<WalRecord> = self.users.iter().cloned().map(WalRecord::User)
+            .chain(self.accounts.iter().cloned().map(WalRecord::Account))
+            .chain(self.admins.iter().cloned().map(WalRecord::Admin))
+            .chain(self.admin_updates.iter().map(|(_, admin)| WalRecord::Admin(admin.clone())))
+            .collect();
+        self.storage.log(&records)?;
+
+        for user in &self.users {
+            users.insert(user.id, user.clone());
+        }
+        for account in &self.accounts {
+            accounts.insert(account.user_id, account.clone());
+        }
+        for admin in self.admins.iter().chain(self.admin_updates.iter().map(|(_, admin)| admin)) {
+            admins.insert(admin.user_id, admin.clone());
+        }
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DbError> {
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl StorageLayer for MemoryStorage {
     async fn health_check(&self) -> bool {
         true
     }
 
+    async fn close(&self) -> Result<(), DbError> {
+        self.snapshot()
+    }
+
+    async fn begin(&self) -> Result<Box<dyn Transaction + '_>, DbError> {
+        Ok(Box::new(MemoryTransaction {
+            storage: self,
+            users: Vec::new(),
+            accounts: Vec::new(),
+            admins: Vec::new(),
+            admin_updates: Vec::new(),
+            superadmins_read: None,
+        }))
+    }
+
     async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
         let users = self.users.read().unwrap();
-        Ok(users.values().find(|u| u.email == email).cloned())
+        Ok(users.values().find(|u| u.email == email && u.deleted_at.is_none()).cloned())
     }
 
     async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, DbError> {
         let users = self.users.read().unwrap();
-        Ok(users.get(&id).cloned())
+        Ok(users.get(&id).filter(|u| u.deleted_at.is_none()).cloned())
     }
 
     async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, DbError> {
         let users = self.users.read().unwrap();
-        Ok(users.values().find(|u| u.username == username).cloned())
+        Ok(users.values().find(|u| u.username == username && u.deleted_at.is_none()).cloned())
     }
 
     async fn create_user(&self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
         let mut users = self.users.write().unwrap();
 
-        if users.values().any(|u| u.email == req.email) {
-            return Err(DbError::Duplicate("email".to_string()));
+        let user = new_user(req, password_hash);
+        if let Some(err) = user_conflict(users.values(), &user) {
+            return Err(err);
+        }
+
+        self.log(&[WalRecord::User(user.clone())])?;
+        users.insert(user.id, user.clone());
+        Ok(user)
+    }
+
+    async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError> {
+        let mut users = self.users.write().unwrap();
+        let user = users
+            .get_mut(&user_id)
+            .filter(|u| u.deleted_at.is_none())
+            .ok_or(DbError::NotFound)?;
+
+        let mut updated = user.clone();
+        updated.last_login = Some(Utc::now());
+        updated.updated_at = Utc::now();
+
+        self.log(&[WalRecord::User(updated.clone())])?;
+        *user = updated;
+        Ok(())
+    }
+
+    async fn update_user(&self, user_id: Uuid, changes: &UpdateUserRequest, expected_version: i64) -> Result<User, DbError> {
+        let mut users = self.users.write().unwrap();
+        let user = users
+            .get(&user_id)
+            .filter(|u| u.deleted_at.is_none())
+            .ok_or(DbError::NotFound)?;
+        check_version(user.version, expected_version)?;
+
+        let mut updated = user.clone();
+        if let Some(email) = &changes.email {
+            updated.email = email.clone();
+        }
+        if let Some(username) = &changes.username {
+            updated.username = username.clone();
+        }
+        if let Some(first_name) = &changes.first_name {
+            updated.first_name = first_name.clone();
+        }
+        if let Some(last_name) = &changes.last_name {
+            updated.last_name = last_name.clone();
+        }
+        if let Some(is_active) = changes.is_active {
+            updated.is_active = is_active;
+        }
+        if let Some(password_hash) = &changes.password_hash {
+            updated.password_hash = password_hash.clone();
         }
+        updated.updated_at = Utc::now();
+        updated.version += 1;
 
-        if users.values().any(|u| u.username == req.username) {
-            return Err(DbError::Duplicate("username".to_string()));
+        if let Some(err) = user_conflict(users.values(), &updated) {
+            return Err(err);
         }
 
+        self.log(&[WalRecord::User(updated.clone())])?;
+        users.insert(user_id, updated.clone());
+        Ok(updated)
+    }
+
+    async fn soft_delete_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        let mut users = self.users.write().unwrap();
+        let user = users
+            .get_mut(&user_id)
+            .filter(|u| u.deleted_at.is_none())
+            .ok_or(DbError::NotFound)?;
+
         let now = Utc::now();
-        let user = User {
-            id: Uuid::new_v4(),
-            email: req.email.clone(),
-            password_hash: password_hash.to_string(),
-            username: req.username.clone(),
-            first_name: req.first_name.clone(),
-            last_name: req.last_name.clone(),
-            is_active: true,
-            created_at: now,
-            updated_at: now,
-            last_login: None,
+        let updated = User { deleted_at: Some(now), updated_at: now, version: user.version + 1, ..user.clone() };
+
+        self.log(&[WalRecord::User(updated.clone())])?;
+        *user = updated;
+        Ok(())
+    }
+
+    async fn restore_user(&self, user_id: Uuid) -> Result<User, DbError> {
+        let mut users = self.users.write().unwrap();
+        let user = users
+            .get(&user_id)
+            .filter(|u| u.deleted_at.is_some())
+            .ok_or(DbError::NotFound)?;
+
+        let restored = User {
+            deleted_at: None,
+            updated_at: Utc::now(),
+            version: user.version + 1,
+            ..user.clone()
         };
+        if let Some(err) = user_conflict(users.values(), &restored) {
+            return Err(err);
+        }
 
-        users.insert(user.id, user.clone());
-        Ok(user)
+        self.log(&[WalRecord::User(restored.clone())])?;
+        users.insert(user_id, restored.clone());
+        Ok(restored)
     }
 
-    async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError> {
+    async fn purge_deleted_users(&self, before: DateTime<Utc>) -> Result<u64, DbError> {
         let mut users = self.users.write().unwrap();
-        if let Some(user) = users.get_mut(&user_id) {
-            user.last_login = Some(Utc::now());
-            user.updated_at = Utc::now();
+        let mut accounts = self.accounts.write().unwrap();
+        let mut admins = self.admins.write().unwrap();
+        let mut tokens = self.tokens.write().unwrap();
+        let mut login_events = self.login_events.write().unwrap();
+
+        if !users.values().any(|u| u.deleted_at.is_some_and(|at| at <= before)) {
+            return Ok(0);
         }
-        Ok(())
+
+        self.log(&[WalRecord::UsersPurged(before)])?;
+        Ok(purge_users(&mut users, &mut accounts, &mut admins, &mut tokens, &mut login_events, before))
+    }
+
+    async fn list_users(&self, filter: &UserFilter, sort: UserSort, page: PageRequest) -> Result<UserPage, DbError> {
+        let users = self.users.read().unwrap();
+        let accounts = self.accounts.read().unwrap();
+        let search = filter.search_term().map(str::to_lowercase);
+
+        let mut matches: Vec<UserSummary> = users
+            .values()
+            .map(|user| {
+                let account = accounts.get(&user.id);
+                UserSummary {
+                    user: user.clone(),
+                    account_level: account.map(|a| a.account_level.clone()),
+                    account_status: account.map(|a| a.account_status.clone()),
+                }
+            })
+            .filter(|s| s.user.deleted_at.is_some() == filter.deleted)
+            .filter(|s| filter.status.is_none() || s.account_status == filter.status)
+            .filter(|s| filter.account_level.is_none() || s.account_level == filter.account_level)
+            .filter(|s| filter.created_after.is_none_or(|t| s.user.created_at >= t))
+            .filter(|s| filter.created_before.is_none_or(|t| s.user.created_at < t))
+            .filter(|s| {
+                search.as_deref().is_none_or(|term| {
+                    let user = &s.user;
+                    user.email.to_lowercase().contains(term)
+                        || user.username.to_lowercase().contains(term)
+                        || format!("{} {}", user.first_name, user.last_name).to_lowercase().contains(term)
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            let (a, b) = (&a.user, &b.user);
+            let ordering = match sort.field {
+                UserSortField::CreatedAt => a.created_at.cmp(&b.created_at),
+                UserSortField::Email => a.email.cmp(&b.email),
+                UserSortField::Username => a.username.cmp(&b.username),
+            };
+            let ordering = ordering.then_with(|| a.id.cmp(&b.id));
+            match sort.direction {
+                SortDirection::Asc => ordering,
+                SortDirection::Desc => ordering.reverse(),
+            }
+        });
+
+        let total = matches.len() as i64;
+        let users = matches
+            .into_iter()
+            .skip(page.offset() as usize)
+            .take(page.per_page as usize)
+            .collect();
+
+        Ok(UserPage { users, total })
     }
 
     async fn get_account_by_user_id(&self, user_id: Uuid) -> Result<Option<UserAccount>, DbError> {
@@ -155,26 +697,49 @@ impl StorageLayer for MemoryStorage {
     }
 
     async fn create_account(&self, user_id: Uuid) -> Result<UserAccount, DbError> {
+        if !self.users.read().unwrap().contains_key(&user_id) {
+            return Err(DbError::foreign_key("user_accounts", "user_id"));
+        }
+
         let mut accounts = self.accounts.write().unwrap();
 
-        let now = Utc::now();
-        let account = UserAccount {
-            id: Uuid::new_v4(),
-            user_id,
-            account_level: AccountLevel::Free,
-            account_status: AccountStatus::Pending,
-            capabilities: vec![],
-            status_reason: None,
-            status_changed_at: None,
-            status_changed_by: None,
-            created_at: now,
-            updated_at: now,
-        };
+        if accounts.contains_key(&user_id) {
+            return Err(DbError::duplicate("user_accounts", "user_id"));
+        }
 
+        let account = new_account(&CreateAccountRequest::default_for(user_id));
+        self.log(&[WalRecord::Account(account.clone())])?;
         accounts.insert(user_id, account.clone());
         Ok(account)
     }
 
+    async fn update_account(&self, user_id: Uuid, changes: &UpdateAccountRequest, expected_version: i64) -> Result<UserAccount, DbError> {
+        let mut accounts = self.accounts.write().unwrap();
+        let account = accounts.get_mut(&user_id).ok_or(DbError::NotFound)?;
+        check_version(account.version, expected_version)?;
+
+        let now = Utc::now();
+        let mut updated = account.clone();
+        if let Some(level) = &changes.account_level {
+            updated.account_level = level.clone();
+        }
+        if let Some(capabilities) = &changes.capabilities {
+            updated.capabilities = capabilities.clone();
+        }
+        if let Some(status) = &changes.account_status {
+            updated.account_status = status.clone();
+            updated.status_reason = changes.status_reason.clone();
+            updated.status_changed_at = Some(now);
+            updated.status_changed_by = changes.changed_by;
+        }
+        updated.updated_at = now;
+        updated.version += 1;
+
+        self.log(&[WalRecord::Account(updated.clone())])?;
+        *account = updated.clone();
+        Ok(updated)
+    }
+
     async fn get_admin_by_user_id(&self, user_id: Uuid) -> Result<Option<Admin>, DbError> {
         let admins = self.admins.read().unwrap();
         Ok(admins.get(&user_id).cloned())
@@ -185,8 +750,37 @@ impl StorageLayer for MemoryStorage {
         Ok(admins.contains_key(&user_id))
     }
 
+    async fn has_superadmin(&self) -> Result<bool, DbError> {
+        let users = self.users.read().unwrap();
+        let admins = self.admins.read().unwrap();
+        Ok(!superadmin_versions(&users, &admins).is_empty())
+    }
+
+    async fn update_admin(&self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        let mut admins = self.admins.write().unwrap();
+        let admin = admins.get_mut(&user_id).ok_or(DbError::NotFound)?;
+        check_version(admin.version, expected_version)?;
+
+        let updated = apply_admin_changes(admin, changes);
+        self.log(&[WalRecord::Admin(updated.clone())])?;
+        *admin = updated.clone();
+        Ok(updated)
+    }
+
+    async fn delete_admin(&self, user_id: Uuid) -> Result<(), DbError> {
+        let mut admins = self.admins.write().unwrap();
+        if !admins.contains_key(&user_id) {
+            return Err(DbError::NotFound);
+        }
+
+        self.log(&[WalRecord::AdminRemoved(user_id)])?;
+        admins.remove(&user_id);
+        Ok(())
+    }
+
     async fn store_token(&self, token: &AuthToken) -> Result<(), DbError> {
         let mut tokens = self.tokens.write().unwrap();
+        self.log(&[WalRecord::Token(token.clone())])?;
         tokens.insert(token.token_hash.clone(), token.clone());
         Ok(())
     }
@@ -199,7 +793,11 @@ impl StorageLayer for MemoryStorage {
     async fn revoke_token(&self, token_hash: &str) -> Result<(), DbError> {
         let mut tokens = self.tokens.write().unwrap();
         if let Some(token) = tokens.get_mut(token_hash) {
-            token.revoked_at = Some(Utc::now());
+            let mut revoked = token.clone();
+            revoked.revoked_at = Some(Utc::now());
+
+            self.log(&[WalRecord::Token(revoked.clone())])?;
+            *token = revoked;
         }
         Ok(())
     }
@@ -207,11 +805,68 @@ impl StorageLayer for MemoryStorage {
     async fn revoke_all_user_tokens(&self, user_id: Uuid) -> Result<(), DbError> {
         let mut tokens = self.tokens.write().unwrap();
         let now = Utc::now();
-        for token in tokens.values_mut() {
-            if token.user_id == user_id && token.revoked_at.is_none() {
-                token.revoked_at = Some(now);
-            }
+        let revoked: Vec<AuthToken> = tokens
+            .values()
+            .filter(|t| t.user_id == user_id && t.revoked_at.is_none())
+            .map(|t| AuthToken { revoked_at: Some(now), ..t.clone() })
+            .collect();
+
+        let records: Vec<WalRecord> = revoked.iter().cloned().map(WalRecord::Token).collect();
+        self.log(&records)?;
+        for token in revoked {
+            tokens.insert(token.token_hash.clone(), token);
         }
         Ok(())
     }
+
+    async fn purge_expired_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let mut tokens = self.tokens.write().unwrap();
+        let expired = tokens.values().filter(|t| t.expires_at <= now).count();
+        if expired == 0 {
+            return Ok(0);
+        }
+
+        self.log(&[WalRecord::TokensPurged(now)])?;
+        tokens.retain(|_, t| t.expires_at > now);
+        Ok(expired as u64)
+    }
+
+    async fn count_active_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let tokens = self.tokens.read().unwrap();
+        Ok(tokens.values().filter(|t| t.revoked_at.is_none() && t.expires_at > now).count() as u64)
+    }
+
+    async fn purge_expired_validation_keys(&self, _now: DateTime<Utc>) -> Result<u64, DbError> {
+        // Validation keys are never persisted here; they only live in the
+        // `ValidationStore`.
+        Ok(0)
+    }
+
+    async fn record_login_event(&self, event: &LoginEvent) -> Result<(), DbError> {
+        let mut events = self.login_events.write().unwrap();
+        self.log(&[WalRecord::LoginEvent(event.clone())])?;
+        events.push(event.clone());
+        Ok(())
+    }
+
+    async fn get_login_events(&self, user_id: Uuid, limit: i64) -> Result<Vec<LoginEvent>, DbError> {
+        let events = self.login_events.read().unwrap();
+        let mut matching: Vec<LoginEvent> = events
+            .iter()
+            .filter(|e| e.user_id == Some(user_id))
+            .cloned()
+            .collect();
+        matching.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+        matching.truncate(limit.max(0) as usize);
+        Ok(matching)
+    }
+
+    async fn is_known_device(&self, user_id: Uuid, fingerprint: &str) -> Result<bool, DbError> {
+        let events = self.login_events.read().unwrap();
+        Ok(events.iter().any(|e| {
+            e.user_id == Some(user_id)
+                && e.success
+                && e.device_fingerprint.as_deref() == Some(fingerprint)
+        }))
+    }
 }
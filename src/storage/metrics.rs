@@ -0,0 +1,409 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info_span, Instrument, Span};
+use uuid::Uuid;
+
+use super::{CacheStats, DbError, JobLock, MigrationStatus, PoolStats, StorageLayer, Transaction};
+use crate::users::model::{User, CreateUserRequest, UpdateUserRequest, UserFilter, UserSort, PageRequest, UserPage};
+use crate::auth::model::{UserAccount, UpdateAccountRequest, LoginEvent};
+use crate::admin::model::{Admin, UpdateAdminRequest};
+use crate::validation::model::AuthToken;
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 11] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+#[derive(Debug, Default, Clone)]
+struct MethodMetrics {
+    calls: u64,
+    /// Per-bucket counts; the last slot counts calls slower than every bound.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    total_seconds: f64,
+    errors: BTreeMap<&'static str, u64>,
+}
+
+impl MethodMetrics {
+    /// Upper bound of the bucket containing the `q` quantile, in
+    /// milliseconds. `None` when it falls in the overflow bucket.
+    fn quantile_ms(&self, q: f64) -> Option<f64> {
+        let target = (self.calls as f64 * q).ceil() as u64;
+        let mut seen = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target.max(1) {
+                return LATENCY_BUCKETS.get(i).map(|bound| bound * 1000.0);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodStats {
+    pub method: &'static str,
+    pub calls: u64,
+    pub errors: u64,
+    pub error_kinds: Vec<(&'static str, u64)>,
+    pub mean_ms: f64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+}
+
+/// Call counts, latency histograms and error counts per `StorageLayer`
+/// method, shared between `MetricsStorage` and whatever reports on it.
+#[derive(Debug, Default)]
+pub struct StorageMetrics {
+    methods: Mutex<HashMap<&'static str, MethodMetrics>>,
+}
+
+impl StorageMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, method: &'static str, elapsed: Duration, error: Option<&DbError>) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        let mut methods = self.methods.lock().unwrap();
+        let metrics = methods.entry(method).or_default();
+        metrics.calls += 1;
+        metrics.buckets[bucket] += 1;
+        metrics.total_seconds += seconds;
+        if let Some(error) = error {
+            *metrics.errors.entry(error.kind()).or_default() += 1;
+        }
+    }
+
+    /// Per-method summary, sorted by method name.
+    pub fn stats(&self) -> Vec<MethodStats> {
+        let methods = self.methods.lock().unwrap();
+        let mut stats: Vec<MethodStats> = methods
+            .iter()
+            .map(|(method, m)| MethodStats {
+                method,
+                calls: m.calls,
+                errors: m.errors.values().sum(),
+                error_kinds: m.errors.iter().map(|(kind, count)| (*kind, *count)).collect(),
+                mean_ms: if m.calls == 0 { 0.0 } else { m.total_seconds * 1000.0 / m.calls as f64 },
+                p50_ms: m.quantile_ms(0.50),
+                p95_ms: m.quantile_ms(0.95),
+                p99_ms: m.quantile_ms(0.99),
+            })
+            .collect();
+        stats.sort_by_key(|s| s.method);
+        stats
+    }
+
+    /// Appends the metrics in the Prometheus text exposition format.
+    pub fn render_prometheus(&self, out: &mut String) {
+        let methods = self.methods.lock().unwrap();
+        let mut names: Vec<&&'static str> = methods.keys().collect();
+        names.sort();
+
+        out.push_str("# HELP storage_calls_total Storage layer calls by method.\n");
+        out.push_str("# TYPE storage_calls_total counter\n");
+        for name in &names {
+            let _ = writeln!(out, "storage_calls_total{{method=\"{}\"}} {}", name, methods[**name].calls);
+        }
+
+        out.push_str("# HELP storage_errors_total Storage layer errors by method and DbError kind.\n");
+        out.push_str("# TYPE storage_errors_total counter\n");
+        for name in &names {
+            for (kind, count) in &methods[**name].errors {
+                let _ = writeln!(out, "storage_errors_total{{method=\"{}\",kind=\"{}\"}} {}", name, kind, count);
+            }
+        }
+
+        out.push_str("# HELP storage_call_duration_seconds Storage layer call latency.\n");
+        out.push_str("# TYPE storage_call_duration_seconds histogram\n");
+        for name in &names {
+            let m = &methods[**name];
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&m.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "storage_call_duration_seconds_bucket{{method=\"{}\",le=\"{}\"}} {}",
+                    name, bound, cumulative
+                );
+            }
+            let _ = writeln!(out, "storage_call_duration_seconds_bucket{{method=\"{}\",le=\"+Inf\"}} {}", name, m.calls);
+            let _ = writeln!(out, "storage_call_duration_seconds_sum{{method=\"{}\"}} {}", name, m.total_seconds);
+            let _ = writeln!(out, "storage_call_duration_seconds_count{{method=\"{}\"}} {}", name, m.calls);
+        }
+    }
+}
+
+/// Times every call into another store and records it in `StorageMetrics`.
+/// Each call also runs inside a `storage` tracing span carrying the method
+/// name and any identifiers among its arguments; emails, usernames and
+/// token or password hashes are never recorded.
+pub struct MetricsStorage {
+    inner: Arc<dyn StorageLayer>,
+    metrics: Arc<StorageMetrics>,
+}
+
+impl MetricsStorage {
+    pub fn new(inner: Arc<dyn StorageLayer>, metrics: Arc<StorageMetrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    async fn observe<T, F>(&self, method: &'static str, span: Span, call: F) -> Result<T, DbError>
+    where
+        F: Future<Output = Result<T, DbError>>,
+    {
+        let start = Instant::now();
+        let result = call.instrument(span).await;
+        self.metrics.record(method, start.elapsed(), result.as_ref().err());
+        result
+    }
+}
+
+#[async_trait]
+impl StorageLayer for MetricsStorage {
+    async fn health_check(&self) -> bool {
+        let span = info_span!("storage", method = "health_check");
+        let start = Instant::now();
+        let healthy = self.inner.health_check().instrument(span).await;
+        self.metrics.record("health_check", start.elapsed(), None);
+        healthy
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, DbError> {
+        let span = info_span!("storage", method = "migration_status");
+        self.observe("migration_status", span, self.inner.migration_status()).await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.inner.pool_stats()
+    }
+
+    async fn close(&self) -> Result<(), DbError> {
+        self.inner.close().await
+    }
+
+    async fn begin(&self) -> Result<Box<dyn Transaction + '_>, DbError> {
+        let span = info_span!("storage", method = "begin");
+        self.observe("begin", span, self.inner.begin()).await
+    }
+
+    async fn try_lock_job(&self, job: &str) -> Result<Option<Box<dyn JobLock + '_>>, DbError> {
+        let span = info_span!("storage", method = "try_lock_job", job = job);
+        self.observe("try_lock_job", span, self.inner.try_lock_job(job)).await
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
+        let span = info_span!("storage", method = "get_user_by_email");
+        self.observe("get_user_by_email", span, self.inner.get_user_by_email(email)).await
+    }
+
+    async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, DbError> {
+        let span = info_span!("storage", method = "get_user_by_id", user_id = %id);
+        self.observe("get_user_by_id", span, self.inner.get_user_by_id(id)).await
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, DbError> {
+        let span = info_span!("storage", method = "get_user_by_username");
+        self.observe("get_user_by_username", span, self.inner.get_user_by_username(username)).await
+    }
+
+    async fn create_user(&self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
+        let span = info_span!("storage", method = "create_user");
+        self.observe("create_user", span, self.inner.create_user(req, password_hash)).await
+    }
+
+    async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError> {
+        let span = info_span!("storage", method = "update_user_last_login", user_id = %user_id);
+        self.observe("update_user_last_login", span, self.inner.update_user_last_login(user_id)).await
+    }
+
+    async fn list_users(&self, filter: &UserFilter, sort: UserSort, page: PageRequest) -> Result<UserPage, DbError> {
+        let span = info_span!(
+            "storage",
+            method = "list_users",
+            sort = ?sort.field,
+            direction = ?sort.direction,
+            page = page.page,
+            per_page = page.per_page,
+        );
+        self.observe("list_users", span, self.inner.list_users(filter, sort, page)).await
+    }
+
+    async fn update_user(&self, user_id: Uuid, changes: &UpdateUserRequest, expected_version: i64) -> Result<User, DbError> {
+        let span = info_span!("storage", method = "update_user", user_id = %user_id);
+        self.observe("update_user", span, self.inner.update_user(user_id, changes, expected_version)).await
+    }
+
+    async fn soft_delete_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        let span = info_span!("storage", method = "soft_delete_user", user_id = %user_id);
+        self.observe("soft_delete_user", span, self.inner.soft_delete_user(user_id)).await
+    }
+
+    async fn restore_user(&self, user_id: Uuid) -> Result<User, DbError> {
+        let span = info_span!("storage", method = "restore_user", user_id = %user_id);
+        self.observe("restore_user", span, self.inner.restore_user(user_id)).await
+    }
+
+    async fn purge_deleted_users(&self, before: DateTime<Utc>) -> Result<u64, DbError> {
+        let span = info_span!("storage", method = "purge_deleted_users");
+        self.observe("purge_deleted_users", span, self.inner.purge_deleted_users(before)).await
+    }
+
+    async fn get_account_by_user_id(&self, user_id: Uuid) -> Result<Option<UserAccount>, DbError> {
+        let span = info_span!("storage", method = "get_account_by_user_id", user_id = %user_id);
+        self.observe("get_account_by_user_id", span, self.inner.get_account_by_user_id(user_id)).await
+    }
+
+    async fn create_account(&self, user_id: Uuid) -> Result<UserAccount, DbError> {
+        let span = info_span!("storage", method = "create_account", user_id = %user_id);
+        self.observe("create_account", span, self.inner.create_account(user_id)).await
+    }
+
+    async fn update_account(&self, user_id: Uuid, changes: &UpdateAccountRequest, expected_version: i64) -> Result<UserAccount, DbError> {
+        let span = info_span!("storage", method = "update_account", user_id = %user_id);
+        self.observe("update_account", span, self.inner.update_account(user_id, changes, expected_version)).await
+    }
+
+    async fn get_admin_by_user_id(&self, user_id: Uuid) -> Result<Option<Admin>, DbError> {
+        let span = info_span!("storage", method = "get_admin_by_user_id", user_id = %user_id);
+        self.observe("get_admin_by_user_id", span, self.inner.get_admin_by_user_id(user_id)).await
+    }
+
+    async fn is_admin(&self, user_id: Uuid) -> Result<bool, DbError> {
+        let span = info_span!("storage", method = "is_admin", user_id = %user_id);
+        self.observe("is_admin", span, self.inner.is_admin(user_id)).await
+    }
+
+    async fn has_superadmin(&self) -> Result<bool, DbError> {
+        let span = info_span!("storage", method = "has_superadmin");
+        self.observe("has_superadmin", span, self.inner.has_superadmin()).await
+    }
+
+    async fn update_admin(&self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        let span = info_span!("storage", method = "update_admin", user_id = %user_id);
+        self.observe("update_admin", span, self.inner.update_admin(user_id, changes, expected_version)).await
+    }
+
+    async fn delete_admin(&self, user_id: Uuid) -> Result<(), DbError> {
+        let span = info_span!("storage", method = "delete_admin", user_id = %user_id);
+        self.observe("delete_admin", span, self.inner.delete_admin(user_id)).await
+    }
+
+    async fn store_token(&self, token: &AuthToken) -> Result<(), DbError> {
+        let span = info_span!("storage", method = "store_token", user_id = %token.user_id, token_id = %token.id);
+        self.observe("store_token", span, self.inner.store_token(token)).await
+    }
+
+    async fn get_token_by_hash(&self, token_hash: &str) -> Result<Option<AuthToken>, DbError> {
+        let span = info_span!("storage", method = "get_token_by_hash");
+        self.observe("get_token_by_hash", span, self.inner.get_token_by_hash(token_hash)).await
+    }
+
+    async fn revoke_token(&self, token_hash: &str) -> Result<(), DbError> {
+        let span = info_span!("storage", method = "revoke_token");
+        self.observe("revoke_token", span, self.inner.revoke_token(token_hash)).await
+    }
+
+    async fn revoke_all_user_tokens(&self, user_id: Uuid) -> Result<(), DbError> {
+        let span = info_span!("storage", method = "revoke_all_user_tokens", user_id = %user_id);
+        self.observe("revoke_all_user_tokens", span, self.inner.revoke_all_user_tokens(user_id)).await
+    }
+
+    async fn purge_expired_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let span = info_span!("storage", method = "purge_expired_tokens");
+        self.observe("purge_expired_tokens", span, self.inner.purge_expired_tokens(now)).await
+    }
+
+    async fn count_active_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let span = info_span!("storage", method = "count_active_tokens");
+        self.observe("count_active_tokens", span, self.inner.count_active_tokens(now)).await
+    }
+
+    async fn purge_expired_validation_keys(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let span = info_span!("storage", method = "purge_expired_validation_keys");
+        self.observe("purge_expired_validation_keys", span, self.inner.purge_expired_validation_keys(now)).await
+    }
+
+    async fn record_login_event(&self, event: &LoginEvent) -> Result<(), DbError> {
+        let span = info_span!("storage", method = "record_login_event", event_id = %event.id, success = event.success);
+        self.observe("record_login_event", span, self.inner.record_login_event(event)).await
+    }
+
+    async fn get_login_events(&self, user_id: Uuid, limit: i64) -> Result<Vec<LoginEvent>, DbError> {
+        let span = info_span!("storage", method = "get_login_events", user_id = %user_id, limit);
+        self.observe("get_login_events", span, self.inner.get_login_events(user_id, limit)).await
+    }
+
+    async fn is_known_device(&self, user_id: Uuid, fingerprint: &str) -> Result<bool, DbError> {
+        let span = info_span!("storage", method = "is_known_device", user_id = %user_id);
+        self.observe("is_known_device", span, self.inner.is_known_device(user_id, fingerprint)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_calls_and_errors_are_counted() {
+        let metrics = Arc::new(StorageMetrics::new());
+        let storage = MetricsStorage::new(Arc::new(MemoryStorage::new()), metrics.clone());
+
+        storage.get_user_by_id(Uuid::new_v4()).await.unwrap();
+        storage.get_user_by_id(Uuid::new_v4()).await.unwrap();
+        assert!(storage.update_user_last_login(Uuid::new_v4()).await.is_err());
+
+        let stats = metrics.stats();
+        let lookups = stats.iter().find(|s| s.method == "get_user_by_id").unwrap();
+        assert_eq!((lookups.calls, lookups.errors), (2, 0));
+        let updates = stats.iter().find(|s| s.method == "update_user_last_login").unwrap();
+        assert_eq!(updates.error_kinds, vec![("not_found", 1)]);
+    }
+
+    #[test]
+    fn test_prometheus_histogram_is_cumulative() {
+        let metrics = StorageMetrics::new();
+        metrics.record("is_admin", Duration::from_micros(500), None);
+        metrics.record("is_admin", Duration::from_millis(20), Some(&DbError::Connection("down".into())));
+        metrics.record("is_admin", Duration::from_secs(5), None);
+
+        let mut out = String::new();
+        metrics.render_prometheus(&mut out);
+
+        assert!(out.contains("storage_calls_total{method=\"is_admin\"} 3"));
+        assert!(out.contains("storage_errors_total{method=\"is_admin\",kind=\"connection\"} 1"));
+        assert!(out.contains("storage_call_duration_seconds_bucket{method=\"is_admin\",le=\"0.001\"} 1"));
+        assert!(out.contains("storage_call_duration_seconds_bucket{method=\"is_admin\",le=\"0.025\"} 2"));
+        assert!(out.contains("storage_call_duration_seconds_bucket{method=\"is_admin\",le=\"2.5\"} 2"));
+        assert!(out.contains("storage_call_duration_seconds_bucket{method=\"is_admin\",le=\"+Inf\"} 3"));
+    }
+
+    #[test]
+    fn test_quantiles_use_bucket_bounds() {
+        let metrics = StorageMetrics::new();
+        for _ in 0..99 {
+            metrics.record("begin", Duration::from_micros(800), None);
+        }
+        metrics.record("begin", Duration::from_millis(80), None);
+
+        let stats = metrics.stats();
+        assert_eq!(stats[0].p50_ms, Some(1.0));
+        assert_eq!(stats[0].p99_ms, Some(1.0));
+    }
+}
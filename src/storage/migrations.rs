@@ -0,0 +1,23 @@
+use serde::Serialize;
+use sqlx::migrate::{MigrationType, Migrator};
+
+/// A migration shipped with this build and whether the database has run it.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Lists `migrator`'s migrations, marking those whose version is in `applied`.
+pub(crate) fn statuses(migrator: &Migrator, applied: &[i64]) -> Vec<MigrationStatus> {
+    migrator
+        .iter()
+        .filter(|m| !matches!(m.migration_type, MigrationType::ReversibleDown))
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains(&m.version),
+        })
+        .collect()
+}
@@ -1,37 +1,160 @@
 pub mod postgres;
 pub mod memory;
+pub mod cached;
+pub mod metrics;
+pub mod sqlite;
 pub mod error;
+pub mod lock;
+pub mod migrations;
+pub mod snapshot;
+pub mod transaction;
 
+#[cfg(test)]
+pub(crate) mod conformance;
+
+pub use cached::{CacheConfig, CacheStats, CachedStorage};
 pub use error::DbError;
+pub use lock::{JobLock, LocalJobLock};
+pub use metrics::{MetricsStorage, StorageMetrics};
+pub use migrations::MigrationStatus;
 pub use memory::MemoryStorage;
 pub use postgres::PostgresStorage;
+pub use sqlite::SqliteStorage;
+pub use transaction::{Transaction, seed_admin};
+
+use std::time::Duration;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use uuid::Uuid;
 
-use crate::users::model::{User, CreateUserRequest};
-use crate::auth::model::UserAccount;
-use crate::admin::model::Admin;
+use crate::users::model::{User, CreateUserRequest, UpdateUserRequest, UserFilter, UserSort, PageRequest, UserPage};
+use crate::auth::model::{UserAccount, UpdateAccountRequest, LoginEvent};
+use crate::admin::model::{Admin, UpdateAdminRequest};
 use crate::validation::model::AuthToken;
 
+/// Connection pool sizing for the database-backed stores.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a query waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PoolStats {
+    /// Open connections, idle or in use.
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
 #[async_trait]
 pub trait StorageLayer: Send + Sync {
     async fn health_check(&self) -> bool;
 
+    /// Hit/miss counters for any caches in front of the store.
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        Vec::new()
+    }
+
+    async fn begin(&self) -> Result<Box<dyn Transaction + '_>, DbError>;
+
+    /// Takes the lock for background job `job` if no other instance holds it.
+    /// Stores that only one process can use never need to refuse.
+    async fn try_lock_job(&self, _job: &str) -> Result<Option<Box<dyn JobLock + '_>>, DbError> {
+        Ok(Some(Box::new(LocalJobLock)))
+    }
+
+    /// Migrations shipped with this build and whether each has run. Empty for
+    /// stores without a schema.
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, DbError> {
+        Ok(Vec::new())
+    }
+
+    /// Connection pool usage, for stores that have a pool.
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+
+    /// Flushes anything buffered and releases connections. Called once on
+    /// shutdown, after the last request has finished.
+    async fn close(&self) -> Result<(), DbError> {
+        Ok(())
+    }
+
     async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError>;
     async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, DbError>;
     async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, DbError>;
     async fn create_user(&self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError>;
     async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError>;
+    /// Applies `changes` if the user is still at `expected_version`, returning
+    /// the updated user. Fails with `Conflict` if it has changed since.
+    async fn update_user(&self, user_id: Uuid, changes: &UpdateUserRequest, expected_version: i64) -> Result<User, DbError>;
+    async fn list_users(&self, filter: &UserFilter, sort: UserSort, page: PageRequest) -> Result<UserPage, DbError>;
+    /// Hides the user from every lookup and frees their email and username,
+    /// keeping the row until `restore_user` or `purge_deleted_users`.
+    async fn soft_delete_user(&self, user_id: Uuid) -> Result<(), DbError>;
+    /// Undoes `soft_delete_user`. Fails with `Duplicate` if the email or
+    /// username has been taken in the meantime.
+    async fn restore_user(&self, user_id: Uuid) -> Result<User, DbError>;
+    /// Permanently deletes users soft-deleted at or before `before`, along
+    /// with everything that belongs to them, returning how many.
+    async fn purge_deleted_users(&self, before: DateTime<Utc>) -> Result<u64, DbError>;
 
     async fn get_account_by_user_id(&self, user_id: Uuid) -> Result<Option<UserAccount>, DbError>;
     async fn create_account(&self, user_id: Uuid) -> Result<UserAccount, DbError>;
+    /// Like `update_user`, for the account of `user_id`.
+    async fn update_account(&self, user_id: Uuid, changes: &UpdateAccountRequest, expected_version: i64) -> Result<UserAccount, DbError>;
 
     async fn get_admin_by_user_id(&self, user_id: Uuid) -> Result<Option<Admin>, DbError>;
     async fn is_admin(&self, user_id: Uuid) -> Result<bool, DbError>;
+    /// Whether any user who isn't deleted is a superadmin.
+    async fn has_superadmin(&self) -> Result<bool, DbError>;
+    /// Like `update_user`, for the admin record of `user_id`.
+    async fn update_admin(&self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError>;
+    /// Takes admin rights away from `user_id`; their user and account stay.
+    async fn delete_admin(&self, user_id: Uuid) -> Result<(), DbError>;
 
     async fn store_token(&self, token: &AuthToken) -> Result<(), DbError>;
     async fn get_token_by_hash(&self, token_hash: &str) -> Result<Option<AuthToken>, DbError>;
     async fn revoke_token(&self, token_hash: &str) -> Result<(), DbError>;
     async fn revoke_all_user_tokens(&self, user_id: Uuid) -> Result<(), DbError>;
+    /// Deletes tokens that expired at or before `now`, returning how many.
+    async fn purge_expired_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError>;
+    /// Tokens neither revoked nor expired at `now`, one per signed-in session.
+    async fn count_active_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError>;
+
+    async fn purge_expired_validation_keys(&self, now: DateTime<Utc>) -> Result<u64, DbError>;
+
+    async fn record_login_event(&self, event: &LoginEvent) -> Result<(), DbError>;
+    async fn get_login_events(&self, user_id: Uuid, limit: i64) -> Result<Vec<LoginEvent>, DbError>;
+    async fn is_known_device(&self, user_id: Uuid, fingerprint: &str) -> Result<bool, DbError>;
+}
+
+/// `%term%` for a LIKE/ILIKE match, with the term's wildcards escaped using
+/// backslash.
+fn like_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
 }
@@ -1,16 +1,30 @@
 use async_trait::async_trait;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, QueryBuilder, Row};
 use sqlx::migrate::Migrator;
+use sqlx::postgres::PgPoolOptions;
 use uuid::Uuid;
 
-use super::{DbError, StorageLayer};
-use crate::users::model::{User, CreateUserRequest};
-use crate::auth::model::{UserAccount, AccountLevel, AccountStatus};
-use crate::admin::model::Admin;
+use super::{DbError, PoolStats, JobLock, PoolConfig, StorageLayer, Transaction, like_pattern};
+use super::lock::lock_key;
+use super::migrations::{self, MigrationStatus};
+use crate::users::model::{
+    User, CreateUserRequest, UpdateUserRequest, UserFilter, UserSort, UserSortField, SortDirection, PageRequest,
+    UserPage, UserSummary,
+};
+use crate::auth::model::{UserAccount, CreateAccountRequest, UpdateAccountRequest, LoginEvent};
+use crate::admin::model::{Admin, CreateAdminRequest, UpdateAdminRequest};
 use crate::validation::model::AuthToken;
 
 static MIGRATOR: Migrator = sqlx::migrate!();
 
+const USER_COLUMNS: &str = "id, email, password_hash, username, first_name, last_name,
+                            is_active, created_at, updated_at, last_login, deleted_at, version";
+const ACCOUNT_COLUMNS: &str = "id, user_id, account_level, account_status, capabilities,
+                               status_reason, status_changed_at, status_changed_by, created_at, updated_at,
+                               version";
+const ADMIN_COLUMNS: &str = "id, user_id, role, permissions, created_at, updated_at, created_by, version";
+
 pub struct PostgresStorage {
     pool: PgPool,
 }
@@ -21,7 +35,15 @@ impl PostgresStorage {
     }
 
     pub async fn from_url(database_url: &str) -> Result<Self, DbError> {
-        let pool = PgPool::connect(database_url)
+        Self::connect(database_url, &PoolConfig::default()).await
+    }
+
+    pub async fn connect(database_url: &str, config: &PoolConfig) -> Result<Self, DbError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(config.acquire_timeout)
+            .connect(database_url)
             .await
             .map_err(|e| DbError::Connection(e.to_string()))?;
         Ok(Self { pool })
@@ -35,60 +57,256 @@ impl PostgresStorage {
         Ok(())
     }
 
+    /// Every migration this build knows about and whether it has been run.
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>, DbError> {
+        let (has_table,): (bool,) = sqlx::query_as("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        let applied: Vec<i64> = if has_table {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            Vec::new()
+        };
+
+        Ok(migrations::statuses(&MIGRATOR, &applied))
+    }
+
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
 
-    pub async fn seed_admin(&self, email: &str, password_hash: &str) -> Result<(), DbError> {
-        let existing = self.get_user_by_email(email).await?;
-        if existing.is_some() {
-            return Ok(());
-        }
+    /// The error for a version-checked update that matched no row: `Conflict`
+    /// if `exists` (which selects the row by id alone) still finds it.
+    async fn missed_update(&self, exists: &str, id: Uuid) -> DbError {
+        missed_update(&self.pool, exists, id).await
+    }
+}
 
-        let user_id = Uuid::new_v4();
+// Queries shared by the pool and by transactions
 
-        sqlx::query(
-            "INSERT INTO users (id, email, password_hash, username, first_name, last_name, is_active)
-             VALUES ($1, $2, $3, $4, $5, $6, true)"
-        )
-        .bind(user_id)
-        .bind(email)
-        .bind(password_hash)
-        .bind("admin")
-        .bind("Admin")
-        .bind("User")
-        .execute(&self.pool)
-        .await?;
+async fn fetch_user_by_email<'e, E: PgExecutor<'e>>(executor: E, email: &str) -> Result<Option<User>, DbError> {
+    let user = sqlx::query_as::<_, User>(&format!(
+        "SELECT {} FROM users WHERE email = $1 AND deleted_at IS NULL",
+        USER_COLUMNS
+    ))
+    .bind(email)
+    .fetch_optional(executor)
+    .await?;
 
-        sqlx::query(
-            "INSERT INTO user_accounts (user_id, account_level, account_status, capabilities)
-             VALUES ($1, 'enterprise', 'active', $2)"
-        )
-        .bind(user_id)
-        .bind(&vec![
-            "create_website",
-            "manage_components",
-            "send_emails",
-            "access_analytics",
-            "api_access",
-            "priority_support",
-        ])
-        .execute(&self.pool)
-        .await?;
+    Ok(user)
+}
 
-        sqlx::query(
-            "INSERT INTO admins (user_id, role, permissions)
-             VALUES ($1, 'superadmin', $2)"
-        )
-        .bind(user_id)
-        .bind(&vec!["*"])
-        .execute(&self.pool)
-        .await?;
+async fn insert_user<'e, E: PgExecutor<'e>>(
+    executor: E,
+    req: &CreateUserRequest,
+    password_hash: &str,
+) -> Result<User, DbError> {
+    let user = sqlx::query_as::<_, User>(&format!(
+        "INSERT INTO users (email, password_hash, username, first_name, last_name)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING {}",
+        USER_COLUMNS
+    ))
+    .bind(&req.email)
+    .bind(password_hash)
+    .bind(&req.username)
+    .bind(&req.first_name)
+    .bind(&req.last_name)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(user)
+}
+
+async fn insert_account<'e, E: PgExecutor<'e>>(
+    executor: E,
+    req: &CreateAccountRequest,
+) -> Result<UserAccount, DbError> {
+    let account = sqlx::query_as::<_, UserAccount>(&format!(
+        "INSERT INTO user_accounts (user_id, account_level, account_status, capabilities)
+         VALUES ($1, $2, $3, $4)
+         RETURNING {}",
+        ACCOUNT_COLUMNS
+    ))
+    .bind(req.user_id)
+    .bind(&req.account_level)
+    .bind(&req.account_status)
+    .bind(&req.capabilities)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(account)
+}
+
+async fn insert_admin<'e, E: PgExecutor<'e>>(
+    executor: E,
+    req: &CreateAdminRequest,
+) -> Result<Admin, DbError> {
+    let admin = sqlx::query_as::<_, Admin>(&format!(
+        "INSERT INTO admins (user_id, role, permissions, created_by)
+         VALUES ($1, $2, $3, $4)
+         RETURNING {}",
+        ADMIN_COLUMNS
+    ))
+    .bind(req.user_id)
+    .bind(&req.role)
+    .bind(&req.permissions)
+    .bind(req.created_by)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(admin)
+}
+
+/// A transaction-scoped advisory lock: it is released when the transaction
+/// ends, including when the lock is dropped or the connection is lost.
+pub struct PostgresJobLock {
+    tx: sqlx::Transaction<'static, Postgres>,
+}
 
+#[async_trait]
+impl JobLock for PostgresJobLock {
+    async fn release(self: Box<Self>) -> Result<(), DbError> {
+        self.tx.commit().await?;
         Ok(())
     }
 }
 
+/// Locks the superadmins' rows, so a concurrent demotion waits for this
+/// transaction to end.
+async fn fetch_superadmin_ids<'e, E: PgExecutor<'e>>(executor: E) -> Result<Vec<Uuid>, DbError> {
+    let ids = sqlx::query_scalar(
+        "SELECT a.user_id FROM admins a JOIN users u ON u.id = a.user_id
+         WHERE a.role = 'superadmin' AND u.deleted_at IS NULL
+         FOR UPDATE OF a"
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(ids)
+}
+
+/// The version-checked update; `None` if no row matched.
+async fn update_admin_row<'e, E: PgExecutor<'e>>(
+    executor: E,
+    user_id: Uuid,
+    changes: &UpdateAdminRequest,
+    expected_version: i64,
+) -> Result<Option<Admin>, DbError> {
+    let admin = sqlx::query_as::<_, Admin>(&format!(
+        "UPDATE admins SET role = COALESCE($3, role),
+                           permissions = COALESCE($4, permissions),
+                           version = version + 1
+         WHERE user_id = $1 AND version = $2
+         RETURNING {}",
+        ADMIN_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(expected_version)
+    .bind(&changes.role)
+    .bind(&changes.permissions)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(admin)
+}
+
+/// The error for a version-checked update that matched no row: `Conflict`
+/// if `exists` (which selects the row by id alone) still finds it.
+async fn missed_update<'e, E: PgExecutor<'e>>(executor: E, exists: &str, id: Uuid) -> DbError {
+    match sqlx::query(exists).bind(id).fetch_optional(executor).await {
+        Ok(Some(_)) => DbError::Conflict,
+        Ok(None) => DbError::NotFound,
+        Err(e) => e.into(),
+    }
+}
+
+pub struct PostgresTransaction {
+    tx: sqlx::Transaction<'static, Postgres>,
+}
+
+#[async_trait]
+impl Transaction for PostgresTransaction {
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<User>, DbError> {
+        fetch_user_by_email(&mut *self.tx, email).await
+    }
+
+    async fn create_user(&mut self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
+        insert_user(&mut *self.tx, req, password_hash).await
+    }
+
+    async fn create_account(&mut self, req: &CreateAccountRequest) -> Result<UserAccount, DbError> {
+        insert_account(&mut *self.tx, req).await
+    }
+
+    async fn create_admin(&mut self, req: &CreateAdminRequest) -> Result<Admin, DbError> {
+        insert_admin(&mut *self.tx, req).await
+    }
+
+    async fn superadmin_ids(&mut self) -> Result<Vec<Uuid>, DbError> {
+        fetch_superadmin_ids(&mut *self.tx).await
+    }
+
+    async fn update_admin(&mut self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        match update_admin_row(&mut *self.tx, user_id, changes, expected_version).await? {
+            Some(admin) => Ok(admin),
+            None => Err(missed_update(&mut *self.tx, "SELECT 1 FROM admins WHERE user_id = $1", user_id).await),
+        }
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DbError> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DbError> {
+        self.tx.rollback().await?;
+        Ok(())
+    }
+}
+
+/// Appends the `FROM ... WHERE ...` part shared by the page and count queries.
+fn push_user_filter<'a>(qb: &mut QueryBuilder<'a, Postgres>, filter: &'a UserFilter) {
+    qb.push(" FROM users u LEFT JOIN user_accounts a ON a.user_id = u.id");
+    qb.push(if filter.deleted { " WHERE u.deleted_at IS NOT NULL" } else { " WHERE u.deleted_at IS NULL" });
+
+    if let Some(status) = &filter.status {
+        qb.push(" AND a.account_status = ").push_bind(status);
+    }
+    if let Some(level) = &filter.account_level {
+        qb.push(" AND a.account_level = ").push_bind(level);
+    }
+    if let Some(after) = filter.created_after {
+        qb.push(" AND u.created_at >= ").push_bind(after);
+    }
+    if let Some(before) = filter.created_before {
+        qb.push(" AND u.created_at < ").push_bind(before);
+    }
+    if let Some(term) = filter.search_term() {
+        let pattern = like_pattern(term);
+        qb.push(" AND (u.email ILIKE ").push_bind(pattern.clone())
+            .push(" OR u.username ILIKE ").push_bind(pattern.clone())
+            .push(" OR (u.first_name || ' ' || u.last_name) ILIKE ").push_bind(pattern)
+            .push(")");
+    }
+}
+
+/// Text columns sort bytewise so every backend orders pages the same way.
+fn order_by(sort: UserSort) -> String {
+    let column = match sort.field {
+        UserSortField::CreatedAt => "u.created_at",
+        UserSortField::Email => "u.email COLLATE \"C\"",
+        UserSortField::Username => "u.username COLLATE \"C\"",
+    };
+    let direction = match sort.direction {
+        SortDirection::Asc => "ASC",
+        SortDirection::Desc => "DESC",
+    };
+    format!(" ORDER BY {} {}, u.id {}", column, direction, direction)
+}
+
 #[async_trait]
 impl StorageLayer for PostgresStorage {
     async fn health_check(&self) -> bool {
@@ -98,25 +316,52 @@ impl StorageLayer for PostgresStorage {
             .is_ok()
     }
 
-    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
-        let user = sqlx::query_as::<_, User>(
-            "SELECT id, email, password_hash, username, first_name, last_name,
-                    is_active, created_at, updated_at, last_login
-             FROM users WHERE email = $1"
-        )
-        .bind(email)
-        .fetch_optional(&self.pool)
-        .await?;
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, DbError> {
+        PostgresStorage::migration_status(self).await
+    }
 
-        Ok(user)
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max_connections: self.pool.options().get_max_connections(),
+        })
+    }
+
+    async fn close(&self) -> Result<(), DbError> {
+        self.pool.close().await;
+        Ok(())
+    }
+
+    async fn begin(&self) -> Result<Box<dyn Transaction + '_>, DbError> {
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(PostgresTransaction { tx }))
+    }
+
+    async fn try_lock_job(&self, job: &str) -> Result<Option<Box<dyn JobLock + '_>>, DbError> {
+        let mut tx = self.pool.begin().await?;
+        let acquired: bool = sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+            .bind(lock_key(job))
+            .fetch_one(&mut *tx)
+            .await?;
+
+        if acquired {
+            Ok(Some(Box::new(PostgresJobLock { tx })))
+        } else {
+            tx.rollback().await?;
+            Ok(None)
+        }
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
+        fetch_user_by_email(&self.pool, email).await
     }
 
     async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, DbError> {
-        let user = sqlx::query_as::<_, User>(
-            "SELECT id, email, password_hash, username, first_name, last_name,
-                    is_active, created_at, updated_at, last_login
-             FROM users WHERE id = $1"
-        )
+        let user = sqlx::query_as::<_, User>(&format!(
+            "SELECT {} FROM users WHERE id = $1 AND deleted_at IS NULL",
+            USER_COLUMNS
+        ))
         .bind(id)
         .fetch_optional(&self.pool)
         .await?;
@@ -125,11 +370,10 @@ impl StorageLayer for PostgresStorage {
     }
 
     async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, DbError> {
-        let user = sqlx::query_as::<_, User>(
-            "SELECT id, email, password_hash, username, first_name, last_name,
-                    is_active, created_at, updated_at, last_login
-             FROM users WHERE username = $1"
-        )
+        let user = sqlx::query_as::<_, User>(&format!(
+            "SELECT {} FROM users WHERE username = $1 AND deleted_at IS NULL",
+            USER_COLUMNS
+        ))
         .bind(username)
         .fetch_optional(&self.pool)
         .await?;
@@ -138,37 +382,120 @@ impl StorageLayer for PostgresStorage {
     }
 
     async fn create_user(&self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
-        let user = sqlx::query_as::<_, User>(
-            "INSERT INTO users (email, password_hash, username, first_name, last_name)
-             VALUES ($1, $2, $3, $4, $5)
-             RETURNING id, email, password_hash, username, first_name, last_name,
-                       is_active, created_at, updated_at, last_login"
-        )
-        .bind(&req.email)
-        .bind(password_hash)
-        .bind(&req.username)
-        .bind(&req.first_name)
-        .bind(&req.last_name)
-        .fetch_one(&self.pool)
+        insert_user(&self.pool, req, password_hash).await
+    }
+
+    async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("UPDATE users SET last_login = NOW() WHERE id = $1 AND deleted_at IS NULL")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn update_user(&self, user_id: Uuid, changes: &UpdateUserRequest, expected_version: i64) -> Result<User, DbError> {
+        let user = sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET email = COALESCE($3, email),
+                              username = COALESCE($4, username),
+                              first_name = COALESCE($5, first_name),
+                              last_name = COALESCE($6, last_name),
+                              is_active = COALESCE($7, is_active),
+                              password_hash = COALESCE($8, password_hash),
+                              version = version + 1
+             WHERE id = $1 AND version = $2 AND deleted_at IS NULL
+             RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(expected_version)
+        .bind(&changes.email)
+        .bind(&changes.username)
+        .bind(&changes.first_name)
+        .bind(&changes.last_name)
+        .bind(changes.is_active)
+        .bind(&changes.password_hash)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(user)
+        match user {
+            Some(user) => Ok(user),
+            None => Err(self.missed_update("SELECT 1 FROM users WHERE id = $1 AND deleted_at IS NULL", user_id).await),
+        }
     }
 
-    async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError> {
-        sqlx::query("UPDATE users SET last_login = NOW() WHERE id = $1")
+    async fn soft_delete_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("UPDATE users SET deleted_at = NOW(), version = version + 1 WHERE id = $1 AND deleted_at IS NULL")
             .bind(user_id)
             .execute(&self.pool)
             .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
         Ok(())
     }
 
+    async fn restore_user(&self, user_id: Uuid) -> Result<User, DbError> {
+        let user = sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET deleted_at = NULL, version = version + 1
+             WHERE id = $1 AND deleted_at IS NOT NULL
+             RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        user.ok_or(DbError::NotFound)
+    }
+
+    async fn purge_deleted_users(&self, before: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM users WHERE deleted_at <= $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn list_users(&self, filter: &UserFilter, sort: UserSort, page: PageRequest) -> Result<UserPage, DbError> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*)");
+        push_user_filter(&mut count, filter);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut query = QueryBuilder::new(
+            "SELECT u.id, u.email, u.password_hash, u.username, u.first_name, u.last_name,
+                    u.is_active, u.created_at, u.updated_at, u.last_login, u.deleted_at, u.version,
+                    a.account_level, a.account_status",
+        );
+        push_user_filter(&mut query, filter);
+        query.push(order_by(sort));
+        query.push(" LIMIT ").push_bind(page.per_page);
+        query.push(" OFFSET ").push_bind(page.offset());
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let users = rows
+            .iter()
+            .map(|row| {
+                Ok(UserSummary {
+                    user: User::from_row(row)?,
+                    account_level: row.try_get("account_level")?,
+                    account_status: row.try_get("account_status")?,
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        Ok(UserPage { users, total })
+    }
+
     async fn get_account_by_user_id(&self, user_id: Uuid) -> Result<Option<UserAccount>, DbError> {
-        let account = sqlx::query_as::<_, UserAccount>(
-            "SELECT id, user_id, account_level, account_status, capabilities,
-                    status_reason, status_changed_at, status_changed_by, created_at, updated_at
-             FROM user_accounts WHERE user_id = $1"
-        )
+        let account = sqlx::query_as::<_, UserAccount>(&format!(
+            "SELECT {} FROM user_accounts WHERE user_id = $1",
+            ACCOUNT_COLUMNS
+        ))
         .bind(user_id)
         .fetch_optional(&self.pool)
         .await?;
@@ -177,27 +504,44 @@ impl StorageLayer for PostgresStorage {
     }
 
     async fn create_account(&self, user_id: Uuid) -> Result<UserAccount, DbError> {
-        let account = sqlx::query_as::<_, UserAccount>(
-            "INSERT INTO user_accounts (user_id, account_level, account_status, capabilities)
-             VALUES ($1, $2, $3, $4)
-             RETURNING id, user_id, account_level, account_status, capabilities,
-                       status_reason, status_changed_at, status_changed_by, created_at, updated_at"
-        )
+        insert_account(&self.pool, &CreateAccountRequest::default_for(user_id)).await
+    }
+
+    async fn update_account(&self, user_id: Uuid, changes: &UpdateAccountRequest, expected_version: i64) -> Result<UserAccount, DbError> {
+        let account = sqlx::query_as::<_, UserAccount>(&format!(
+            "UPDATE user_accounts SET
+                 account_level = COALESCE($3, account_level),
+                 capabilities = COALESCE($4, capabilities),
+                 account_status = COALESCE($5, account_status),
+                 status_reason = CASE WHEN $5::account_status IS NULL THEN status_reason ELSE $6 END,
+                 status_changed_at = CASE WHEN $5::account_status IS NULL THEN status_changed_at ELSE NOW() END,
+                 status_changed_by = CASE WHEN $5::account_status IS NULL THEN status_changed_by ELSE $7 END,
+                 version = version + 1
+             WHERE user_id = $1 AND version = $2
+             RETURNING {}",
+            ACCOUNT_COLUMNS
+        ))
         .bind(user_id)
-        .bind(AccountLevel::Free)
-        .bind(AccountStatus::Pending)
-        .bind(Vec::<String>::new())
-        .fetch_one(&self.pool)
+        .bind(expected_version)
+        .bind(&changes.account_level)
+        .bind(&changes.capabilities)
+        .bind(&changes.account_status)
+        .bind(&changes.status_reason)
+        .bind(changes.changed_by)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(account)
+        match account {
+            Some(account) => Ok(account),
+            None => Err(self.missed_update("SELECT 1 FROM user_accounts WHERE user_id = $1", user_id).await),
+        }
     }
 
     async fn get_admin_by_user_id(&self, user_id: Uuid) -> Result<Option<Admin>, DbError> {
-        let admin = sqlx::query_as::<_, Admin>(
-            "SELECT id, user_id, role, permissions, created_at, updated_at, created_by
-             FROM admins WHERE user_id = $1"
-        )
+        let admin = sqlx::query_as::<_, Admin>(&format!(
+            "SELECT {} FROM admins WHERE user_id = $1",
+            ADMIN_COLUMNS
+        ))
         .bind(user_id)
         .fetch_optional(&self.pool)
         .await?;
@@ -206,14 +550,44 @@ impl StorageLayer for PostgresStorage {
     }
 
     async fn is_admin(&self, user_id: Uuid) -> Result<bool, DbError> {
-        let result: Option<(i64,)> = sqlx::query_as(
-            "SELECT 1 FROM admins WHERE user_id = $1"
+        let (exists,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM admins WHERE user_id = $1)"
         )
         .bind(user_id)
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
 
-        Ok(result.is_some())
+        Ok(exists)
+    }
+
+    async fn has_superadmin(&self) -> Result<bool, DbError> {
+        let (exists,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM admins a JOIN users u ON u.id = a.user_id
+                           WHERE a.role = 'superadmin' AND u.deleted_at IS NULL)"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    async fn update_admin(&self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        match update_admin_row(&self.pool, user_id, changes, expected_version).await? {
+            Some(admin) => Ok(admin),
+            None => Err(self.missed_update("SELECT 1 FROM admins WHERE user_id = $1", user_id).await),
+        }
+    }
+
+    async fn delete_admin(&self, user_id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM admins WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
     }
 
     async fn store_token(&self, token: &AuthToken) -> Result<(), DbError> {
@@ -260,4 +634,82 @@ impl StorageLayer for PostgresStorage {
             .await?;
         Ok(())
     }
+
+    async fn purge_expired_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM auth_tokens WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn count_active_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM auth_tokens WHERE revoked_at IS NULL AND expires_at > $1")
+                .bind(now)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count as u64)
+    }
+
+    async fn purge_expired_validation_keys(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM validation_keys WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn record_login_event(&self, event: &LoginEvent) -> Result<(), DbError> {
+        sqlx::query(
+            "INSERT INTO login_events (id, user_id, email, success, failure_reason, ip_address,
+                                       user_agent, auth_method, device_fingerprint, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
+        )
+        .bind(event.id)
+        .bind(event.user_id)
+        .bind(&event.email)
+        .bind(event.success)
+        .bind(&event.failure_reason)
+        .bind(&event.ip_address)
+        .bind(&event.user_agent)
+        .bind(&event.auth_method)
+        .bind(&event.device_fingerprint)
+        .bind(event.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_login_events(&self, user_id: Uuid, limit: i64) -> Result<Vec<LoginEvent>, DbError> {
+        let events = sqlx::query_as::<_, LoginEvent>(
+            "SELECT id, user_id, email, success, failure_reason, ip_address,
+                    user_agent, auth_method, device_fingerprint, created_at
+             FROM login_events WHERE user_id = $1
+             ORDER BY created_at DESC
+             LIMIT $2"
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    async fn is_known_device(&self, user_id: Uuid, fingerprint: &str) -> Result<bool, DbError> {
+        let (known,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(
+                SELECT 1 FROM login_events
+                WHERE user_id = $1 AND device_fingerprint = $2 AND success
+             )"
+        )
+        .bind(user_id)
+        .bind(fingerprint)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(known)
+    }
 }
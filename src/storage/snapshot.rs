@@ -0,0 +1,421 @@
+//! On-disk persistence for `MemoryStorage`.
+//!
+//! State is kept in two files: a versioned JSON snapshot of every map, written
+//! to a temporary file and renamed into place, and a write-ahead log of
+//! JSON lines next to it (`<snapshot>.wal`). Every write is appended to the log
+//! before it is applied in memory; taking a snapshot truncates the log. At
+//! startup the snapshot is loaded and the log replayed on top of it.
+//!
+//! Log records hold the full record after the write, so replaying one that is
+//! already reflected in the snapshot is harmless. Purges are logged as the
+//! cutoff they used and replay the same way.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::DbError;
+use crate::users::model::User;
+use crate::auth::model::{UserAccount, LoginEvent};
+use crate::admin::model::Admin;
+use crate::validation::model::AuthToken;
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub taken_at: DateTime<Utc>,
+    pub users: Vec<User>,
+    pub accounts: Vec<UserAccount>,
+    pub admins: Vec<Admin>,
+    pub tokens: Vec<AuthToken>,
+    pub login_events: Vec<LoginEvent>,
+}
+
+/// One logged write: the record as it looks after the write.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", content = "record", rename_all = "snake_case")]
+pub enum WalRecord {
+    User(User),
+    Account(UserAccount),
+    Admin(Admin),
+    /// The admin record of this user was deleted.
+    AdminRemoved(Uuid),
+    Token(AuthToken),
+    LoginEvent(LoginEvent),
+    /// Tokens that expired at or before this instant were deleted.
+    TokensPurged(DateTime<Utc>),
+    /// Users soft-deleted at or before this instant were deleted for good.
+    UsersPurged(DateTime<Utc>),
+}
+
+/// What was found on disk when persistence was opened.
+pub struct Recovered {
+    pub snapshot: Option<Snapshot>,
+    pub log: Vec<WalRecord>,
+}
+
+pub struct Persistence {
+    snapshot_path: PathBuf,
+    wal: Mutex<File>,
+    /// Records appended since the last snapshot.
+    pending: AtomicU64,
+}
+
+impl Persistence {
+    /// Reads any existing snapshot and log at `snapshot_path` and opens the
+    /// log for appending.
+    pub fn open(snapshot_path: impl Into<PathBuf>) -> Result<(Self, Recovered), DbError> {
+        let snapshot_path = snapshot_path.into();
+        if let Some(dir) = snapshot_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+
+        let snapshot = read_snapshot(&snapshot_path)?;
+        let wal_path = wal_path(&snapshot_path);
+        let log = read_log(&wal_path)?;
+
+        let wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&wal_path)
+            .map_err(io_error)?;
+
+        let persistence = Self {
+            snapshot_path,
+            wal: Mutex::new(wal),
+            pending: AtomicU64::new(log.len() as u64),
+        };
+        Ok((persistence, Recovered { snapshot, log }))
+    }
+
+    /// Appends `records` to the log as a single write.
+    pub fn append(&self, records: &[WalRecord]) -> Result<(), DbError> {
+        let mut buf = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut buf, record).map_err(|e| DbError::Other(e.to_string()))?;
+            buf.push(b'\n');
+        }
+
+        self.lock_wal().write_all(&buf).map_err(io_error)?;
+        self.pending.fetch_add(records.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn has_pending_writes(&self) -> bool {
+        self.pending.load(Ordering::Relaxed) > 0
+    }
+
+    /// Holding the log lock blocks further appends. `MemoryStorage` takes it
+    /// before releasing its map locks so no write falls between the snapshot
+    /// and the log truncation.
+    pub fn lock_wal(&self) -> MutexGuard<'_, File> {
+        self.wal.lock().unwrap()
+    }
+
+    /// Atomically replaces the snapshot and empties the log. `wal` must be the
+    /// guard returned by `lock_wal`.
+    pub fn replace_snapshot(&self, snapshot: &Snapshot, wal: &mut File) -> Result<(), DbError> {
+        let tmp_path = self.snapshot_path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp_path).map_err(io_error)?;
+            serde_json::to_writer(&mut file, snapshot).map_err(|e| DbError::Other(e.to_string()))?;
+            file.sync_all().map_err(io_error)?;
+        }
+        fs::rename(&tmp_path, &self.snapshot_path).map_err(io_error)?;
+        sync_parent_dir(&self.snapshot_path);
+
+        wal.set_len(0).map_err(io_error)?;
+        wal.sync_all().map_err(io_error)?;
+        self.pending.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+pub fn wal_path(snapshot_path: &Path) -> PathBuf {
+    let mut path = snapshot_path.as_os_str().to_owned();
+    path.push(".wal");
+    PathBuf::from(path)
+}
+
+fn read_snapshot(path: &Path) -> Result<Option<Snapshot>, DbError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error(e)),
+    };
+
+    let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| DbError::Other(format!("corrupt snapshot {}: {}", path.display(), e)))?;
+
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(DbError::Other(format!(
+            "snapshot {} has version {}, expected {}",
+            path.display(),
+            snapshot.version,
+            SNAPSHOT_VERSION
+        )));
+    }
+    Ok(Some(snapshot))
+}
+
+/// Reads log records up to the first line that doesn't parse. A crash in the
+/// middle of an append leaves a torn final line, which is dropped.
+fn read_log(path: &Path) -> Result<Vec<WalRecord>, DbError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io_error(e)),
+    };
+
+    let mut records = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => {
+                tracing::warn!(
+                    path = %path.display(),
+                    line = number + 1,
+                    error = %e,
+                    "ignoring the rest of the write-ahead log"
+                );
+                break;
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) {
+    let dir = path
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .and_then(|d| File::open(d).ok());
+    if let Some(dir) = dir {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) {}
+
+fn io_error(e: io::Error) -> DbError {
+    DbError::Other(format!("storage file error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    use crate::storage::{seed_admin, MemoryStorage, StorageLayer};
+    use crate::users::model::CreateUserRequest;
+    use crate::validation::model::TokenType;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("learner-snapshot-{}", Uuid::new_v4().simple()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn snapshot_path(&self) -> PathBuf {
+            self.0.join("memory.json")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn user_request(name: &str) -> CreateUserRequest {
+        CreateUserRequest {
+            email: format!("{}@example.com", name),
+            password: "password".to_string(),
+            username: name.to_string(),
+            first_name: "Test".to_string(),
+            last_name: "User".to_string(),
+        }
+    }
+
+    fn log_lines(path: &Path) -> usize {
+        fs::read_to_string(wal_path(path)).unwrap().lines().count()
+    }
+
+    #[tokio::test]
+    async fn test_logged_writes_survive_restart() {
+        let dir = TempDir::new();
+        let path = dir.snapshot_path();
+
+        let (user_id, token_hash) = {
+            let storage = MemoryStorage::open(&path).unwrap();
+            let user = storage.create_user(&user_request("alice"), "hash").await.unwrap();
+            storage.create_account(user.id).await.unwrap();
+
+            let now = Utc::now();
+            let token = AuthToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                token_hash: "refresh".to_string(),
+                token_type: TokenType::Refresh,
+                expires_at: now + Duration::days(7),
+                created_at: now,
+                revoked_at: None,
+                device_info: None,
+            };
+            storage.store_token(&token).await.unwrap();
+            storage.revoke_all_user_tokens(user.id).await.unwrap();
+
+            assert_eq!(log_lines(&path), 4);
+            (user.id, token.token_hash)
+        };
+
+        let storage = MemoryStorage::open(&path).unwrap();
+        assert!(storage.get_user_by_id(user_id).await.unwrap().is_some());
+        assert!(storage.get_account_by_user_id(user_id).await.unwrap().is_some());
+        let token = storage.get_token_by_hash(&token_hash).await.unwrap().unwrap();
+        assert!(token.revoked_at.is_some());
+        assert_eq!(log_lines(&path), 0);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_truncates_log() {
+        let dir = TempDir::new();
+        let path = dir.snapshot_path();
+
+        {
+            let storage = MemoryStorage::open(&path).unwrap();
+            storage.create_user(&user_request("bob"), "hash").await.unwrap();
+            storage.snapshot().unwrap();
+            assert_eq!(log_lines(&path), 0);
+
+            storage.create_user(&user_request("carol"), "hash").await.unwrap();
+            assert_eq!(log_lines(&path), 1);
+        }
+
+        let storage = MemoryStorage::open(&path).unwrap();
+        assert!(storage.get_user_by_username("bob").await.unwrap().is_some());
+        assert!(storage.get_user_by_username("carol").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_torn_log_tail_is_dropped() {
+        let dir = TempDir::new();
+        let path = dir.snapshot_path();
+
+        {
+            let storage = MemoryStorage::open(&path).unwrap();
+            storage.create_user(&user_request("dave"), "hash").await.unwrap();
+        }
+        let mut wal = OpenOptions::new().append(true).open(wal_path(&path)).unwrap();
+        wal.write_all(br#"{"op":"user","record":{"id":"#).unwrap();
+        drop(wal);
+
+        let storage = MemoryStorage::open(&path).unwrap();
+        assert!(storage.get_user_by_username("dave").await.unwrap().is_some());
+
+        storage.create_user(&user_request("erin"), "hash").await.unwrap();
+        drop(storage);
+        let storage = MemoryStorage::open(&path).unwrap();
+        assert!(storage.get_user_by_username("erin").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_purge_is_replayed() {
+        let dir = TempDir::new();
+        let path = dir.snapshot_path();
+
+        let user_id = {
+            let storage = MemoryStorage::open(&path).unwrap();
+            let user = storage.create_user(&user_request("frank"), "hash").await.unwrap();
+            let now = Utc::now();
+            let token = AuthToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                token_hash: "expired".to_string(),
+                token_type: TokenType::Refresh,
+                expires_at: now - Duration::minutes(1),
+                created_at: now - Duration::days(7),
+                revoked_at: None,
+                device_info: None,
+            };
+            storage.store_token(&token).await.unwrap();
+            assert_eq!(storage.purge_expired_tokens(now).await.unwrap(), 1);
+            user.id
+        };
+
+        let storage = MemoryStorage::open(&path).unwrap();
+        assert!(storage.get_user_by_id(user_id).await.unwrap().is_some());
+        assert!(storage.get_token_by_hash("expired").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_purge_is_replayed() {
+        let dir = TempDir::new();
+        let path = dir.snapshot_path();
+
+        let (gone, kept) = {
+            let storage = MemoryStorage::open(&path).unwrap();
+            let gone = storage.create_user(&user_request("gina"), "hash").await.unwrap();
+            storage.create_account(gone.id).await.unwrap();
+            let kept = storage.create_user(&user_request("hank"), "hash").await.unwrap();
+            storage.soft_delete_user(gone.id).await.unwrap();
+            assert_eq!(storage.purge_deleted_users(Utc::now()).await.unwrap(), 1);
+            // Deleted after the purge's cutoff, so replay must leave it alone.
+            storage.soft_delete_user(kept.id).await.unwrap();
+            (gone.id, kept.id)
+        };
+
+        let storage = MemoryStorage::open(&path).unwrap();
+        assert!(storage.get_account_by_user_id(gone).await.unwrap().is_none());
+        assert!(matches!(storage.restore_user(gone).await, Err(DbError::NotFound)));
+        assert_eq!(storage.restore_user(kept).await.unwrap().id, kept);
+    }
+
+    #[tokio::test]
+    async fn test_admin_removal_is_replayed() {
+        let dir = TempDir::new();
+        let path = dir.snapshot_path();
+
+        let user_id = {
+            let storage = MemoryStorage::open(&path).unwrap();
+            seed_admin(&storage, "root@example.com", "root", "hash").await.unwrap();
+            let user = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+            storage.delete_admin(user.id).await.unwrap();
+            user.id
+        };
+
+        let storage = MemoryStorage::open(&path).unwrap();
+        assert!(!storage.is_admin(user_id).await.unwrap());
+        assert!(storage.get_user_by_id(user_id).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_unknown_snapshot_version_is_rejected() {
+        let dir = TempDir::new();
+        let path = dir.snapshot_path();
+        fs::write(
+            &path,
+            r#"{"version":99,"taken_at":"2026-01-01T00:00:00Z","users":[],"accounts":[],"admins":[],"tokens":[],"login_events":[]}"#,
+        )
+        .unwrap();
+
+        assert!(MemoryStorage::open(&path).is_err());
+    }
+}
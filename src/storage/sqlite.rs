@@ -0,0 +1,874 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteExecutor};
+use uuid::Uuid;
+
+use super::{DbError, PoolStats, PoolConfig, StorageLayer, Transaction, like_pattern};
+use super::migrations::{self, MigrationStatus};
+use crate::users::model::{
+    User, CreateUserRequest, UpdateUserRequest, UserFilter, UserSort, UserSortField, SortDirection, PageRequest,
+    UserPage, UserSummary,
+};
+use crate::auth::model::{UserAccount, CreateAccountRequest, UpdateAccountRequest, LoginEvent};
+use crate::admin::model::{Admin, CreateAdminRequest, UpdateAdminRequest};
+use crate::validation::model::AuthToken;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+const USER_COLUMNS: &str = "id, email, password_hash, username, first_name, last_name,
+                            is_active, created_at, updated_at, last_login, deleted_at, version";
+const ACCOUNT_COLUMNS: &str = "id, user_id, account_level, account_status, capabilities,
+                               status_reason, status_changed_at, status_changed_by, created_at, updated_at,
+                               version";
+const ADMIN_COLUMNS: &str = "id, user_id, role, permissions, created_at, updated_at, created_by, version";
+const TOKEN_COLUMNS: &str = "id, user_id, token_hash, token_type, expires_at, created_at, revoked_at, device_info";
+const LOGIN_EVENT_COLUMNS: &str = "id, user_id, email, success, failure_reason, ip_address,
+                                   user_agent, auth_method, device_fingerprint, created_at";
+
+/// SQLite-backed storage for small self-hosted installs and local development.
+///
+/// UUIDs are stored as hyphenated TEXT, enums as their lowercase TEXT values
+/// and string arrays (capabilities, permissions) as JSON TEXT.
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn from_url(database_url: &str) -> Result<Self, DbError> {
+        Self::connect(database_url, &PoolConfig::default()).await
+    }
+
+    pub async fn connect(database_url: &str, config: &PoolConfig) -> Result<Self, DbError> {
+        let options = SqliteConnectOptions::from_str(database_url)
+            .map_err(|e| DbError::Connection(e.to_string()))?
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        // Every connection to `:memory:` opens its own empty database, so an
+        // in-memory store must stay on a single connection.
+        let max_connections = if database_url.contains(":memory:") { 1 } else { config.max_connections };
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .min_connections(config.min_connections.min(max_connections))
+            .acquire_timeout(config.acquire_timeout)
+            .connect_with(options)
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?;
+        Ok(Self { pool })
+    }
+
+    /// Runs pending migrations on one connection with foreign key enforcement
+    /// off, as SQLite requires for migrations that rebuild a table others
+    /// reference. The keys are checked again once every migration has run.
+    pub async fn run_migrations(&self) -> Result<(), DbError> {
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await?;
+
+        let result = MIGRATOR
+            .run(&mut *conn)
+            .await
+            .map_err(|e| DbError::Migration(e.to_string()));
+
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await?;
+        result?;
+
+        let violations = sqlx::query("PRAGMA foreign_key_check").fetch_all(&mut *conn).await?;
+        if !violations.is_empty() {
+            return Err(DbError::Migration(format!(
+                "{} foreign key violations after migrating",
+                violations.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Every migration this build knows about and whether it has been run.
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>, DbError> {
+        let (has_table,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let applied: Vec<i64> = if has_table {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            Vec::new()
+        };
+
+        Ok(migrations::statuses(&MIGRATOR, &applied))
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    /// The error for a version-checked update that matched no row: `Conflict`
+    /// if `exists` (which selects the row by id alone) still finds it.
+    async fn missed_update(&self, exists: &str, id: Uuid) -> DbError {
+        missed_update(&self.pool, exists, id).await
+    }
+}
+
+// Row mapping
+
+fn decode_err(e: impl std::fmt::Display) -> DbError {
+    DbError::Query(format!("Failed to decode row: {}", e))
+}
+
+fn get_uuid(row: &SqliteRow, column: &str) -> Result<Uuid, DbError> {
+    let value: String = row.try_get(column)?;
+    Uuid::parse_str(&value).map_err(decode_err)
+}
+
+fn get_opt_uuid(row: &SqliteRow, column: &str) -> Result<Option<Uuid>, DbError> {
+    let value: Option<String> = row.try_get(column)?;
+    value
+        .map(|v| Uuid::parse_str(&v).map_err(decode_err))
+        .transpose()
+}
+
+fn get_list(row: &SqliteRow, column: &str) -> Result<Vec<String>, DbError> {
+    let value: String = row.try_get(column)?;
+    serde_json::from_str(&value).map_err(decode_err)
+}
+
+fn encode_list(values: &[String]) -> String {
+    serde_json::to_string(values).unwrap_or_else(|_| "[]".to_string())
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<User, DbError> {
+    Ok(User {
+        id: get_uuid(row, "id")?,
+        email: row.try_get("email")?,
+        password_hash: row.try_get("password_hash")?,
+        username: row.try_get("username")?,
+        first_name: row.try_get("first_name")?,
+        last_name: row.try_get("last_name")?,
+        is_active: row.try_get("is_active")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        last_login: row.try_get("last_login")?,
+        deleted_at: row.try_get("deleted_at")?,
+        version: row.try_get("version")?,
+    })
+}
+
+fn account_from_row(row: &SqliteRow) -> Result<UserAccount, DbError> {
+    Ok(UserAccount {
+        id: get_uuid(row, "id")?,
+        user_id: get_uuid(row, "user_id")?,
+        account_level: row.try_get("account_level")?,
+        account_status: row.try_get("account_status")?,
+        capabilities: get_list(row, "capabilities")?,
+        status_reason: row.try_get("status_reason")?,
+        status_changed_at: row.try_get("status_changed_at")?,
+        status_changed_by: get_opt_uuid(row, "status_changed_by")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        version: row.try_get("version")?,
+    })
+}
+
+fn admin_from_row(row: &SqliteRow) -> Result<Admin, DbError> {
+    Ok(Admin {
+        id: get_uuid(row, "id")?,
+        user_id: get_uuid(row, "user_id")?,
+        role: row.try_get("role")?,
+        permissions: get_list(row, "permissions")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        created_by: get_opt_uuid(row, "created_by")?,
+        version: row.try_get("version")?,
+    })
+}
+
+fn token_from_row(row: &SqliteRow) -> Result<AuthToken, DbError> {
+    Ok(AuthToken {
+        id: get_uuid(row, "id")?,
+        user_id: get_uuid(row, "user_id")?,
+        token_hash: row.try_get("token_hash")?,
+        token_type: row.try_get("token_type")?,
+        expires_at: row.try_get("expires_at")?,
+        created_at: row.try_get("created_at")?,
+        revoked_at: row.try_get("revoked_at")?,
+        device_info: row.try_get("device_info")?,
+    })
+}
+
+fn login_event_from_row(row: &SqliteRow) -> Result<LoginEvent, DbError> {
+    Ok(LoginEvent {
+        id: get_uuid(row, "id")?,
+        user_id: get_opt_uuid(row, "user_id")?,
+        email: row.try_get("email")?,
+        success: row.try_get("success")?,
+        failure_reason: row.try_get("failure_reason")?,
+        ip_address: row.try_get("ip_address")?,
+        user_agent: row.try_get("user_agent")?,
+        auth_method: row.try_get("auth_method")?,
+        device_fingerprint: row.try_get("device_fingerprint")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+// Queries shared by the pool and by transactions
+
+async fn fetch_user_by_email<'e, E: SqliteExecutor<'e>>(executor: E, email: &str) -> Result<Option<User>, DbError> {
+    let row = sqlx::query(&format!("SELECT {} FROM users WHERE email = $1 AND deleted_at IS NULL", USER_COLUMNS))
+        .bind(email)
+        .fetch_optional(executor)
+        .await?;
+
+    row.as_ref().map(user_from_row).transpose()
+}
+
+async fn insert_user<'e, E: SqliteExecutor<'e>>(
+    executor: E,
+    req: &CreateUserRequest,
+    password_hash: &str,
+) -> Result<User, DbError> {
+    let now = Utc::now();
+    let row = sqlx::query(&format!(
+        "INSERT INTO users (id, email, password_hash, username, first_name, last_name,
+                            created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $7)
+         RETURNING {}",
+        USER_COLUMNS
+    ))
+    .bind(Uuid::new_v4().to_string())
+    .bind(&req.email)
+    .bind(password_hash)
+    .bind(&req.username)
+    .bind(&req.first_name)
+    .bind(&req.last_name)
+    .bind(now)
+    .fetch_one(executor)
+    .await?;
+
+    user_from_row(&row)
+}
+
+async fn insert_account<'e, E: SqliteExecutor<'e>>(
+    executor: E,
+    req: &CreateAccountRequest,
+) -> Result<UserAccount, DbError> {
+    let now = Utc::now();
+    let row = sqlx::query(&format!(
+        "INSERT INTO user_accounts (id, user_id, account_level, account_status, capabilities,
+                                    created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $6)
+         RETURNING {}",
+        ACCOUNT_COLUMNS
+    ))
+    .bind(Uuid::new_v4().to_string())
+    .bind(req.user_id.to_string())
+    .bind(&req.account_level)
+    .bind(&req.account_status)
+    .bind(encode_list(&req.capabilities))
+    .bind(now)
+    .fetch_one(executor)
+    .await?;
+
+    account_from_row(&row)
+}
+
+async fn insert_admin<'e, E: SqliteExecutor<'e>>(
+    executor: E,
+    req: &CreateAdminRequest,
+) -> Result<Admin, DbError> {
+    let now = Utc::now();
+    let row = sqlx::query(&format!(
+        "INSERT INTO admins (id, user_id, role, permissions, created_at, updated_at, created_by)
+         VALUES ($1, $2, $3, $4, $5, $5, $6)
+         RETURNING {}",
+        ADMIN_COLUMNS
+    ))
+    .bind(Uuid::new_v4().to_string())
+    .bind(req.user_id.to_string())
+    .bind(&req.role)
+    .bind(encode_list(&req.permissions))
+    .bind(now)
+    .bind(req.created_by.map(|id| id.to_string()))
+    .fetch_one(executor)
+    .await?;
+
+    admin_from_row(&row)
+}
+
+async fn fetch_superadmin_ids<'e, E: SqliteExecutor<'e>>(executor: E) -> Result<Vec<Uuid>, DbError> {
+    let ids: Vec<String> = sqlx::query_scalar(
+        "SELECT a.user_id FROM admins a JOIN users u ON u.id = a.user_id
+         WHERE a.role = 'superadmin' AND u.deleted_at IS NULL"
+    )
+    .fetch_all(executor)
+    .await?;
+
+    ids.iter().map(|id| Uuid::parse_str(id).map_err(decode_err)).collect()
+}
+
+/// The version-checked update; `None` if no row matched.
+async fn update_admin_row<'e, E: SqliteExecutor<'e>>(
+    executor: E,
+    user_id: Uuid,
+    changes: &UpdateAdminRequest,
+    expected_version: i64,
+) -> Result<Option<Admin>, DbError> {
+    let row = sqlx::query(&format!(
+        "UPDATE admins SET role = COALESCE($3, role),
+                           permissions = COALESCE($4, permissions),
+                           updated_at = $5,
+                           version = version + 1
+         WHERE user_id = $1 AND version = $2
+         RETURNING {}",
+        ADMIN_COLUMNS
+    ))
+    .bind(user_id.to_string())
+    .bind(expected_version)
+    .bind(&changes.role)
+    .bind(changes.permissions.as_deref().map(encode_list))
+    .bind(Utc::now())
+    .fetch_optional(executor)
+    .await?;
+
+    row.map(|row| admin_from_row(&row)).transpose()
+}
+
+/// The error for a version-checked update that matched no row: `Conflict`
+/// if `exists` (which selects the row by id alone) still finds it.
+async fn missed_update<'e, E: SqliteExecutor<'e>>(executor: E, exists: &str, id: Uuid) -> DbError {
+    match sqlx::query(exists).bind(id.to_string()).fetch_optional(executor).await {
+        Ok(Some(_)) => DbError::Conflict,
+        Ok(None) => DbError::NotFound,
+        Err(e) => e.into(),
+    }
+}
+
+pub struct SqliteTransaction {
+    tx: sqlx::Transaction<'static, Sqlite>,
+}
+
+#[async_trait]
+impl Transaction for SqliteTransaction {
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<User>, DbError> {
+        fetch_user_by_email(&mut *self.tx, email).await
+    }
+
+    async fn create_user(&mut self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
+        insert_user(&mut *self.tx, req, password_hash).await
+    }
+
+    async fn create_account(&mut self, req: &CreateAccountRequest) -> Result<UserAccount, DbError> {
+        insert_account(&mut *self.tx, req).await
+    }
+
+    async fn create_admin(&mut self, req: &CreateAdminRequest) -> Result<Admin, DbError> {
+        insert_admin(&mut *self.tx, req).await
+    }
+
+    async fn superadmin_ids(&mut self) -> Result<Vec<Uuid>, DbError> {
+        fetch_superadmin_ids(&mut *self.tx).await
+    }
+
+    async fn update_admin(&mut self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        match update_admin_row(&mut *self.tx, user_id, changes, expected_version).await? {
+            Some(admin) => Ok(admin),
+            None => Err(missed_update(&mut *self.tx, "SELECT 1 FROM admins WHERE user_id = $1", user_id).await),
+        }
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), DbError> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DbError> {
+        self.tx.rollback().await?;
+        Ok(())
+    }
+}
+
+/// Appends the `FROM ... WHERE ...` part shared by the page and count queries.
+/// LIKE is case-insensitive for ASCII in SQLite, so it stands in for ILIKE.
+fn push_user_filter<'a>(qb: &mut QueryBuilder<'a, Sqlite>, filter: &'a UserFilter) {
+    qb.push(" FROM users u LEFT JOIN user_accounts a ON a.user_id = u.id");
+    qb.push(if filter.deleted { " WHERE u.deleted_at IS NOT NULL" } else { " WHERE u.deleted_at IS NULL" });
+
+    if let Some(status) = &filter.status {
+        qb.push(" AND a.account_status = ").push_bind(status);
+    }
+    if let Some(level) = &filter.account_level {
+        qb.push(" AND a.account_level = ").push_bind(level);
+    }
+    if let Some(after) = filter.created_after {
+        qb.push(" AND u.created_at >= ").push_bind(after);
+    }
+    if let Some(before) = filter.created_before {
+        qb.push(" AND u.created_at < ").push_bind(before);
+    }
+    if let Some(term) = filter.search_term() {
+        let pattern = like_pattern(term);
+        qb.push(" AND (u.email LIKE ").push_bind(pattern.clone())
+            .push(" ESCAPE '\\' OR u.username LIKE ").push_bind(pattern.clone())
+            .push(" ESCAPE '\\' OR (u.first_name || ' ' || u.last_name) LIKE ").push_bind(pattern)
+            .push(" ESCAPE '\\')");
+    }
+}
+
+fn order_by(sort: UserSort) -> String {
+    let column = match sort.field {
+        UserSortField::CreatedAt => "u.created_at",
+        UserSortField::Email => "u.email",
+        UserSortField::Username => "u.username",
+    };
+    let direction = match sort.direction {
+        SortDirection::Asc => "ASC",
+        SortDirection::Desc => "DESC",
+    };
+    format!(" ORDER BY {} {}, u.id {}", column, direction, direction)
+}
+
+#[async_trait]
+impl StorageLayer for SqliteStorage {
+    async fn health_check(&self) -> bool {
+        sqlx::query("SELECT 1")
+            .fetch_one(&self.pool)
+            .await
+            .is_ok()
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, DbError> {
+        SqliteStorage::migration_status(self).await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max_connections: self.pool.options().get_max_connections(),
+        })
+    }
+
+    async fn close(&self) -> Result<(), DbError> {
+        self.pool.close().await;
+        Ok(())
+    }
+
+    async fn begin(&self) -> Result<Box<dyn Transaction + '_>, DbError> {
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(SqliteTransaction { tx }))
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
+        fetch_user_by_email(&self.pool, email).await
+    }
+
+    async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, DbError> {
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE id = $1 AND deleted_at IS NULL", USER_COLUMNS))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, DbError> {
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE username = $1 AND deleted_at IS NULL", USER_COLUMNS))
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    async fn create_user(&self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError> {
+        insert_user(&self.pool, req, password_hash).await
+    }
+
+    async fn update_user_last_login(&self, user_id: Uuid) -> Result<(), DbError> {
+        let now = Utc::now();
+        let result = sqlx::query("UPDATE users SET last_login = $1, updated_at = $1 WHERE id = $2 AND deleted_at IS NULL")
+            .bind(now)
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn update_user(&self, user_id: Uuid, changes: &UpdateUserRequest, expected_version: i64) -> Result<User, DbError> {
+        let row = sqlx::query(&format!(
+            "UPDATE users SET email = COALESCE($3, email),
+                              username = COALESCE($4, username),
+                              first_name = COALESCE($5, first_name),
+                              last_name = COALESCE($6, last_name),
+                              is_active = COALESCE($7, is_active),
+                              password_hash = COALESCE($9, password_hash),
+                              updated_at = $8,
+                              version = version + 1
+             WHERE id = $1 AND version = $2 AND deleted_at IS NULL
+             RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(user_id.to_string())
+        .bind(expected_version)
+        .bind(&changes.email)
+        .bind(&changes.username)
+        .bind(&changes.first_name)
+        .bind(&changes.last_name)
+        .bind(changes.is_active)
+        .bind(Utc::now())
+        .bind(&changes.password_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => user_from_row(&row),
+            None => Err(self.missed_update("SELECT 1 FROM users WHERE id = $1 AND deleted_at IS NULL", user_id).await),
+        }
+    }
+
+    async fn soft_delete_user(&self, user_id: Uuid) -> Result<(), DbError> {
+        let now = Utc::now();
+        let result = sqlx::query(
+            "UPDATE users SET deleted_at = $1, updated_at = $1, version = version + 1
+             WHERE id = $2 AND deleted_at IS NULL"
+        )
+        .bind(now)
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn restore_user(&self, user_id: Uuid) -> Result<User, DbError> {
+        let row = sqlx::query(&format!(
+            "UPDATE users SET deleted_at = NULL, updated_at = $1, version = version + 1
+             WHERE id = $2 AND deleted_at IS NOT NULL
+             RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(Utc::now())
+        .bind(user_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(user_from_row).transpose()?.ok_or(DbError::NotFound)
+    }
+
+    async fn purge_deleted_users(&self, before: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM users WHERE deleted_at <= $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn list_users(&self, filter: &UserFilter, sort: UserSort, page: PageRequest) -> Result<UserPage, DbError> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*)");
+        push_user_filter(&mut count, filter);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.pool).await?;
+
+        let mut query = QueryBuilder::new(
+            "SELECT u.id, u.email, u.password_hash, u.username, u.first_name, u.last_name,
+                    u.is_active, u.created_at, u.updated_at, u.last_login, u.deleted_at, u.version,
+                    a.account_level, a.account_status",
+        );
+        push_user_filter(&mut query, filter);
+        query.push(order_by(sort));
+        query.push(" LIMIT ").push_bind(page.per_page);
+        query.push(" OFFSET ").push_bind(page.offset());
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let users = rows
+            .iter()
+            .map(|row| {
+                Ok(UserSummary {
+                    user: user_from_row(row)?,
+                    account_level: row.try_get("account_level")?,
+                    account_status: row.try_get("account_status")?,
+                })
+            })
+            .collect::<Result<Vec<_>, DbError>>()?;
+
+        Ok(UserPage { users, total })
+    }
+
+    async fn get_account_by_user_id(&self, user_id: Uuid) -> Result<Option<UserAccount>, DbError> {
+        let row = sqlx::query(&format!("SELECT {} FROM user_accounts WHERE user_id = $1", ACCOUNT_COLUMNS))
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(account_from_row).transpose()
+    }
+
+    async fn create_account(&self, user_id: Uuid) -> Result<UserAccount, DbError> {
+        insert_account(&self.pool, &CreateAccountRequest::default_for(user_id)).await
+    }
+
+    async fn update_account(&self, user_id: Uuid, changes: &UpdateAccountRequest, expected_version: i64) -> Result<UserAccount, DbError> {
+        let row = sqlx::query(&format!(
+            "UPDATE user_accounts SET
+                 account_level = COALESCE($3, account_level),
+                 capabilities = COALESCE($4, capabilities),
+                 account_status = COALESCE($5, account_status),
+                 status_reason = CASE WHEN $5 IS NULL THEN status_reason ELSE $6 END,
+                 status_changed_at = CASE WHEN $5 IS NULL THEN status_changed_at ELSE $8 END,
+                 status_changed_by = CASE WHEN $5 IS NULL THEN status_changed_by ELSE $7 END,
+                 updated_at = $8,
+                 version = version + 1
+             WHERE user_id = $1 AND version = $2
+             RETURNING {}",
+            ACCOUNT_COLUMNS
+        ))
+        .bind(user_id.to_string())
+        .bind(expected_version)
+        .bind(&changes.account_level)
+        .bind(changes.capabilities.as_deref().map(encode_list))
+        .bind(&changes.account_status)
+        .bind(&changes.status_reason)
+        .bind(changes.changed_by.map(|id| id.to_string()))
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => account_from_row(&row),
+            None => Err(self.missed_update("SELECT 1 FROM user_accounts WHERE user_id = $1", user_id).await),
+        }
+    }
+
+    async fn get_admin_by_user_id(&self, user_id: Uuid) -> Result<Option<Admin>, DbError> {
+        let row = sqlx::query(&format!("SELECT {} FROM admins WHERE user_id = $1", ADMIN_COLUMNS))
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(admin_from_row).transpose()
+    }
+
+    async fn is_admin(&self, user_id: Uuid) -> Result<bool, DbError> {
+        let (exists,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM admins WHERE user_id = $1)"
+        )
+        .bind(user_id.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    async fn has_superadmin(&self) -> Result<bool, DbError> {
+        let (exists,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM admins a JOIN users u ON u.id = a.user_id
+                           WHERE a.role = 'superadmin' AND u.deleted_at IS NULL)"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    async fn update_admin(&self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError> {
+        match update_admin_row(&self.pool, user_id, changes, expected_version).await? {
+            Some(admin) => Ok(admin),
+            None => Err(self.missed_update("SELECT 1 FROM admins WHERE user_id = $1", user_id).await),
+        }
+    }
+
+    async fn delete_admin(&self, user_id: Uuid) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM admins WHERE user_id = $1")
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn store_token(&self, token: &AuthToken) -> Result<(), DbError> {
+        sqlx::query(
+            "INSERT INTO auth_tokens (id, user_id, token_hash, token_type, expires_at, created_at,
+                                      revoked_at, device_info)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+        )
+        .bind(token.id.to_string())
+        .bind(token.user_id.to_string())
+        .bind(&token.token_hash)
+        .bind(&token.token_type)
+        .bind(token.expires_at)
+        .bind(token.created_at)
+        .bind(token.revoked_at)
+        .bind(&token.device_info)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_token_by_hash(&self, token_hash: &str) -> Result<Option<AuthToken>, DbError> {
+        let row = sqlx::query(&format!("SELECT {} FROM auth_tokens WHERE token_hash = $1", TOKEN_COLUMNS))
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(token_from_row).transpose()
+    }
+
+    async fn revoke_token(&self, token_hash: &str) -> Result<(), DbError> {
+        sqlx::query("UPDATE auth_tokens SET revoked_at = $1 WHERE token_hash = $2")
+            .bind(Utc::now())
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_all_user_tokens(&self, user_id: Uuid) -> Result<(), DbError> {
+        sqlx::query("UPDATE auth_tokens SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL")
+            .bind(Utc::now())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_expired_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM auth_tokens WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn count_active_tokens(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM auth_tokens WHERE revoked_at IS NULL AND expires_at > $1")
+                .bind(now)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count as u64)
+    }
+
+    async fn purge_expired_validation_keys(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let result = sqlx::query("DELETE FROM validation_keys WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn record_login_event(&self, event: &LoginEvent) -> Result<(), DbError> {
+        sqlx::query(
+            "INSERT INTO login_events (id, user_id, email, success, failure_reason, ip_address,
+                                       user_agent, auth_method, device_fingerprint, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
+        )
+        .bind(event.id.to_string())
+        .bind(event.user_id.map(|id| id.to_string()))
+        .bind(&event.email)
+        .bind(event.success)
+        .bind(&event.failure_reason)
+        .bind(&event.ip_address)
+        .bind(&event.user_agent)
+        .bind(&event.auth_method)
+        .bind(&event.device_fingerprint)
+        .bind(event.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_login_events(&self, user_id: Uuid, limit: i64) -> Result<Vec<LoginEvent>, DbError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM login_events WHERE user_id = $1
+             ORDER BY created_at DESC
+             LIMIT $2",
+            LOGIN_EVENT_COLUMNS
+        ))
+        .bind(user_id.to_string())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(login_event_from_row).collect()
+    }
+
+    async fn is_known_device(&self, user_id: Uuid, fingerprint: &str) -> Result<bool, DbError> {
+        let (known,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(
+                SELECT 1 FROM login_events
+                WHERE user_id = $1 AND device_fingerprint = $2 AND success
+             )"
+        )
+        .bind(user_id.to_string())
+        .bind(fingerprint)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(known)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::model::AdminRole;
+    use crate::auth::model::AccountStatus;
+    use crate::storage::seed_admin;
+
+    async fn storage() -> SqliteStorage {
+        let storage = SqliteStorage::from_url("sqlite::memory:").await.unwrap();
+        storage.run_migrations().await.unwrap();
+        storage
+    }
+
+    #[tokio::test]
+    async fn test_seeded_admin_round_trips() {
+        let storage = storage().await;
+        seed_admin(&storage, "admin@example.com", "admin", "hash").await.unwrap();
+
+        let user = storage.get_user_by_email("admin@example.com").await.unwrap().unwrap();
+        let account = storage.get_account_by_user_id(user.id).await.unwrap().unwrap();
+        let admin = storage.get_admin_by_user_id(user.id).await.unwrap().unwrap();
+
+        assert_eq!(account.account_status, AccountStatus::Active);
+        assert!(account.capabilities.contains(&"api_access".to_string()));
+        assert!(matches!(admin.role, AdminRole::SuperAdmin));
+        assert_eq!(admin.permissions, vec!["*".to_string()]);
+        assert!(storage.is_admin(user.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_migration_status_tracks_applied_migrations() {
+        let storage = SqliteStorage::from_url("sqlite::memory:").await.unwrap();
+        let pending = storage.migration_status().await.unwrap();
+        assert!(!pending.is_empty());
+        assert!(pending.iter().all(|m| !m.applied));
+
+        storage.run_migrations().await.unwrap();
+        let applied = storage.migration_status().await.unwrap();
+        assert_eq!(applied.len(), pending.len());
+        assert!(applied.iter().all(|m| m.applied));
+    }
+}
@@ -0,0 +1,115 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::{DbError, StorageLayer};
+use crate::users::model::{User, CreateUserRequest};
+use crate::auth::model::{UserAccount, CreateAccountRequest, AccountLevel, AccountStatus};
+use crate::admin::model::{Admin, AdminRole, CreateAdminRequest, UpdateAdminRequest};
+
+pub type TxFuture<'t, T> = Pin<Box<dyn Future<Output = Result<T, DbError>> + Send + 't>>;
+
+/// A unit of work against the store. Nothing written through a transaction is
+/// visible to other callers until `commit`; dropping it without committing
+/// discards every write.
+#[async_trait]
+pub trait Transaction: Send {
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<User>, DbError>;
+    async fn create_user(&mut self, req: &CreateUserRequest, password_hash: &str) -> Result<User, DbError>;
+    async fn create_account(&mut self, req: &CreateAccountRequest) -> Result<UserAccount, DbError>;
+    async fn create_admin(&mut self, req: &CreateAdminRequest) -> Result<Admin, DbError>;
+    /// Users who are superadmins and aren't deleted. A concurrent change to
+    /// that set makes this transaction fail rather than commit on stale data.
+    async fn superadmin_ids(&mut self) -> Result<Vec<Uuid>, DbError>;
+    /// Like `StorageLayer::update_admin`.
+    async fn update_admin(&mut self, user_id: Uuid, changes: &UpdateAdminRequest, expected_version: i64) -> Result<Admin, DbError>;
+
+    async fn commit(self: Box<Self>) -> Result<(), DbError>;
+    async fn rollback(self: Box<Self>) -> Result<(), DbError>;
+}
+
+impl dyn StorageLayer + '_ {
+    /// Runs `f` inside a transaction, committing if it returns `Ok` and rolling
+    /// back if it returns `Err`.
+    ///
+    /// ```ignore
+    /// let user = storage.transaction(|tx| Box::pin(async move {
+    ///     let user = tx.create_user(&req, &hash).await?;
+    ///     tx.create_account(&CreateAccountRequest::default_for(user.id)).await?;
+    ///     Ok(user)
+    /// })).await?;
+    /// ```
+    pub async fn transaction<T, F>(&self, f: F) -> Result<T, DbError>
+    where
+        T: Send,
+        F: for<'t> FnOnce(&'t mut dyn Transaction) -> TxFuture<'t, T> + Send,
+    {
+        let mut tx = self.begin().await?;
+        match f(&mut *tx).await {
+            Ok(value) => {
+                tx.commit().await?;
+                Ok(value)
+            }
+            Err(err) => {
+                let _ = tx.rollback().await;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Creates a superadmin with an active enterprise account, unless a user with
+/// `email` already exists. All three records are written atomically.
+pub async fn seed_admin(
+    storage: &dyn StorageLayer,
+    email: &str,
+    username: &str,
+    password_hash: &str,
+) -> Result<(), DbError> {
+    let email = email.to_string();
+    let username = username.to_string();
+    let password_hash = password_hash.to_string();
+
+    storage
+        .transaction(|tx| {
+            Box::pin(async move {
+                if tx.get_user_by_email(&email).await?.is_some() {
+                    return Ok(());
+                }
+
+                let user = tx
+                    .create_user(
+                        &CreateUserRequest {
+                            email: email.clone(),
+                            password: String::new(),
+                            username,
+                            first_name: "Admin".to_string(),
+                            last_name: "User".to_string(),
+                        },
+                        &password_hash,
+                    )
+                    .await?;
+
+                tx.create_account(&CreateAccountRequest {
+                    user_id: user.id,
+                    account_level: AccountLevel::Enterprise,
+                    account_status: AccountStatus::Active,
+                    capabilities: AccountLevel::Enterprise.default_capabilities(),
+                })
+                .await?;
+
+                tx.create_admin(&CreateAdminRequest {
+                    user_id: user.id,
+                    role: AdminRole::SuperAdmin,
+                    permissions: vec!["*".to_string()],
+                    created_by: None,
+                })
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await
+}
@@ -0,0 +1,51 @@
+//! Fixtures for tests that drive the whole app.
+
+use crate::app::AppConfig;
+use crate::auth::model::{AccountStatus, UpdateAccountRequest};
+use crate::auth::TokenService;
+use crate::scheduler::SchedulerConfig;
+use crate::storage::{seed_admin, StorageLayer};
+use crate::users::model::{CreateUserRequest, User};
+
+/// The password of users made by `active_user`.
+pub const PASSWORD: &str = "Correct-horse-9";
+
+/// The default config without the maintenance scheduler.
+pub fn config() -> AppConfig {
+    AppConfig {
+        scheduler: SchedulerConfig { enabled: false, ..SchedulerConfig::default() },
+        ..AppConfig::default()
+    }
+}
+
+/// Creates `<username>@example.com` with an active account and `PASSWORD`.
+pub async fn active_user(storage: &dyn StorageLayer, username: &str) -> User {
+    let req = CreateUserRequest {
+        email: format!("{}@example.com", username),
+        password: PASSWORD.to_string(),
+        username: username.to_string(),
+        first_name: "Test".to_string(),
+        last_name: "User".to_string(),
+    };
+    let user = storage.create_user(&req, &bcrypt::hash(PASSWORD, 4).unwrap()).await.unwrap();
+    let account = storage.create_account(user.id).await.unwrap();
+    let activate = UpdateAccountRequest { account_status: Some(AccountStatus::Active), ..Default::default() };
+    storage.update_account(user.id, &activate, account.version).await.unwrap();
+    user
+}
+
+/// Seeds `root@example.com` as a superadmin and returns an access token for
+/// it that an app built from `config` accepts.
+pub async fn superadmin_token(storage: &dyn StorageLayer, config: &AppConfig) -> String {
+    seed_admin(storage, "root@example.com", "root", "hash").await.unwrap();
+    let root = storage.get_user_by_email("root@example.com").await.unwrap().unwrap();
+    let account = storage.get_account_by_user_id(root.id).await.unwrap().unwrap();
+    let admin = storage.get_admin_by_user_id(root.id).await.unwrap().unwrap();
+
+    let tokens = TokenService::with_ttl(
+        config.token.jwt_secret.clone(),
+        config.token.access_ttl_minutes,
+        config.token.refresh_ttl_days,
+    );
+    tokens.generate_admin_tokens(&root, &account, &admin).unwrap().access_token
+}
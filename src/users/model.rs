@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+use crate::auth::model::{AccountLevel, AccountStatus};
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
@@ -15,6 +16,13 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
+    /// Set while the user is soft-deleted; lookups skip such users.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Bumped by every edit (but not by logins); updates must name the
+    /// version they were based on.
+    #[serde(default)]
+    pub version: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -26,10 +34,17 @@ pub struct CreateUserRequest {
     pub last_name: String,
 }
 
-#[derive(Debug, Serialize)]
+/// Changes for `StorageLayer::update_user`. Unset fields are left as they are.
+#[derive(Debug, Default, Deserialize)]
 pub struct UpdateUserRequest {
     pub email: Option<String>,
     pub username: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub is_active: Option<bool>,
+    /// Already hashed; never taken from a request body.
+    #[serde(skip)]
+    pub password_hash: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -51,6 +66,101 @@ pub struct UserLoginResponse {
     pub per_page: i32,
 }
 
+pub const DEFAULT_PER_PAGE: i64 = 20;
+pub const MAX_PER_PAGE: i64 = 100;
+
+/// Criteria for `StorageLayer::list_users`. Unset fields match everything.
+/// `status` and `account_level` only match users that have an account.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UserFilter {
+    pub status: Option<AccountStatus>,
+    pub account_level: Option<AccountLevel>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Case-insensitive substring match on email, username, first or last name.
+    pub search: Option<String>,
+    /// List soft-deleted users instead of live ones.
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+impl UserFilter {
+    /// The search term with surrounding whitespace removed, if non-empty.
+    pub fn search_term(&self) -> Option<&str> {
+        self.search.as_deref().map(str::trim).filter(|s| !s.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSortField {
+    #[default]
+    CreatedAt,
+    Email,
+    Username,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Ordering for `list_users`. Ties are broken by user id so pages are stable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserSort {
+    pub field: UserSortField,
+    pub direction: SortDirection,
+}
+
+/// Offset pagination; `page` is 1-based.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageRequest {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl PageRequest {
+    pub fn new(page: Option<i64>, per_page: Option<i64>) -> Self {
+        Self {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    pub fn total_pages(&self, total: i64) -> i64 {
+        ((total + self.per_page - 1) / self.per_page).max(1)
+    }
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+/// A user together with the level and status of their account, if any.
+#[derive(Debug, Clone)]
+pub struct UserSummary {
+    pub user: User,
+    pub account_level: Option<AccountLevel>,
+    pub account_status: Option<AccountStatus>,
+}
+
+/// One page of `list_users` results. `total` counts every match, not just
+/// the users on this page.
+#[derive(Debug, Clone)]
+pub struct UserPage {
+    pub users: Vec<UserSummary>,
+    pub total: i64,
+}
+
 // Conversions
 impl From<User> for UserProfile {
     fn from(user: User) -> Self {
@@ -79,3 +189,14 @@ impl From<&User> for UserProfile {
         }
     }
 }
+
+impl UserLoginResponse {
+    pub fn from_page(page: UserPage, request: PageRequest) -> Self {
+        UserLoginResponse {
+            users: page.users.into_iter().map(|s| UserProfile::from(s.user)).collect(),
+            total: page.total,
+            page: request.page as i32,
+            per_page: request.per_page as i32,
+        }
+    }
+}
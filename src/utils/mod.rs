@@ -1 +1,91 @@
-// Utility functions will go here
+use std::net::IpAddr;
+
+use axum::http::HeaderMap;
+
+/// The client address. Forwarding headers are only believed when the socket
+/// peer is one of `trusted_proxies`, and `X-Forwarded-For` is then read from
+/// the right, skipping trusted hops, so a client can't pick its address by
+/// sending the header itself. Values that aren't addresses are ignored in
+/// favour of the peer.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let hops: Option<Vec<IpAddr>> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .flat_map(|h| h.to_str().unwrap_or_default().split(','))
+        .map(|hop| hop.trim().parse().ok())
+        .collect();
+    let Some(hops) = hops else {
+        return Some(peer);
+    };
+    if let Some(hop) = hops.into_iter().rev().find(|hop| !trusted_proxies.contains(hop)) {
+        return Some(hop);
+    }
+
+    let real_ip = headers
+        .get("X-Real-IP")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.trim().parse().ok());
+    Some(real_ip.unwrap_or(peer))
+}
+
+pub fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// The caller-supplied `X-Request-Id`, if any.
+pub fn request_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("X-Request-Id")
+        .and_then(|h| h.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_forwarded_headers_are_ignored_from_untrusted_peers() {
+        let forged = headers(&[("X-Forwarded-For", "1.2.3.4"), ("X-Real-IP", "5.6.7.8")]);
+        assert_eq!(client_ip(&forged, Some(ip("203.0.113.9")), &[]), Some(ip("203.0.113.9")));
+        assert_eq!(client_ip(&forged, None, &[ip("10.0.0.1")]), None);
+    }
+
+    #[test]
+    fn test_forwarded_for_is_read_from_the_right_past_trusted_hops() {
+        let proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+        let forwarded = headers(&[("X-Forwarded-For", "1.2.3.4, 198.51.100.7, 10.0.0.2")]);
+        assert_eq!(client_ip(&forwarded, Some(ip("10.0.0.1")), &proxies), Some(ip("198.51.100.7")));
+
+        let real_ip = headers(&[("X-Real-IP", "198.51.100.7")]);
+        assert_eq!(client_ip(&real_ip, Some(ip("10.0.0.1")), &proxies), Some(ip("198.51.100.7")));
+    }
+
+    #[test]
+    fn test_unparseable_forwarded_values_fall_back_to_the_peer() {
+        let proxies = [ip("10.0.0.1")];
+        let junk = headers(&[("X-Forwarded-For", "not-an-ip, 198.51.100.7")]);
+        assert_eq!(client_ip(&junk, Some(ip("10.0.0.1")), &proxies), Some(ip("10.0.0.1")));
+    }
+}
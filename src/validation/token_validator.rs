@@ -1,6 +1,6 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::sync::RwLock;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use super::model::{AuthToken, ValidationKey, TokenValidation, TokenType};
@@ -8,7 +8,11 @@ use super::model::{AuthToken, ValidationKey, TokenValidation, TokenType};
 pub struct ValidationStore {
     keys: RwLock<HashMap<Uuid, ValidationKey>>,
     tokens: RwLock<HashMap<String, AuthToken>>,
-    blacklisted_jtis: RwLock<HashSet<Uuid>>,
+    /// Revoked access token ids, kept until the token would have expired.
+    blacklisted_jtis: RwLock<HashMap<Uuid, DateTime<Utc>>>,
+    /// Access token ids issued to each user, so they can all be revoked at
+    /// once. Also kept until the tokens would have expired.
+    issued_jtis: RwLock<HashMap<Uuid, HashMap<Uuid, DateTime<Utc>>>>,
 }
 
 impl Default for ValidationStore {
@@ -22,21 +26,52 @@ impl ValidationStore {
         Self {
             keys: RwLock::new(HashMap::new()),
             tokens: RwLock::new(HashMap::new()),
-            blacklisted_jtis: RwLock::new(HashSet::new()),
+            blacklisted_jtis: RwLock::new(HashMap::new()),
+            issued_jtis: RwLock::new(HashMap::new()),
         }
     }
 
-    pub fn blacklist_jti(&self, jti: Uuid) {
+    pub fn blacklist_jti(&self, jti: Uuid, expires_at: DateTime<Utc>) {
         let mut blacklist = self.blacklisted_jtis.write().unwrap();
-        blacklist.insert(jti);
+        blacklist.insert(jti, expires_at);
     }
 
     pub fn is_jti_blacklisted(&self, jti: &Uuid) -> bool {
         let blacklist = self.blacklisted_jtis.read().unwrap();
-        blacklist.contains(jti)
+        blacklist.contains_key(jti)
     }
 
-    pub fn cleanup_blacklist(&self) {
+    pub fn record_issued_jti(&self, user_id: Uuid, jti: Uuid, expires_at: DateTime<Utc>) {
+        let mut issued = self.issued_jtis.write().unwrap();
+        issued.entry(user_id).or_default().insert(jti, expires_at);
+    }
+
+    /// Blacklists every unexpired access token issued to `user_id`, returning
+    /// how many there were.
+    pub fn blacklist_user(&self, user_id: Uuid) -> usize {
+        let now = Utc::now();
+        let issued = self.issued_jtis.write().unwrap().remove(&user_id).unwrap_or_default();
+        let mut blacklist = self.blacklisted_jtis.write().unwrap();
+        let before = blacklist.len();
+        blacklist.extend(issued.into_iter().filter(|(_, expires_at)| *expires_at > now));
+        blacklist.len() - before
+    }
+
+    /// Drops blacklisted ids whose tokens have expired anyway, returning how
+    /// many were removed.
+    pub fn cleanup_blacklist(&self) -> usize {
+        let now = Utc::now();
+        {
+            let mut issued = self.issued_jtis.write().unwrap();
+            issued.retain(|_, jtis| {
+                jtis.retain(|_, expires_at| *expires_at > now);
+                !jtis.is_empty()
+            });
+        }
+        let mut blacklist = self.blacklisted_jtis.write().unwrap();
+        let before = blacklist.len();
+        blacklist.retain(|_, expires_at| *expires_at > now);
+        before - blacklist.len()
     }
 
     pub fn store_key(&self, key: ValidationKey) {
@@ -116,14 +151,19 @@ impl ValidationStore {
         }
     }
 
-    pub fn cleanup_expired(&self) {
+    /// Drops expired keys and tokens, returning how many of each were removed.
+    pub fn cleanup_expired(&self) -> (usize, usize) {
         let now = Utc::now();
 
         let mut keys = self.keys.write().unwrap();
+        let keys_before = keys.len();
         keys.retain(|_, key| key.expires_at > now);
 
         let mut tokens = self.tokens.write().unwrap();
+        let tokens_before = tokens.len();
         tokens.retain(|_, token| token.expires_at > now);
+
+        (keys_before - keys.len(), tokens_before - tokens.len())
     }
 
     pub fn revoke_all_user_tokens(&self, user_id: Uuid) {